@@ -11,13 +11,14 @@
 //!
 
 use std::time::Duration;
-use std::vec::IntoIter as VecIntoIter;
 
+use crate::source::SeekError;
 use crate::{Sample, Source};
 
 /// A buffer of samples treated as a source.
 pub struct SamplesBuffer<S> {
-    data: VecIntoIter<S>,
+    data: Vec<S>,
+    pos: usize,
     channels: u16,
     sample_rate: u32,
     duration: Duration,
@@ -53,7 +54,8 @@ where
         );
 
         SamplesBuffer {
-            data: data.into_iter(),
+            data,
+            pos: 0,
             channels,
             sample_rate,
             duration,
@@ -84,6 +86,15 @@ where
     fn total_duration(&self) -> Option<Duration> {
         Some(self.duration)
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let frame = (pos.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize;
+        self.pos = frame
+            .saturating_mul(self.channels as usize)
+            .min(self.data.len());
+        Ok(())
+    }
 }
 
 impl<S> Iterator for SamplesBuffer<S>
@@ -94,17 +105,24 @@ where
 
     #[inline]
     fn next(&mut self) -> Option<S> {
-        self.data.next()
+        let sample = self.data.get(self.pos).copied();
+        if sample.is_some() {
+            self.pos += 1;
+        }
+        sample
     }
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.data.size_hint()
+        let len = self.data.len() - self.pos;
+        (len, Some(len))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use crate::buffer::SamplesBuffer;
     use crate::source::Source;
 
@@ -144,4 +162,16 @@ mod tests {
         assert_eq!(buf.next(), Some(6));
         assert_eq!(buf.next(), None);
     }
+
+    #[test]
+    fn seek() {
+        let mut buf = SamplesBuffer::new(2, 2, vec![1i16, 2, 3, 4, 5, 6, 7, 8]);
+        buf.try_seek(Duration::from_secs(1)).unwrap();
+        assert_eq!(buf.next(), Some(5));
+        buf.try_seek(Duration::from_millis(500)).unwrap();
+        assert_eq!(buf.next(), Some(3));
+        assert_eq!(buf.next(), Some(4));
+        buf.try_seek(Duration::from_secs(10)).unwrap();
+        assert_eq!(buf.next(), None);
+    }
 }
@@ -1,3 +1,214 @@
+use crate::conversions::Sample;
+use crate::source::{ChannelLayout, Speaker};
+use cpal::Sample as CpalSample;
+
+/// Gain of a channel mixed into two others, -3 dB, so that the power of the sound is kept.
+const HALF_POWER: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Gain of the low-frequency effects channel in a stereo downmix, -6 dB.
+const LFE_GAIN: f32 = 0.5;
+
+/// How the channels of a sound are converted when the output has a different channel count.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelMixing {
+    /// Drops the extra channels, or repeats the last one. Cheap, but the content of the dropped
+    /// channels is lost, and a mono sound only plays on the first channel of a surround output.
+    Simple,
+    /// Mixes the channels with the standard matrices of `ChannelMatrix::from_layouts`, or
+    /// `ChannelMatrix::standard` if the layout of the source is unknown.
+    Standard,
+    /// Mixes the channels with the matrices whose channel counts match, and with the standard
+    /// matrices otherwise.
+    Custom(Vec<ChannelMatrix>),
+}
+
+impl ChannelMixing {
+    /// Returns the matrix to convert from `from` channels to `to` channels, or `None` if the
+    /// channels are dropped or repeated.
+    pub fn matrix(&self, from: u16, to: u16) -> Option<ChannelMatrix> {
+        match self {
+            ChannelMixing::Simple => None,
+            ChannelMixing::Standard if from == to => None,
+            ChannelMixing::Standard => Some(ChannelMatrix::standard(from, to)),
+            ChannelMixing::Custom(matrices) => matrices
+                .iter()
+                .find(|m| m.from == from && m.to == to)
+                .cloned()
+                .or_else(|| ChannelMixing::Standard.matrix(from, to)),
+        }
+    }
+
+    /// Returns the matrix to convert from the layout `from` to the layout `to`, or `None` if
+    /// the channels are dropped or repeated.
+    pub fn layout_matrix(&self, from: ChannelLayout, to: ChannelLayout) -> Option<ChannelMatrix> {
+        match self {
+            ChannelMixing::Simple => None,
+            ChannelMixing::Standard if from == to => None,
+            ChannelMixing::Standard => Some(ChannelMatrix::from_layouts(from, to)),
+            ChannelMixing::Custom(matrices) => matrices
+                .iter()
+                .find(|m| m.from == from.channels() && m.to == to.channels())
+                .cloned()
+                .or_else(|| ChannelMixing::Standard.layout_matrix(from, to)),
+        }
+    }
+}
+
+impl Default for ChannelMixing {
+    #[inline]
+    fn default() -> ChannelMixing {
+        ChannelMixing::Simple
+    }
+}
+
+/// Gains applied to each input channel to compute each output channel.
+///
+/// The channels are in the order of WAV files: front left, front right, front center,
+/// low-frequency effects, back left, back right, side left, side right.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelMatrix {
+    from: u16,
+    to: u16,
+    // `to` rows of `from` gains.
+    gains: Vec<f32>,
+}
+
+impl ChannelMatrix {
+    /// Builds a matrix from its gains. The gain of the input channel `i` in the output channel
+    /// `o` is `gains[o * from + i]`.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `from` or `to` are equal to 0, or if there aren't `from * to` gains.
+    ///
+    pub fn new(from: u16, to: u16, gains: Vec<f32>) -> ChannelMatrix {
+        assert!(from >= 1);
+        assert!(to >= 1);
+        assert_eq!(gains.len(), from as usize * to as usize);
+        ChannelMatrix { from, to, gains }
+    }
+
+    /// Returns the standard matrix to convert from `from` channels to `to` channels, assuming
+    /// the layouts of `ChannelLayout::default_for`. See `ChannelMatrix::from_layouts`.
+    ///
+    /// The channels are copied as they are for the channel counts without a usual layout, the
+    /// extra ones being dropped or silent.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `from` or `to` are equal to 0.
+    ///
+    pub fn standard(from: u16, to: u16) -> ChannelMatrix {
+        assert!(from >= 1);
+        assert!(to >= 1);
+
+        if let (Some(from), Some(to)) = (
+            ChannelLayout::default_for(from),
+            ChannelLayout::default_for(to),
+        ) {
+            return ChannelMatrix::from_layouts(from, to);
+        }
+
+        let (f, t) = (from as usize, to as usize);
+        let mut gains = vec![0.0; f * t];
+        for channel in 0..f.min(t) {
+            gains[channel * f + channel] = 1.0;
+        }
+        ChannelMatrix { from, to, gains }
+    }
+
+    /// Returns the matrix to convert from the layout `from` to the layout `to`.
+    ///
+    /// - The channels whose speaker is in both layouts are copied.
+    /// - The back and side surround channels replace each other.
+    /// - The other channels are mixed into the front left and front right channels, with the
+    ///   center and surround channels at -3 dB and the low-frequency effects at -6 dB. In
+    ///   particular, mono is played at -3 dB on the two front channels, unless there is a center
+    ///   channel.
+    /// - Mixing down to a single channel averages the two channels of the stereo mix.
+    pub fn from_layouts(from: ChannelLayout, to: ChannelLayout) -> ChannelMatrix {
+        let (f, t) = (from.channels() as usize, to.channels() as usize);
+        let mut gains = vec![0.0; f * t];
+        let mut add = |output: u16, input: usize, gain: f32| {
+            gains[output as usize * f + input] += gain;
+        };
+
+        for (input, speaker) in from.speakers().enumerate() {
+            let (left, right) = stereo_downmix(speaker);
+            if t == 1 {
+                add(0, input, (left + right) / 2.0);
+            } else if let Some(output) = to.channel_of(speaker) {
+                add(output, input, 1.0);
+            } else if let Some(output) = surround_pair(speaker).and_then(|s| to.channel_of(s)) {
+                add(output, input, 1.0);
+            } else {
+                if let Some(output) = to.channel_of(Speaker::FrontLeft) {
+                    add(output, input, left);
+                }
+                if let Some(output) = to.channel_of(Speaker::FrontRight) {
+                    add(output, input, right);
+                }
+            }
+        }
+        ChannelMatrix {
+            from: f as u16,
+            to: t as u16,
+            gains,
+        }
+    }
+
+    /// Returns the number of input channels.
+    #[inline]
+    pub fn from_channels(&self) -> u16 {
+        self.from
+    }
+
+    /// Returns the number of output channels.
+    #[inline]
+    pub fn to_channels(&self) -> u16 {
+        self.to
+    }
+
+    /// Returns the gain of the input channel `input` in the output channel `output`.
+    #[inline]
+    pub fn gain(&self, output: u16, input: u16) -> f32 {
+        self.gains[output as usize * self.from as usize + input as usize]
+    }
+}
+
+/// Returns the gains of a speaker in the left and right channels of a stereo mix.
+fn stereo_downmix(speaker: Speaker) -> (f32, f32) {
+    const H: f32 = HALF_POWER;
+    match speaker {
+        Speaker::FrontLeft | Speaker::FrontLeftOfCenter => (1.0, 0.0),
+        Speaker::FrontRight | Speaker::FrontRightOfCenter => (0.0, 1.0),
+        Speaker::FrontCenter => (H, H),
+        Speaker::LowFrequency => (LFE_GAIN, LFE_GAIN),
+        Speaker::BackLeft | Speaker::SideLeft | Speaker::TopFrontLeft | Speaker::TopBackLeft => {
+            (H, 0.0)
+        }
+        Speaker::BackRight
+        | Speaker::SideRight
+        | Speaker::TopFrontRight
+        | Speaker::TopBackRight => (0.0, H),
+        Speaker::BackCenter
+        | Speaker::TopCenter
+        | Speaker::TopFrontCenter
+        | Speaker::TopBackCenter => (0.5, 0.5),
+    }
+}
+
+/// Returns the speaker that plays the same surround channel in the other surround layout.
+fn surround_pair(speaker: Speaker) -> Option<Speaker> {
+    match speaker {
+        Speaker::BackLeft => Some(Speaker::SideLeft),
+        Speaker::BackRight => Some(Speaker::SideRight),
+        Speaker::SideLeft => Some(Speaker::BackLeft),
+        Speaker::SideRight => Some(Speaker::BackRight),
+        _ => None,
+    }
+}
+
 /// Iterator that converts from a certain channel count to another.
 #[derive(Clone, Debug)]
 pub struct ChannelCountConverter<I>
@@ -9,6 +220,11 @@ where
     to: cpal::ChannelCount,
     sample_repeat: Option<I::Item>,
     next_output_sample_pos: cpal::ChannelCount,
+    /// The matrix to mix the channels with, instead of dropping or repeating them.
+    matrix: Option<ChannelMatrix>,
+    /// The output frame being returned, if the channels are mixed.
+    output: Vec<f32>,
+    output_pos: usize,
 }
 
 impl<I> ChannelCountConverter<I>
@@ -36,32 +252,137 @@ where
             to,
             sample_repeat: None,
             next_output_sample_pos: 0,
+            matrix: None,
+            output: Vec::new(),
+            output_pos: 0,
         }
     }
 
-    /// Destroys this iterator and returns the underlying iterator.
+    /// Initializes the iterator, mixing the channels as requested.
+    ///
+    /// # Panic
+    ///
+    /// Panicks if `from` or `to` are equal to 0.
+    ///
+    #[inline]
+    pub fn with_mixing(
+        input: I,
+        from: cpal::ChannelCount,
+        to: cpal::ChannelCount,
+        mixing: &ChannelMixing,
+    ) -> ChannelCountConverter<I> {
+        let mut converter = ChannelCountConverter::new(input, from, to);
+        converter.set_input_channels(from, mixing.matrix(from, to));
+        converter
+    }
+
+    /// Initializes the iterator, mixing the channels as requested according to their speaker
+    /// positions.
+    #[inline]
+    pub fn with_layouts(
+        input: I,
+        from: ChannelLayout,
+        to: ChannelLayout,
+        mixing: &ChannelMixing,
+    ) -> ChannelCountConverter<I> {
+        let mut converter = ChannelCountConverter::new(input, from.channels(), to.channels());
+        converter.set_input_channels(from.channels(), mixing.layout_matrix(from, to));
+        converter
+    }
+
+    /// Returns a mutable reference to the underlying iterator.
     #[inline]
-    pub fn into_inner(self) -> I {
-        self.input
+    pub(crate) fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Forgets the frame being converted, so that the conversion starts again from the next
+    /// sample of the input.
+    #[inline]
+    pub(crate) fn reset(&mut self) {
+        self.sample_repeat = None;
+        self.next_output_sample_pos = 0;
+        self.output.clear();
+        self.output_pos = 0;
+    }
+
+    /// Changes the channel count of the input, and the matrix to mix its channels with.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `from` is equal to 0, or if the matrix doesn't convert from `from` channels.
+    ///
+    pub(crate) fn set_input_channels(
+        &mut self,
+        from: cpal::ChannelCount,
+        matrix: Option<ChannelMatrix>,
+    ) {
+        assert!(from >= 1);
+        if let Some(matrix) = &matrix {
+            assert_eq!((matrix.from, matrix.to), (from, self.to));
+            // Allocated now rather than when the first frame is mixed.
+            self.output.reserve(self.to as usize);
+        }
+        self.from = from;
+        self.matrix = matrix;
+        self.reset();
+    }
+}
+
+impl<I> ChannelCountConverter<I>
+where
+    I: Iterator,
+    I::Item: Sample,
+{
+    /// Reads the next frame of the input and mixes it into `output`. Returns `false` if the
+    /// input ended before the end of the frame.
+    fn mix_frame(&mut self) -> bool {
+        let matrix = match &self.matrix {
+            Some(matrix) => matrix,
+            None => return false,
+        };
+        let from = self.from as usize;
+        self.output.clear();
+        self.output.resize(self.to as usize, 0.0);
+        self.output_pos = 0;
+        for input in 0..from {
+            let sample = match self.input.next() {
+                Some(sample) => sample.to_f32(),
+                None => return false,
+            };
+            for (output, gains) in self.output.iter_mut().zip(matrix.gains.chunks(from)) {
+                *output += sample * gains[input];
+            }
+        }
+        true
     }
 }
 
 impl<I> Iterator for ChannelCountConverter<I>
 where
     I: Iterator,
-    I::Item: Clone,
+    I::Item: Sample,
 {
     type Item = I::Item;
 
     fn next(&mut self) -> Option<I::Item> {
+        if self.matrix.is_some() {
+            if self.output_pos >= self.output.len() && !self.mix_frame() {
+                return None;
+            }
+            let sample = self.output[self.output_pos];
+            self.output_pos += 1;
+            return Some(CpalSample::from(&sample));
+        }
+
         let result = if self.next_output_sample_pos == self.from - 1 {
             let value = self.input.next();
-            self.sample_repeat = value.clone();
+            self.sample_repeat = value;
             value
         } else if self.next_output_sample_pos < self.from {
             self.input.next()
         } else {
-            self.sample_repeat.clone()
+            self.sample_repeat
         };
 
         self.next_output_sample_pos += 1;
@@ -83,6 +404,13 @@ where
     fn size_hint(&self) -> (usize, Option<usize>) {
         let (min, max) = self.input.size_hint();
 
+        if self.matrix.is_some() {
+            let remaining = self.output.len() - self.output_pos;
+            let apply =
+                |samples: usize| (samples / self.from as usize) * self.to as usize + remaining;
+            return (apply(min), max.map(apply));
+        }
+
         let min =
             (min / self.from as usize) * self.to as usize + self.next_output_sample_pos as usize;
         let max = max.map(|max| {
@@ -96,13 +424,25 @@ where
 impl<I> ExactSizeIterator for ChannelCountConverter<I>
 where
     I: ExactSizeIterator,
-    I::Item: Clone,
+    I::Item: Sample,
 {
 }
 
 #[cfg(test)]
 mod test {
-    use super::ChannelCountConverter;
+    use super::{ChannelCountConverter, ChannelMatrix, ChannelMixing};
+    use crate::source::{ChannelLayout, Speaker};
+
+    fn mix(input: Vec<f32>, from: u16, to: u16, mixing: ChannelMixing) -> Vec<f32> {
+        ChannelCountConverter::with_mixing(input.into_iter(), from, to, &mixing).collect()
+    }
+
+    fn assert_close(output: &[f32], expected: &[f32]) {
+        assert_eq!(output.len(), expected.len());
+        for (o, e) in output.iter().zip(expected) {
+            assert!((o - e).abs() < 1e-4, "{:?} != {:?}", output, expected);
+        }
+    }
 
     #[test]
     fn remove_channels() {
@@ -139,4 +479,93 @@ mod test {
         let output = ChannelCountConverter::new(input.into_iter(), 2, 1);
         assert_eq!(output.len(), 2);
     }
+
+    #[test]
+    fn simple_mixing_drops_and_repeats() {
+        let output = mix(vec![1.0, 2.0, 3.0, 4.0], 2, 3, ChannelMixing::Simple);
+        assert_eq!(output, [1.0, 2.0, 2.0, 3.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    fn surround_to_stereo() {
+        let input = vec![1.0, 2.0, 0.5, 0.25, 0.1, 0.2];
+        let output = mix(input, 6, 2, ChannelMixing::Standard);
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(
+            &output,
+            &[
+                1.0 + 0.5 * h + 0.25 * 0.5 + 0.1 * h,
+                2.0 + 0.5 * h + 0.25 * 0.5 + 0.2 * h,
+            ],
+        );
+    }
+
+    #[test]
+    fn mono_to_stereo() {
+        let output = mix(vec![1.0, 0.5], 1, 2, ChannelMixing::Standard);
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(&output, &[h, h, 0.5 * h, 0.5 * h]);
+
+        let output = mix(vec![1.0], 1, 6, ChannelMixing::Standard);
+        assert_close(&output, &[0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn stereo_to_mono() {
+        let output = mix(vec![1.0, 0.5, -1.0, 1.0], 2, 1, ChannelMixing::Standard);
+        assert_close(&output, &[0.75, 0.0]);
+    }
+
+    #[test]
+    fn custom_matrix() {
+        // Swaps the two channels.
+        let swap = ChannelMatrix::new(2, 2, vec![0.0, 1.0, 1.0, 0.0]);
+        let mixing = ChannelMixing::Custom(vec![swap]);
+        let output = mix(vec![1.0, 2.0, 3.0, 4.0], 2, 2, mixing.clone());
+        assert_close(&output, &[2.0, 1.0, 4.0, 3.0]);
+
+        // Other channel counts use the standard matrices.
+        let output = mix(vec![1.0, 0.5], 2, 1, mixing);
+        assert_close(&output, &[0.75]);
+    }
+
+    #[test]
+    fn routes_by_speaker() {
+        // Front left, front right, side left, side right.
+        let from = ChannelLayout::from_speakers(&[
+            Speaker::FrontLeft,
+            Speaker::FrontRight,
+            Speaker::SideLeft,
+            Speaker::SideRight,
+        ]);
+        let input = vec![0.1f32, 0.2, 0.3, 0.4];
+        let output: Vec<f32> = ChannelCountConverter::with_layouts(
+            input.into_iter(),
+            from,
+            ChannelLayout::SURROUND_5_1,
+            &ChannelMixing::Standard,
+        )
+        .collect();
+        // The side channels are played on the back speakers of 5.1.
+        assert_close(&output, &[0.1, 0.2, 0.0, 0.0, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn single_speaker_to_stereo() {
+        let from = ChannelLayout::from_speakers(&[Speaker::FrontLeft]);
+        let matrix = ChannelMatrix::from_layouts(from, ChannelLayout::STEREO);
+        assert_eq!(matrix.gain(0, 0), 1.0);
+        assert_eq!(matrix.gain(1, 0), 0.0);
+    }
+
+    #[test]
+    fn incomplete_frame_is_dropped() {
+        let input = vec![1000i16, 2000, 3000];
+        let output =
+            ChannelCountConverter::with_mixing(input.into_iter(), 2, 1, &ChannelMixing::Standard);
+        assert_eq!(output.len(), 1);
+        let output = output.collect::<Vec<_>>();
+        assert_eq!(output.len(), 1);
+        assert!((output[0] - 1500).abs() <= 1);
+    }
 }
@@ -5,10 +5,10 @@ This includes conversion between sample formats, channels or sample rates.
 
 */
 
-pub use self::channels::ChannelCountConverter;
+pub use self::channels::{ChannelCountConverter, ChannelMatrix, ChannelMixing};
 pub use self::sample::DataConverter;
 pub use self::sample::Sample;
-pub use self::sample_rate::SampleRateConverter;
+pub use self::sample_rate::{ResampleQuality, SampleRateConverter};
 
 mod channels;
 // TODO: < shouldn't be public ; there's a bug in Rust 1.4 and below that makes This
@@ -18,10 +18,10 @@ impl<I, O> DataConverter<I, O> {
         }
     }
 
-    /// Destroys this iterator and returns the underlying iterator.
+    /// Returns a mutable reference to the underlying iterator.
     #[inline]
-    pub fn into_inner(self) -> I {
-        self.input
+    pub(crate) fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
     }
 }
 
@@ -1,7 +1,42 @@
 use crate::conversions::Sample;
+use cpal::Sample as CpalSample;
 
+use std::f32::consts::PI;
 use std::mem;
 
+/// Number of zero crossings of the sinc function on each side of the center of the filters.
+const SINC_ZERO_CROSSINGS: usize = 16;
+
+/// Fraction of the Nyquist frequency kept by the sinc filters, which leaves room for their
+/// transition band.
+const SINC_ROLLOFF: f32 = 0.95;
+
+/// Maximum number of sinc filters that are computed in advance, one per position of the output
+/// frames between two input frames. Above this, the filters are computed for each frame.
+const SINC_MAX_PHASES: u32 = 1024;
+
+/// Number of input frames that the sinc resampler keeps before dropping the ones it doesn't
+/// need anymore.
+const SINC_MAX_KEPT_FRAMES: u64 = 4096;
+
+/// Algorithm used to convert the sample rate of a sound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Linear interpolation between two frames. Cheap, but adds audible aliasing, especially to
+    /// music played at 44.1kHz on a 48kHz device, or to upsampled voice.
+    Linear,
+    /// Band-limited interpolation with windowed-sinc filters. Much cleaner, but several times
+    /// more expensive.
+    Sinc,
+}
+
+impl Default for ResampleQuality {
+    #[inline]
+    fn default() -> ResampleQuality {
+        ResampleQuality::Linear
+    }
+}
+
 /// Iterator that converts from a certain sample rate to another.
 #[derive(Clone, Debug)]
 pub struct SampleRateConverter<I>
@@ -27,6 +62,12 @@ where
     next_output_frame_pos_in_chunk: u32,
     /// The buffer containing the samples waiting to be output.
     output_buffer: Vec<I::Item>,
+    /// Whether `current_frame` and `next_frame` have been read from the input since the last
+    /// restart. They are read by the first call to `next`, so that building the converter
+    /// doesn't read the input.
+    frames_read: bool,
+    /// The state of the sinc interpolation, used instead of the fields above if selected.
+    sinc: Option<SincResampler>,
 }
 
 impl<I> SampleRateConverter<I>
@@ -41,12 +82,61 @@ where
     /// Panics if `from` or `to` are equal to 0.
     ///
     #[inline]
+    #[cfg(test)]
     pub fn new(
-        mut input: I,
+        input: I,
         from: cpal::SampleRate,
         to: cpal::SampleRate,
         num_channels: cpal::ChannelCount,
     ) -> SampleRateConverter<I> {
+        SampleRateConverter::with_quality(input, from, to, num_channels, ResampleQuality::Linear)
+    }
+
+    /// Builds a converter that uses the given algorithm.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `from` or `to` are equal to 0.
+    ///
+    pub fn with_quality(
+        input: I,
+        from: cpal::SampleRate,
+        to: cpal::SampleRate,
+        num_channels: cpal::ChannelCount,
+        quality: ResampleQuality,
+    ) -> SampleRateConverter<I> {
+        let mut converter = SampleRateConverter {
+            input,
+            from: 1,
+            to: 1,
+            channels: num_channels,
+            current_frame_pos_in_chunk: 0,
+            next_output_frame_pos_in_chunk: 0,
+            current_frame: Vec::new(),
+            next_frame: Vec::new(),
+            output_buffer: Vec::new(),
+            frames_read: false,
+            sinc: None,
+        };
+        converter.restart(from, to, num_channels, quality);
+        converter
+    }
+
+    /// Starts converting again from the next sample of the input, which may have changed its
+    /// format. The buffers are kept, so that this doesn't allocate unless they must grow or new
+    /// sinc filters are needed.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `from` or `to` are equal to 0.
+    ///
+    pub(crate) fn restart(
+        &mut self,
+        from: cpal::SampleRate,
+        to: cpal::SampleRate,
+        num_channels: cpal::ChannelCount,
+        quality: ResampleQuality,
+    ) {
         let from = from.0;
         let to = to.0;
 
@@ -67,39 +157,44 @@ where
             gcd(from, to)
         };
 
-        let (first_samples, next_samples) = if from == to {
-            // if `from` == `to` == 1, then we just pass through
-            debug_assert_eq!(from, gcd);
-            (Vec::new(), Vec::new())
-        } else {
-            let first = input
-                .by_ref()
-                .take(num_channels as usize)
-                .collect::<Vec<_>>();
-            let next = input
-                .by_ref()
-                .take(num_channels as usize)
-                .collect::<Vec<_>>();
-            (first, next)
+        let (from, to) = (from / gcd, to / gcd);
+        let channels = num_channels as usize;
+
+        self.sinc = match (quality, self.sinc.take()) {
+            (ResampleQuality::Sinc, _) if from == to => None,
+            (ResampleQuality::Sinc, Some(mut sinc))
+                if (sinc.from, sinc.to, sinc.channels) == (from, to, channels) =>
+            {
+                sinc.reset();
+                Some(sinc)
+            }
+            (ResampleQuality::Sinc, _) => Some(SincResampler::new(from, to, channels)),
+            (ResampleQuality::Linear, _) => None,
         };
 
-        SampleRateConverter {
-            input,
-            from: from / gcd,
-            to: to / gcd,
-            channels: num_channels,
-            current_frame_pos_in_chunk: 0,
-            next_output_frame_pos_in_chunk: 0,
-            current_frame: first_samples,
-            next_frame: next_samples,
-            output_buffer: Vec::with_capacity(num_channels as usize - 1),
+        self.from = from;
+        self.to = to;
+        self.channels = num_channels;
+        self.current_frame_pos_in_chunk = 0;
+        self.next_output_frame_pos_in_chunk = 0;
+        self.current_frame.clear();
+        self.next_frame.clear();
+        self.output_buffer.clear();
+        // The three buffers are swapped with each other.
+        for buffer in [
+            &mut self.current_frame,
+            &mut self.next_frame,
+            &mut self.output_buffer,
+        ] {
+            buffer.reserve(channels);
         }
+        self.frames_read = false;
     }
 
-    /// Destroys this iterator and returns the underlying iterator.
+    /// Returns a mutable reference to the underlying iterator.
     #[inline]
-    pub fn into_inner(self) -> I {
-        self.input
+    pub(crate) fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
     }
 
     fn next_input_frame(&mut self) {
@@ -131,6 +226,18 @@ where
             return self.input.next();
         }
 
+        if let Some(sinc) = &mut self.sinc {
+            return sinc.next(&mut self.input);
+        }
+
+        if !self.frames_read {
+            let channels = self.channels as usize;
+            self.current_frame
+                .extend(self.input.by_ref().take(channels));
+            self.next_frame.extend(self.input.by_ref().take(channels));
+            self.frames_read = true;
+        }
+
         // Short circuit if there are some samples waiting.
         if !self.output_buffer.is_empty() {
             return Some(self.output_buffer.remove(0));
@@ -203,12 +310,22 @@ where
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
         let apply = |samples: usize| {
+            // The first two frames are still in the input until `next` is called.
+            let (samples, next_frame_len) = if self.frames_read {
+                (samples, self.next_frame.len())
+            } else {
+                let channels = usize::from(self.channels);
+                let current_frame_len = samples.min(channels);
+                let next_frame_len = (samples - current_frame_len).min(channels);
+                (samples - current_frame_len - next_frame_len, next_frame_len)
+            };
+
             // `samples_after_chunk` will contain the number of samples remaining after the chunk
             // currently being processed
             let samples_after_chunk = samples;
             // adding the samples of the next chunk that may have already been read
             let samples_after_chunk = if self.current_frame_pos_in_chunk == self.from - 1 {
-                samples_after_chunk + self.next_frame.len()
+                samples_after_chunk + next_frame_len
             } else {
                 samples_after_chunk
             };
@@ -232,6 +349,8 @@ where
 
         if self.from == self.to {
             self.input.size_hint()
+        } else if let Some(sinc) = &self.sinc {
+            sinc.size_hint(self.input.size_hint())
         } else {
             let (min, max) = self.input.size_hint();
             (apply(min), max.map(apply))
@@ -246,12 +365,218 @@ where
 {
 }
 
+/// Converts the sample rate by convolving the input with windowed-sinc filters.
+///
+/// Output frame `k` is at input position `k * from / to`. Its value is the sum of the input
+/// frames around this position, weighted by a sinc filter centered on it. The cutoff of the
+/// filter is lowered when downsampling, so that the frequencies above the new Nyquist frequency
+/// are removed instead of folding back.
+#[derive(Clone, Debug)]
+struct SincResampler {
+    from: u32,
+    to: u32,
+    channels: usize,
+    /// Cutoff frequency of the filters, relative to the Nyquist frequency of the input.
+    cutoff: f32,
+    /// Number of input frames on each side of the position of an output frame.
+    half_width: usize,
+    /// The filters of all the phases, `2 * half_width` coefficients each, or nothing if there
+    /// are too many phases.
+    filters: Vec<f32>,
+    /// The filter of the current frame, if they aren't computed in advance.
+    scratch: Vec<f32>,
+    /// The input frames that may still be needed, interleaved.
+    frames: Vec<f32>,
+    /// Index of the first frame of `frames` in the input.
+    first_frame: u64,
+    /// Number of frames read from the input.
+    frames_read: u64,
+    input_ended: bool,
+    /// Index of the next output frame.
+    next_output: u64,
+    /// The output frame being returned.
+    output: Vec<f32>,
+    output_pos: usize,
+}
+
+impl SincResampler {
+    fn new(from: u32, to: u32, channels: usize) -> SincResampler {
+        let cutoff = SINC_ROLLOFF * (to as f32 / from as f32).min(1.0);
+        // When downsampling, the filter is wider to keep the same number of zero crossings.
+        let half_width = (SINC_ZERO_CROSSINGS as f32 / cutoff).ceil() as usize;
+
+        let mut resampler = SincResampler {
+            from,
+            to,
+            channels,
+            cutoff,
+            half_width,
+            filters: Vec::new(),
+            scratch: Vec::new(),
+            frames: Vec::with_capacity(
+                // The frames that are kept, and those read for the next output frame.
+                (SINC_MAX_KEPT_FRAMES as usize + 2 * half_width + (from / to) as usize + 2)
+                    * channels,
+            ),
+            first_frame: 0,
+            frames_read: 0,
+            input_ended: false,
+            next_output: 0,
+            output: Vec::with_capacity(channels),
+            output_pos: 0,
+        };
+        if to <= SINC_MAX_PHASES {
+            let mut filters = Vec::with_capacity(to as usize * 2 * half_width);
+            for phase in 0..to {
+                resampler.compute_filter(phase);
+                filters.extend_from_slice(&resampler.scratch);
+            }
+            resampler.filters = filters;
+        }
+        resampler
+    }
+
+    /// Forgets the input frames, so that the resampling starts again from the next sample of the
+    /// input.
+    fn reset(&mut self) {
+        self.frames.clear();
+        self.first_frame = 0;
+        self.frames_read = 0;
+        self.input_ended = false;
+        self.next_output = 0;
+        self.output.clear();
+        self.output_pos = 0;
+    }
+
+    /// Computes the filter for output frames that are `phase / to` frames after an input frame
+    /// into `scratch`.
+    fn compute_filter(&mut self, phase: u32) {
+        let half_width = self.half_width as f32;
+        let offset = phase as f32 / self.to as f32;
+        let cutoff = self.cutoff;
+
+        self.scratch.clear();
+        self.scratch.extend((0..2 * self.half_width).map(|tap| {
+            // Distance between the input frame and the position of the output frame.
+            let x = tap as f32 + 1.0 - half_width - offset;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * cutoff * x).sin() / (PI * cutoff * x)
+            };
+            // Blackman window.
+            let u = x / half_width;
+            let window = 0.42 + 0.5 * (PI * u).cos() + 0.08 * (2.0 * PI * u).cos();
+            sinc * window
+        }));
+
+        // Normalize the filter, so that a constant signal keeps its level.
+        let sum: f32 = self.scratch.iter().sum();
+        self.scratch.iter_mut().for_each(|c| *c /= sum);
+    }
+
+    /// Reads the input until it contains the frame of index `frame`, or until it ends.
+    fn read_until<I>(&mut self, input: &mut I, frame: u64)
+    where
+        I: Iterator,
+        I::Item: Sample,
+    {
+        while !self.input_ended && self.frames_read <= frame {
+            for _ in 0..self.channels {
+                match input.next() {
+                    Some(sample) => self.frames.push(sample.to_f32()),
+                    None => {
+                        // Drop the incomplete frame.
+                        let len = (self.frames_read - self.first_frame) as usize * self.channels;
+                        self.frames.truncate(len);
+                        self.input_ended = true;
+                        return;
+                    }
+                }
+            }
+            self.frames_read += 1;
+        }
+    }
+
+    fn next<I>(&mut self, input: &mut I) -> Option<I::Item>
+    where
+        I: Iterator,
+        I::Item: Sample,
+    {
+        if self.output_pos < self.output.len() {
+            let sample = self.output[self.output_pos];
+            self.output_pos += 1;
+            return Some(CpalSample::from(&sample));
+        }
+
+        let position = self.next_output * self.from as u64;
+        let frame = position / self.to as u64;
+        let phase = (position % self.to as u64) as u32;
+        let half_width = self.half_width as u64;
+
+        self.read_until(input, frame + half_width);
+        if self.input_ended && position >= self.frames_read * self.to as u64 {
+            return None;
+        }
+
+        // Drop the frames that the next output frames won't need.
+        let first_needed = (frame + 1).saturating_sub(half_width);
+        if first_needed - self.first_frame > SINC_MAX_KEPT_FRAMES {
+            let dropped = (first_needed - self.first_frame) as usize;
+            self.frames.drain(..dropped * self.channels);
+            self.first_frame = first_needed;
+        }
+
+        let filter = if self.filters.is_empty() {
+            self.compute_filter(phase);
+            &self.scratch[..]
+        } else {
+            let len = 2 * self.half_width;
+            &self.filters[phase as usize * len..(phase as usize + 1) * len]
+        };
+
+        self.output.clear();
+        self.output_pos = 0;
+        for channel in 0..self.channels {
+            let mut sum = 0.0;
+            for (tap, coefficient) in filter.iter().enumerate() {
+                // Frames before the start or after the end of the input are silent.
+                let index = (frame + tap as u64 + 1).checked_sub(half_width);
+                match index {
+                    Some(index) if index >= self.first_frame && index < self.frames_read => {
+                        let pos = (index - self.first_frame) as usize * self.channels + channel;
+                        sum += self.frames[pos] * coefficient;
+                    }
+                    _ => (),
+                }
+            }
+            self.output.push(sum);
+        }
+        self.next_output += 1;
+
+        self.output_pos = 1;
+        Some(CpalSample::from(&self.output[0]))
+    }
+
+    fn size_hint(&self, input: (usize, Option<usize>)) -> (usize, Option<usize>) {
+        let apply = |samples: usize| {
+            let frames = self.frames_read + (samples / self.channels) as u64;
+            let total = (frames * self.to as u64).div_ceil(self.from as u64);
+            total.saturating_sub(self.next_output) as usize * self.channels + self.output.len()
+                - self.output_pos
+        };
+        let (min, max) = input;
+        (apply(min), max.map(apply))
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::SampleRateConverter;
+    use super::{ResampleQuality, SampleRateConverter};
     use core::time::Duration;
     use cpal::SampleRate;
     use quickcheck::quickcheck;
+    use std::f32::consts::PI;
 
     // TODO: Remove once cpal 0.12.2 is released and the dependency is updated
     //  (cpal#483 implemented ops::Mul on SampleRate)
@@ -351,7 +676,7 @@ mod test {
             let duration =
                 Duration::from_secs_f32(resampled.count() as f32 / to.0 as f32);
 
-            let delta = if d < duration { duration - d } else { d - duration };
+            let delta = duration.max(d) - duration.min(d);
             assert!(delta < Duration::from_millis(1),
                     "Resampled duration ({:?}) is not close to original ({:?}); Δ = {:?}",
                     duration, d, delta);
@@ -368,4 +693,77 @@ mod test {
         let output = output.collect::<Vec<_>>();
         assert_eq!(output, [2, 16, 3, 17, 4, 18, 6, 20, 7, 21, 8, 22]);
     }
+
+    fn sinc(input: Vec<f32>, from: u32, to: u32, channels: u16) -> Vec<f32> {
+        SampleRateConverter::with_quality(
+            input.into_iter(),
+            SampleRate(from),
+            SampleRate(to),
+            channels,
+            ResampleQuality::Sinc,
+        )
+        .collect()
+    }
+
+    fn sine(freq: f32, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn sinc_preserves_duration() {
+        let output = sinc(vec![0.0; 44100], 44100, 48000, 1);
+        assert_eq!(output.len(), 48000);
+
+        let converter = SampleRateConverter::with_quality(
+            vec![0.0f32; 2 * 48000].into_iter(),
+            SampleRate(48000),
+            SampleRate(8000),
+            2,
+            ResampleQuality::Sinc,
+        );
+        assert_eq!(converter.len(), 2 * 8000);
+        assert_eq!(converter.count(), 2 * 8000);
+    }
+
+    #[test]
+    fn sinc_upsampling_is_accurate() {
+        let output = sinc(sine(1000.0, 44100, 44100), 44100, 48000, 1);
+        let expected = sine(1000.0, 48000, 48000);
+        // The edges are attenuated, as the filters reach outside of the input.
+        let error = output[100..47900]
+            .iter()
+            .zip(&expected[100..47900])
+            .fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+        assert!(error < 1e-3, "{}", error);
+    }
+
+    #[test]
+    fn sinc_downsampling_removes_aliases() {
+        // 7kHz is above the Nyquist frequency of 8kHz, and can't be represented.
+        let input = sine(7000.0, 48000, 48000);
+        let peak = |output: Vec<f32>| output[100..7900].iter().fold(0.0f32, |p, s| p.max(s.abs()));
+
+        let linear = SampleRateConverter::new(
+            input.clone().into_iter(),
+            SampleRate(48000),
+            SampleRate(8000),
+            1,
+        );
+        assert!(peak(linear.collect()) > 0.5);
+        assert!(peak(sinc(input, 48000, 8000, 1)) < 0.01);
+    }
+
+    #[test]
+    fn sinc_keeps_channels_apart() {
+        let input: Vec<f32> = sine(440.0, 8000, 800)
+            .into_iter()
+            .flat_map(|s| vec![s, 0.0])
+            .collect();
+        let output = sinc(input, 8000, 44100, 2);
+        assert_eq!(output.len(), 2 * 4410);
+        assert!(output.iter().step_by(2).any(|&s| s.abs() > 0.9));
+        assert!(output.iter().skip(1).step_by(2).all(|&s| s == 0.0));
+    }
 }
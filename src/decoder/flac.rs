@@ -1,61 +1,129 @@
-use std::cmp::Ordering;
 use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
 use std::mem;
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::Source;
 
+use super::metadata::{Metadata, Picture};
+use super::sample::DecoderSample;
+
 use claxon::FlacReader;
 
 /// Decoder for the Flac format.
-pub struct FlacDecoder<R>
+pub struct FlacDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    reader: FlacReader<R>,
+    // Only `None` if rewinding the stream failed during a seek.
+    reader: Option<FlacReader<R>>,
     current_block: Vec<i32>,
     current_block_channel_len: usize,
     current_block_off: usize,
+    // Position of the first frame of `current_block` in the stream.
+    current_block_start: u64,
     bits_per_sample: u32,
     sample_rate: u32,
     channels: u16,
     samples: Option<u64>,
+    metadata: Metadata,
+    marker: PhantomData<S>,
 }
 
-impl<R> FlacDecoder<R>
+impl<R, S> FlacDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     /// Attempts to decode the data as Flac.
-    pub fn new(mut data: R) -> Result<FlacDecoder<R>, R> {
+    pub fn new(mut data: R) -> Result<FlacDecoder<R, S>, R> {
         if !is_flac(data.by_ref()) {
             return Err(data);
         }
 
+        let pictures = read_pictures(data.by_ref());
         let reader = FlacReader::new(data).unwrap();
         let spec = reader.streaminfo();
 
+        let mut metadata = Metadata::new();
+        for (key, value) in reader.tags() {
+            metadata.push_tag(key.to_uppercase(), value.to_owned());
+        }
+        for picture in pictures {
+            metadata.push_picture(picture);
+        }
+
         Ok(FlacDecoder {
-            reader,
+            reader: Some(reader),
             current_block: Vec::with_capacity(
                 spec.max_block_size as usize * spec.channels as usize,
             ),
             current_block_channel_len: 1,
             current_block_off: 0,
+            current_block_start: 0,
             bits_per_sample: spec.bits_per_sample,
             sample_rate: spec.sample_rate,
             channels: spec.channels as u16,
             samples: spec.samples,
+            metadata,
+            marker: PhantomData,
         })
     }
-    pub fn into_inner(self) -> R {
-        self.reader.into_inner()
+    /// Returns the tags and pictures of the stream.
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the underlying reader, or `None` if it was lost while seeking.
+    pub fn into_inner(self) -> Option<R> {
+        self.reader.map(FlacReader::into_inner)
+    }
+
+    /// Decodes the next block into `current_block`. Returns `false` at the end of the stream.
+    fn load_next_block(&mut self) -> bool {
+        let reader = match self.reader.as_mut() {
+            Some(reader) => reader,
+            None => return false,
+        };
+
+        self.current_block_off = 0;
+        let buffer = mem::replace(&mut self.current_block, Vec::new());
+        match reader.blocks().read_next_or_eof(buffer) {
+            Ok(Some(block)) => {
+                self.current_block_channel_len = (block.len() / block.channels()) as usize;
+                self.current_block_start = block.time();
+                self.current_block = block.into_buffer();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Restarts decoding from the beginning of the stream.
+    fn rewind(&mut self) -> Result<(), SeekError> {
+        let mut data = match self.reader.take() {
+            Some(reader) => reader.into_inner(),
+            None => return Err(SeekError::DecodeError("the stream was lost".to_owned())),
+        };
+        data.seek(SeekFrom::Start(0))?;
+        let reader = FlacReader::new(data).map_err(|e| SeekError::DecodeError(e.to_string()))?;
+
+        self.reader = Some(reader);
+        self.current_block.clear();
+        self.current_block_channel_len = 1;
+        self.current_block_off = 0;
+        self.current_block_start = 0;
+        Ok(())
     }
 }
 
-impl<R> Source for FlacDecoder<R>
+impl<R, S> Source for FlacDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -67,6 +135,12 @@ where
         self.channels
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        // The order of the channels is defined by the FLAC format.
+        ChannelLayout::default_for(self.channels)
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.sample_rate
@@ -79,16 +153,42 @@ where
         self.samples
             .map(|s| Duration::from_micros(s * 1_000_000 / self.sample_rate as u64))
     }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let target = (pos.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as u64;
+
+        // claxon can not seek, so seeking backwards restarts decoding from the beginning.
+        if target < self.current_block_start {
+            self.rewind()?;
+        }
+
+        // Skip whole blocks until we reach the one that contains the target frame.
+        loop {
+            let block_frames = (self.current_block.len() / self.channels as usize) as u64;
+            if target < self.current_block_start + block_frames {
+                let frames_in_block = (target - self.current_block_start) as usize;
+                self.current_block_off = frames_in_block * self.channels as usize;
+                return Ok(());
+            }
+
+            if !self.load_next_block() {
+                // Seeking past the end of the stream ends the sound.
+                self.current_block_off = self.current_block.len();
+                return Ok(());
+            }
+        }
+    }
 }
 
-impl<R> Iterator for FlacDecoder<R>
+impl<R, S> Iterator for FlacDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
+    fn next(&mut self) -> Option<S> {
         loop {
             if self.current_block_off < self.current_block.len() {
                 // Read from current block.
@@ -97,23 +197,12 @@ where
                     + self.current_block_off / self.channels as usize;
                 let raw_val = self.current_block[real_offset];
                 self.current_block_off += 1;
-                let real_val = match self.bits_per_sample.cmp(&16) {
-                    Ordering::Less => (raw_val << (16 - self.bits_per_sample)) as i16,
-                    Ordering::Equal => raw_val as i16,
-                    Ordering::Greater => (raw_val >> (self.bits_per_sample - 16)) as i16,
-                };
-                return Some(real_val as i16);
+                return Some(S::from_int(raw_val, self.bits_per_sample));
             }
 
             // Load the next block.
-            self.current_block_off = 0;
-            let buffer = mem::replace(&mut self.current_block, Vec::new());
-            match self.reader.blocks().read_next_or_eof(buffer) {
-                Ok(Some(block)) => {
-                    self.current_block_channel_len = (block.len() / block.channels()) as usize;
-                    self.current_block = block.into_buffer();
-                }
-                _ => return None,
+            if !self.load_next_block() {
+                return None;
             }
         }
     }
@@ -134,3 +223,42 @@ where
     data.seek(SeekFrom::Start(stream_pos)).unwrap();
     true
 }
+
+/// Reads the `PICTURE` metadata blocks, which claxon skips, then resets the stream to where it was.
+fn read_pictures<R>(mut data: R) -> Vec<Picture>
+where
+    R: Read + Seek,
+{
+    let mut pictures = Vec::new();
+    let stream_pos = match data.stream_position() {
+        Ok(pos) => pos,
+        Err(_) => return pictures,
+    };
+
+    let mut marker = [0; 4];
+    if data.read_exact(&mut marker).is_ok() && &marker == b"fLaC" {
+        let mut header = [0; 4];
+        while data.read_exact(&mut header).is_ok() {
+            let is_last = header[0] & 0x80 != 0;
+            let block_type = header[0] & 0x7f;
+            let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+            if block_type == 6 {
+                let mut block = vec![0; len];
+                if data.read_exact(&mut block).is_err() {
+                    break;
+                }
+                pictures.extend(Picture::from_flac_block(&block));
+            } else if data.seek(SeekFrom::Current(len as i64)).is_err() {
+                break;
+            }
+
+            if is_last {
+                break;
+            }
+        }
+    }
+
+    let _ = data.seek(SeekFrom::Start(stream_pos));
+    pictures
+}
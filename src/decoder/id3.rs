@@ -0,0 +1,297 @@
+//! Minimal reader for ID3v2.3 and ID3v2.4 tags, which are found at the start of MP3 files.
+
+use std::io::{self, Read};
+
+use super::metadata::{Metadata, Picture};
+
+/// Reads the ID3v2 tag at the current position of the stream, if there is one.
+///
+/// Compressed and encrypted frames, as well as ID3v2.2 tags, are ignored.
+pub fn read_id3v2<R>(mut data: R) -> io::Result<Metadata>
+where
+    R: Read,
+{
+    let mut metadata = Metadata::new();
+
+    let mut header = [0; 10];
+    if data.read_exact(&mut header).is_err() || &header[..3] != b"ID3" {
+        return Ok(metadata);
+    }
+    let version = header[3];
+    let flags = header[5];
+    if version != 3 && version != 4 {
+        return Ok(metadata);
+    }
+
+    let size = syncsafe(&header[6..10]);
+    let mut tag = Vec::new();
+    data.take(size as u64).read_to_end(&mut tag)?;
+    if version == 3 && flags & 0x80 != 0 {
+        tag = remove_unsynchronisation(&tag);
+    }
+
+    let mut pos = 0;
+    if flags & 0x40 != 0 {
+        // Skip the extended header. Its size includes itself in ID3v2.4 only.
+        let size = match tag.get(..4) {
+            Some(bytes) if version == 4 => syncsafe(bytes),
+            Some(bytes) => {
+                u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).saturating_add(4)
+            }
+            None => return Ok(metadata),
+        };
+        pos = size as usize;
+    }
+
+    while pos.saturating_add(10) <= tag.len() {
+        let frame_header = &tag[pos..pos + 10];
+        // The rest of the tag is padding.
+        if frame_header[0] == 0 {
+            break;
+        }
+
+        let id = String::from_utf8_lossy(&frame_header[..4]).into_owned();
+        let size = if version == 4 {
+            syncsafe(&frame_header[4..8])
+        } else {
+            u32::from_be_bytes([
+                frame_header[4],
+                frame_header[5],
+                frame_header[6],
+                frame_header[7],
+            ])
+        } as usize;
+        let format_flags = frame_header[9];
+        pos += 10;
+
+        // A size that overflows is past the end of the tag as well.
+        let body = match pos.checked_add(size).and_then(|end| tag.get(pos..end)) {
+            Some(body) => body,
+            None => break,
+        };
+        pos += size;
+
+        if version == 3 {
+            // Compression or encryption.
+            if format_flags & 0xc0 != 0 {
+                continue;
+            }
+            // Grouping identity.
+            let body = if format_flags & 0x20 != 0 {
+                body.get(1..).unwrap_or(&[])
+            } else {
+                body
+            };
+            read_frame(&id, body, &mut metadata);
+        } else {
+            // Compression or encryption.
+            if format_flags & 0x0c != 0 {
+                continue;
+            }
+            let mut body = body;
+            // Grouping identity and data length indicator.
+            if format_flags & 0x40 != 0 {
+                body = body.get(1..).unwrap_or(&[]);
+            }
+            if format_flags & 0x01 != 0 {
+                body = body.get(4..).unwrap_or(&[]);
+            }
+            if format_flags & 0x02 != 0 {
+                read_frame(&id, &remove_unsynchronisation(body), &mut metadata);
+            } else {
+                read_frame(&id, body, &mut metadata);
+            }
+        }
+    }
+
+    Ok(metadata)
+}
+
+fn read_frame(id: &str, body: &[u8], metadata: &mut Metadata) {
+    let (&encoding, body) = match body.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    match id {
+        "TXXX" => {
+            let (description, mut rest) = split_string(encoding, body);
+            let key = decode_string(encoding, description).to_uppercase();
+            while !rest.is_empty() {
+                let (value, next) = split_string(encoding, rest);
+                metadata.push_tag(key.clone(), decode_string(encoding, value));
+                rest = next;
+            }
+        }
+        "COMM" => {
+            // Skip the language.
+            let (description, rest) = split_string(encoding, body.get(3..).unwrap_or(&[]));
+            if description.is_empty() {
+                let (value, _) = split_string(encoding, rest);
+                metadata.push_tag("COMMENT".to_owned(), decode_string(encoding, value));
+            }
+        }
+        "APIC" => {
+            // The MIME type is always ISO-8859-1.
+            let (mime_type, rest) = split_string(0, body);
+            let (&picture_type, rest) = match rest.split_first() {
+                Some(split) => split,
+                None => return,
+            };
+            let (description, data) = split_string(encoding, rest);
+            metadata.push_picture(Picture::new(
+                decode_string(0, mime_type),
+                decode_string(encoding, description),
+                picture_type == 3,
+                data.to_vec(),
+            ));
+        }
+        _ if id.starts_with('T') => {
+            let key = match id {
+                "TIT2" => "TITLE",
+                "TPE1" => "ARTIST",
+                "TALB" => "ALBUM",
+                "TPE2" => "ALBUMARTIST",
+                "TRCK" => "TRACKNUMBER",
+                "TPOS" => "DISCNUMBER",
+                "TCON" => "GENRE",
+                "TCOM" => "COMPOSER",
+                "TYER" | "TDRC" => "DATE",
+                "TCOP" => "COPYRIGHT",
+                "TSRC" => "ISRC",
+                "TSSE" => "ENCODER",
+                other => other,
+            };
+            // ID3v2.4 separates multiple values with a terminator.
+            let mut rest = body;
+            while !rest.is_empty() {
+                let (value, next) = split_string(encoding, rest);
+                metadata.push_tag(key.to_owned(), decode_string(encoding, value));
+                rest = next;
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Splits `bytes` at the first string terminator, which is two bytes long in UTF-16.
+fn split_string(encoding: u8, bytes: &[u8]) -> (&[u8], &[u8]) {
+    if encoding == 1 || encoding == 2 {
+        let end = bytes.chunks(2).position(|c| c == [0, 0]);
+        match end {
+            Some(end) => (&bytes[..end * 2], &bytes[end * 2 + 2..]),
+            None => (bytes, &[]),
+        }
+    } else {
+        match bytes.iter().position(|&b| b == 0) {
+            Some(end) => (&bytes[..end], &bytes[end + 1..]),
+            None => (bytes, &[]),
+        }
+    }
+}
+
+/// Decodes a string that is encoded in ISO-8859-1 (0), UTF-16 with a BOM (1), UTF-16BE (2) or
+/// UTF-8 (3).
+fn decode_string(encoding: u8, bytes: &[u8]) -> String {
+    match encoding {
+        0 => bytes.iter().map(|&b| b as char).collect(),
+        1 | 2 => {
+            let (big_endian, bytes) = match bytes {
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                _ => (encoding == 2, bytes),
+            };
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| {
+                    if big_endian {
+                        u16::from_be_bytes([c[0], c[1]])
+                    } else {
+                        u16::from_le_bytes([c[0], c[1]])
+                    }
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Reads a 28 bit integer stored in 4 bytes whose most significant bit is always 0.
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |size, &byte| (size << 7) | (byte & 0x7f) as u32)
+}
+
+/// Removes the `0x00` bytes that are inserted after every `0xff` byte.
+fn remove_unsynchronisation(bytes: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut previous = 0;
+    for &byte in bytes {
+        if !(previous == 0xff && byte == 0) {
+            output.push(byte);
+        }
+        previous = byte;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_id3v2;
+
+    fn frame(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut frame = id.to_vec();
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    fn tag(flags: u8, frames: Vec<u8>) -> Vec<u8> {
+        let mut tag = vec![b'I', b'D', b'3', 3, 0, flags];
+        let size = frames.len() as u32;
+        tag.extend_from_slice(&[
+            (size >> 21) as u8 & 0x7f,
+            (size >> 14) as u8 & 0x7f,
+            (size >> 7) as u8 & 0x7f,
+            size as u8 & 0x7f,
+        ]);
+        tag.extend(frames);
+        tag
+    }
+
+    #[test]
+    fn id3v23() {
+        let mut frames = Vec::new();
+        frames.extend(frame(b"TIT2", b"\x00Sonata"));
+        frames.extend(frame(b"TPE1", b"\x01\xff\xfeR\x00P\x00"));
+        frames.extend(frame(b"TXXX", b"\x03replaygain_track_gain\x00-3.20 dB"));
+        frames.extend(frame(b"APIC", b"\x00image/png\x00\x03cover\x00PNG"));
+        frames.extend_from_slice(&[0; 16]);
+
+        let metadata = read_id3v2(&tag(0, frames)[..]).unwrap();
+        assert_eq!(metadata.title(), Some("Sonata"));
+        assert_eq!(metadata.artist(), Some("RP"));
+        assert_eq!(metadata.replay_gain().track_gain, Some(-3.2));
+        let picture = &metadata.pictures()[0];
+        assert_eq!(picture.mime_type(), "image/png");
+        assert_eq!(picture.description(), "cover");
+        assert!(picture.is_front_cover());
+        assert_eq!(picture.data(), b"PNG");
+    }
+
+    #[test]
+    fn oversized_lengths() {
+        // Extended header whose size overflows.
+        let metadata = read_id3v2(&tag(0x40, vec![0xff; 20])[..]).unwrap();
+        assert_eq!(metadata.title(), None);
+
+        let mut frames = frame(b"TIT2", b"\x00Sonata");
+        frames.extend_from_slice(b"TPE1\xff\xff\xff\xff\x00\x00");
+        let metadata = read_id3v2(&tag(0, frames)[..]).unwrap();
+        assert_eq!(metadata.title(), Some("Sonata"));
+        assert_eq!(metadata.artist(), None);
+    }
+}
@@ -0,0 +1,290 @@
+/// Tags and pictures embedded in an audio file.
+///
+/// The tags are stored under the names used by Vorbis comments, in upper case: `TITLE`, `ARTIST`,
+/// `ALBUM`, `TRACKNUMBER`, `REPLAYGAIN_TRACK_GAIN` and so on. The tags of other formats, such as
+/// ID3, are translated to these names when there is an equivalent, and keep their own name
+/// otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    tags: Vec<(String, String)>,
+    pictures: Vec<Picture>,
+}
+
+impl Metadata {
+    /// Builds an empty `Metadata`.
+    #[inline]
+    pub const fn new() -> Metadata {
+        Metadata {
+            tags: Vec::new(),
+            pictures: Vec::new(),
+        }
+    }
+
+    /// Returns all the tags, in the order they appear in the file.
+    ///
+    /// A key can appear multiple times, for example if a track has multiple artists.
+    #[inline]
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Returns the value of the first tag with the given name. The name is case-insensitive.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the pictures embedded in the file, such as the cover art.
+    #[inline]
+    pub fn pictures(&self) -> &[Picture] {
+        &self.pictures
+    }
+
+    /// Returns the title of the track.
+    #[inline]
+    pub fn title(&self) -> Option<&str> {
+        self.get("TITLE")
+    }
+
+    /// Returns the artist of the track.
+    #[inline]
+    pub fn artist(&self) -> Option<&str> {
+        self.get("ARTIST")
+    }
+
+    /// Returns the album the track is part of.
+    #[inline]
+    pub fn album(&self) -> Option<&str> {
+        self.get("ALBUM")
+    }
+
+    /// Returns the number of the track in its album.
+    ///
+    /// Values of the form `3/12` are supported.
+    pub fn track_number(&self) -> Option<u32> {
+        let value = self.get("TRACKNUMBER")?;
+        value.split('/').next()?.trim().parse().ok()
+    }
+
+    /// Returns the ReplayGain values of the track.
+    pub fn replay_gain(&self) -> ReplayGain {
+        let parse = |key| {
+            let value: &str = self.get(key)?;
+            value.trim().trim_end_matches("dB").trim().parse().ok()
+        };
+
+        ReplayGain {
+            track_gain: parse("REPLAYGAIN_TRACK_GAIN"),
+            track_peak: parse("REPLAYGAIN_TRACK_PEAK"),
+            album_gain: parse("REPLAYGAIN_ALBUM_GAIN"),
+            album_peak: parse("REPLAYGAIN_ALBUM_PEAK"),
+        }
+    }
+
+    /// Adds a tag.
+    // Unused if the WAV decoder is the only one enabled, same for the other constructors.
+    #[allow(dead_code)]
+    #[inline]
+    pub(crate) fn push_tag(&mut self, key: String, value: String) {
+        self.tags.push((key, value));
+    }
+
+    /// Adds a picture.
+    #[allow(dead_code)]
+    #[inline]
+    pub(crate) fn push_picture(&mut self, picture: Picture) {
+        self.pictures.push(picture);
+    }
+}
+
+/// ReplayGain values of a track. Gains are in dB, peaks are relative to full scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayGain {
+    /// Gain to apply to play the track at the reference loudness.
+    pub track_gain: Option<f32>,
+    /// Peak amplitude of the track.
+    pub track_peak: Option<f32>,
+    /// Gain to apply to play the album at the reference loudness.
+    pub album_gain: Option<f32>,
+    /// Peak amplitude of the album.
+    pub album_peak: Option<f32>,
+}
+
+/// A picture embedded in an audio file.
+#[derive(Clone, Debug)]
+pub struct Picture {
+    mime_type: String,
+    description: String,
+    front_cover: bool,
+    data: Vec<u8>,
+}
+
+impl Picture {
+    /// Builds a new `Picture`.
+    #[allow(dead_code)]
+    #[inline]
+    pub(crate) fn new(
+        mime_type: String,
+        description: String,
+        front_cover: bool,
+        data: Vec<u8>,
+    ) -> Self {
+        Picture {
+            mime_type,
+            description,
+            front_cover,
+            data,
+        }
+    }
+
+    /// Returns the MIME type of the picture, such as `image/jpeg`.
+    #[inline]
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// Returns the description of the picture. Often empty.
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns true if this picture is the front cover of the album.
+    #[inline]
+    pub fn is_front_cover(&self) -> bool {
+        self.front_cover
+    }
+
+    /// Returns the encoded image.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Parses a FLAC `PICTURE` metadata block, which is also the format of the
+    /// `METADATA_BLOCK_PICTURE` Vorbis comment.
+    #[cfg(any(
+        all(feature = "flac", not(feature = "symphonia-flac")),
+        all(feature = "vorbis", not(feature = "symphonia-vorbis"))
+    ))]
+    pub(crate) fn from_flac_block(block: &[u8]) -> Option<Picture> {
+        let mut pos = 0;
+        let read = |pos: &mut usize, len: usize| {
+            let bytes = pos.checked_add(len).and_then(|end| block.get(*pos..end))?;
+            *pos += len;
+            Some(bytes)
+        };
+        let read_u32 = |pos: &mut usize| {
+            let bytes = read(pos, 4)?;
+            Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        };
+
+        // Picture type 3 is the front cover, as in ID3.
+        let picture_type = read_u32(&mut pos)?;
+        let mime_len = read_u32(&mut pos)?;
+        let mime_type = String::from_utf8_lossy(read(&mut pos, mime_len)?).into_owned();
+        let description_len = read_u32(&mut pos)?;
+        let description = String::from_utf8_lossy(read(&mut pos, description_len)?).into_owned();
+        // Width, height, color depth and number of colors.
+        read(&mut pos, 16)?;
+        let data_len = read_u32(&mut pos)?;
+        let data = read(&mut pos, data_len)?.to_vec();
+
+        Some(Picture::new(
+            mime_type,
+            description,
+            picture_type == 3,
+            data,
+        ))
+    }
+}
+
+/// Decodes standard base64, as used by the `METADATA_BLOCK_PICTURE` Vorbis comment.
+#[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+pub(crate) fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let input = input.trim_end_matches('=').as_bytes();
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    for chunk in input.chunks(4) {
+        let mut bits = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            bits |= value(c)? << (18 - 6 * i);
+        }
+        let bytes = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+        output.extend_from_slice(&bytes[..chunk.len() - 1]);
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metadata, ReplayGain};
+
+    #[test]
+    fn accessors() {
+        let mut metadata = Metadata::new();
+        metadata.push_tag("Title".to_owned(), "Sonata".to_owned());
+        metadata.push_tag("TRACKNUMBER".to_owned(), "3/12".to_owned());
+        metadata.push_tag("REPLAYGAIN_TRACK_GAIN".to_owned(), "-6.50 dB".to_owned());
+        metadata.push_tag("REPLAYGAIN_TRACK_PEAK".to_owned(), "0.988".to_owned());
+
+        assert_eq!(metadata.title(), Some("Sonata"));
+        assert_eq!(metadata.artist(), None);
+        assert_eq!(metadata.track_number(), Some(3));
+        assert_eq!(
+            metadata.replay_gain(),
+            ReplayGain {
+                track_gain: Some(-6.5),
+                track_peak: Some(0.988),
+                album_gain: None,
+                album_peak: None,
+            }
+        );
+    }
+
+    #[cfg(any(
+        all(feature = "flac", not(feature = "symphonia-flac")),
+        all(feature = "vorbis", not(feature = "symphonia-vorbis"))
+    ))]
+    #[test]
+    fn flac_picture_block() {
+        let block = |data_len: u32| {
+            let mut block = Vec::new();
+            block.extend_from_slice(&3u32.to_be_bytes());
+            block.extend_from_slice(&9u32.to_be_bytes());
+            block.extend_from_slice(b"image/png");
+            block.extend_from_slice(&0u32.to_be_bytes());
+            block.extend_from_slice(&[0; 16]);
+            block.extend_from_slice(&data_len.to_be_bytes());
+            block.extend_from_slice(b"png");
+            block
+        };
+
+        let picture = super::Picture::from_flac_block(&block(3)).unwrap();
+        assert_eq!(picture.mime_type(), "image/png");
+        assert!(picture.is_front_cover());
+        assert_eq!(picture.data(), b"png");
+
+        assert!(super::Picture::from_flac_block(&block(u32::MAX)).is_none());
+    }
+
+    #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+    #[test]
+    fn base64() {
+        assert_eq!(super::decode_base64("cm9kaW8="), Some(b"rodio".to_vec()));
+        assert_eq!(super::decode_base64("cm9kaW8h"), Some(b"rodio!".to_vec()));
+        assert_eq!(super::decode_base64("cm9k*W8h"), None);
+    }
+}
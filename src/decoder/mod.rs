@@ -9,6 +9,7 @@ use std::mem;
 use std::str::FromStr;
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::Source;
 
 #[cfg(feature = "symphonia")]
@@ -19,42 +20,60 @@ use ::symphonia::core::io::{MediaSource, MediaSourceStream};
 #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
 mod flac;
 #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+mod id3;
+mod metadata;
+#[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
 mod mp3;
 #[cfg(feature = "symphonia")]
 mod read_seek_source;
+mod sample;
 #[cfg(feature = "symphonia")]
 mod symphonia;
+mod track;
 #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
 mod vorbis;
 #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
 mod wav;
 
+pub use self::metadata::{Metadata, Picture, ReplayGain};
+pub use self::sample::DecoderSample;
+pub use self::track::{GaplessInfo, TrackInfo};
+
+/// Returned for the decoders that can't read tags.
+static EMPTY_METADATA: Metadata = Metadata::new();
+
 /// Source of audio samples from decoding a file.
 ///
 /// Supports MP3, WAV, Vorbis and Flac.
-pub struct Decoder<R>(DecoderImpl<R>)
+///
+/// The decoder produces `i16` samples by default. Use `Decoder::new_f32` to decode to `f32`
+/// instead, which keeps the full precision of high resolution files.
+pub struct Decoder<R, S = i16>(DecoderImpl<R, S>)
 where
-    R: Read + Seek;
+    R: Read + Seek,
+    S: DecoderSample;
 
-pub struct LoopedDecoder<R>(DecoderImpl<R>)
+pub struct LoopedDecoder<R, S = i16>(DecoderImpl<R, S>)
 where
-    R: Read + Seek;
+    R: Read + Seek,
+    S: DecoderSample;
 
-enum DecoderImpl<R>
+enum DecoderImpl<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
-    Wav(wav::WavDecoder<R>),
+    Wav(wav::WavDecoder<R, S>),
     #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
-    Vorbis(vorbis::VorbisDecoder<R>),
+    Vorbis(vorbis::VorbisDecoder<R, S>),
     #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
-    Flac(flac::FlacDecoder<R>),
+    Flac(flac::FlacDecoder<R, S>),
     #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
-    Mp3(mp3::Mp3Decoder<R>),
+    Mp3(mp3::Mp3Decoder<R, S>),
     #[cfg(feature = "symphonia")]
-    Symphonia(symphonia::SymphoniaDecoder),
-    None(::std::marker::PhantomData<R>),
+    Symphonia(Box<symphonia::SymphoniaDecoder<S>>),
+    None(::std::marker::PhantomData<(R, S)>),
 }
 
 impl<R> Decoder<R>
@@ -64,61 +83,22 @@ where
     /// Builds a new decoder.
     ///
     /// Attempts to automatically detect the format of the source of data.
-    #[allow(unused_variables)]
     pub fn new(data: R) -> Result<Decoder<R>, DecoderError> {
-        #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
-        let data = match wav::WavDecoder::new(data) {
-            Err(data) => data,
-            Ok(decoder) => {
-                return Ok(Decoder(DecoderImpl::Wav(decoder)));
-            }
-        };
-
-        #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
-        let data = match flac::FlacDecoder::new(data) {
-            Err(data) => data,
-            Ok(decoder) => {
-                return Ok(Decoder(DecoderImpl::Flac(decoder)));
-            }
-        };
-
-        #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
-        let data = match vorbis::VorbisDecoder::new(data) {
-            Err(data) => data,
-            Ok(decoder) => {
-                return Ok(Decoder(DecoderImpl::Vorbis(decoder)));
-            }
-        };
-
-        #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
-        let data = match mp3::Mp3Decoder::new(data) {
-            Err(data) => data,
-            Ok(decoder) => {
-                return Ok(Decoder(DecoderImpl::Mp3(decoder)));
-            }
-        };
-
-        #[cfg(feature = "symphonia")]
-        {
-            let mss = MediaSourceStream::new(
-                Box::new(ReadSeekSource::new(data)) as Box<dyn MediaSource>,
-                Default::default(),
-            );
-
-            match symphonia::SymphoniaDecoder::new(mss, None) {
-                Err(e) => Err(e),
-                Ok(decoder) => {
-                    return Ok(Decoder(DecoderImpl::Symphonia(decoder)));
-                }
-            }
-        }
-        #[cfg(not(feature = "symphonia"))]
-        Err(DecoderError::UnrecognizedFormat)
+        DecoderImpl::new(data).map(Decoder)
     }
     pub fn new_looped(data: R) -> Result<LoopedDecoder<R>, DecoderError> {
         Self::new(data).map(LoopedDecoder::new)
     }
 
+    /// Builds a new decoder for the audio track with the given id.
+    ///
+    /// The ids of the tracks of a file can be found with `Decoder::tracks`. Returns
+    /// `DecoderError::TrackNotFound` if the file has no track with this id.
+    #[cfg(feature = "symphonia")]
+    pub fn new_with_track(data: R, track_id: u32) -> Result<Decoder<R>, DecoderError> {
+        DecoderImpl::new_with_track(data, track_id).map(Decoder)
+    }
+
     /// Builds a new decoder from wav data.
     #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
     pub fn new_wav(data: R) -> Result<Decoder<R>, DecoderError> {
@@ -131,7 +111,7 @@ where
     /// Builds a new decoder from wav data.
     #[cfg(feature = "symphonia-wav")]
     pub fn new_wav(data: R) -> Result<Decoder<R>, DecoderError> {
-        Decoder::new_symphonia(data, "wav")
+        DecoderImpl::new_symphonia(data, "wav").map(Decoder)
     }
 
     /// Builds a new decoder from flac data.
@@ -146,7 +126,7 @@ where
     /// Builds a new decoder from flac data.
     #[cfg(feature = "symphonia-flac")]
     pub fn new_flac(data: R) -> Result<Decoder<R>, DecoderError> {
-        Decoder::new_symphonia(data, "flac")
+        DecoderImpl::new_symphonia(data, "flac").map(Decoder)
     }
 
     /// Builds a new decoder from vorbis data.
@@ -170,34 +150,121 @@ where
     /// Builds a new decoder from mp3 data.
     #[cfg(feature = "symphonia-mp3")]
     pub fn new_mp3(data: R) -> Result<Decoder<R>, DecoderError> {
-        Decoder::new_symphonia(data, "mp3")
+        DecoderImpl::new_symphonia(data, "mp3").map(Decoder)
     }
 
     /// Builds a new decoder from aac data.
     #[cfg(feature = "symphonia-aac")]
     pub fn new_aac(data: R) -> Result<Decoder<R>, DecoderError> {
-        Decoder::new_symphonia(data, "aac")
+        DecoderImpl::new_symphonia(data, "aac").map(Decoder)
     }
 
     /// Builds a new decoder from mp4 data.
     #[cfg(feature = "symphonia-isomp4")]
     pub fn new_mp4(data: R, hint: Mp4Type) -> Result<Decoder<R>, DecoderError> {
-        Decoder::new_symphonia(data, &hint.to_string())
+        DecoderImpl::new_symphonia(data, &hint.to_string()).map(Decoder)
+    }
+}
+
+impl<R> Decoder<R, f32>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    /// Builds a new decoder that produces `f32` samples.
+    ///
+    /// Attempts to automatically detect the format of the source of data. Unlike `Decoder::new`,
+    /// the samples are not reduced to 16 bits, so 24 bit and 32 bit files keep their precision.
+    pub fn new_f32(data: R) -> Result<Decoder<R, f32>, DecoderError> {
+        DecoderImpl::new(data).map(Decoder)
+    }
+
+    /// Builds a new looped decoder that produces `f32` samples.
+    pub fn new_looped_f32(data: R) -> Result<LoopedDecoder<R, f32>, DecoderError> {
+        Self::new_f32(data).map(LoopedDecoder::new)
+    }
+
+    /// Builds a new decoder that produces `f32` samples for the audio track with the given id.
+    #[cfg(feature = "symphonia")]
+    pub fn new_f32_with_track(data: R, track_id: u32) -> Result<Decoder<R, f32>, DecoderError> {
+        DecoderImpl::new_with_track(data, track_id).map(Decoder)
+    }
+}
+
+impl<R, S> DecoderImpl<R, S>
+where
+    R: Read + Seek + Send + Sync + 'static,
+    S: DecoderSample,
+{
+    #[allow(unused_variables)]
+    fn new(data: R) -> Result<DecoderImpl<R, S>, DecoderError> {
+        #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+        let data = match wav::WavDecoder::new(data) {
+            Err(data) => data,
+            Ok(decoder) => {
+                return Ok(DecoderImpl::Wav(decoder));
+            }
+        };
+
+        #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
+        let data = match flac::FlacDecoder::new(data) {
+            Err(data) => data,
+            Ok(decoder) => {
+                return Ok(DecoderImpl::Flac(decoder));
+            }
+        };
+
+        #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+        let data = match vorbis::VorbisDecoder::new(data) {
+            Err(data) => data,
+            Ok(decoder) => {
+                return Ok(DecoderImpl::Vorbis(decoder));
+            }
+        };
+
+        #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+        let data = match mp3::Mp3Decoder::new(data) {
+            Err(data) => data,
+            Ok(decoder) => {
+                return Ok(DecoderImpl::Mp3(decoder));
+            }
+        };
+
+        #[cfg(feature = "symphonia")]
+        {
+            let mss = MediaSourceStream::new(
+                Box::new(ReadSeekSource::new(data)) as Box<dyn MediaSource>,
+                Default::default(),
+            );
+
+            symphonia::SymphoniaDecoder::new(mss, None, None)
+                .map(|decoder| DecoderImpl::Symphonia(Box::new(decoder)))
+        }
+        #[cfg(not(feature = "symphonia"))]
+        Err(DecoderError::UnrecognizedFormat)
     }
 
     #[cfg(feature = "symphonia")]
-    fn new_symphonia(data: R, hint: &str) -> Result<Decoder<R>, DecoderError> {
+    fn new_symphonia(data: R, hint: &str) -> Result<DecoderImpl<R, S>, DecoderError> {
         let mss = MediaSourceStream::new(
             Box::new(ReadSeekSource::new(data)) as Box<dyn MediaSource>,
             Default::default(),
         );
 
-        match symphonia::SymphoniaDecoder::new(mss, Some(hint)) {
-            Err(e) => Err(e),
-            Ok(decoder) => {
-                return Ok(Decoder(DecoderImpl::Symphonia(decoder)));
-            }
-        }
+        symphonia::SymphoniaDecoder::new(mss, Some(hint), None)
+            .map(|decoder| DecoderImpl::Symphonia(Box::new(decoder)))
+    }
+
+    /// Only symphonia understands containers with multiple tracks, so the other backends are
+    /// skipped.
+    #[cfg(feature = "symphonia")]
+    fn new_with_track(data: R, track_id: u32) -> Result<DecoderImpl<R, S>, DecoderError> {
+        let mss = MediaSourceStream::new(
+            Box::new(ReadSeekSource::new(data)) as Box<dyn MediaSource>,
+            Default::default(),
+        );
+
+        symphonia::SymphoniaDecoder::new(mss, None, Some(track_id))
+            .map(|decoder| DecoderImpl::Symphonia(Box::new(decoder)))
     }
 }
 
@@ -244,23 +311,142 @@ impl fmt::Display for Mp4Type {
     }
 }
 
-impl<R> LoopedDecoder<R>
+impl<R, S> Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    fn new(decoder: Decoder<R>) -> LoopedDecoder<R> {
+    /// Returns the tags and pictures embedded in the file, such as the title, the artist, the
+    /// ReplayGain values or the cover art.
+    ///
+    /// The WAV decoder doesn't read tags, and the FLAC and Vorbis decoders only read the Vorbis
+    /// comments and pictures of the stream. MP3 tags are read from ID3v2.3 and ID3v2.4 tags.
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        self.0.metadata()
+    }
+
+    /// Returns the audio tracks of the file.
+    ///
+    /// Containers such as MP4 or Matroska can hold several audio tracks, which are all listed
+    /// here. The decoder plays the default one, use `Decoder::new_with_track` to play another.
+    /// Files that aren't decoded by symphonia always have a single track, with id 0.
+    pub fn tracks(&self) -> Vec<TrackInfo> {
+        self.0.tracks()
+    }
+
+    /// Returns the encoder delay and padding of the track, if the file stores them.
+    ///
+    /// They are read from the LAME tag of MP3 files and from the `iTunSMPB` tag of MP4 files. The
+    /// decoder doesn't return these samples, so that the tracks of a gapless album can be played
+    /// one after the other without a gap. When Ogg Vorbis streams are decoded with lewton (the
+    /// `vorbis` feature without `symphonia-vorbis`), they are also trimmed to the granule position
+    /// of their last page. Symphonia doesn't trim them.
+    #[inline]
+    pub fn gapless_info(&self) -> Option<GaplessInfo> {
+        self.0.gapless_info()
+    }
+}
+
+impl<R, S> LoopedDecoder<R, S>
+where
+    R: Read + Seek,
+    S: DecoderSample,
+{
+    fn new(decoder: Decoder<R, S>) -> LoopedDecoder<R, S> {
         Self(decoder.0)
     }
 }
 
-impl<R> Iterator for Decoder<R>
+impl<R, S> DecoderImpl<R, S>
+where
+    R: Read + Seek,
+    S: DecoderSample,
+{
+    #[inline]
+    fn metadata(&self) -> &Metadata {
+        match self {
+            #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+            DecoderImpl::Wav(_) => &EMPTY_METADATA,
+            #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+            DecoderImpl::Vorbis(source) => source.metadata(),
+            #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
+            DecoderImpl::Flac(source) => source.metadata(),
+            #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+            DecoderImpl::Mp3(source) => source.metadata(),
+            #[cfg(feature = "symphonia")]
+            DecoderImpl::Symphonia(source) => source.metadata(),
+            DecoderImpl::None(_) => &EMPTY_METADATA,
+        }
+    }
+
+    #[inline]
+    fn gapless_info(&self) -> Option<GaplessInfo> {
+        match self {
+            #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+            DecoderImpl::Mp3(source) => source.gapless_info(),
+            #[cfg(feature = "symphonia")]
+            DecoderImpl::Symphonia(source) => source.gapless_info(),
+            _ => None,
+        }
+    }
+
+    // Unreachable if symphonia decodes every format.
+    #[allow(unreachable_code)]
+    fn tracks(&self) -> Vec<TrackInfo> {
+        let (codec, channels, sample_rate): (&str, u16, u32) = match self {
+            #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+            DecoderImpl::Wav(source) => ("pcm", source.channels(), source.sample_rate()),
+            #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+            DecoderImpl::Vorbis(source) => ("vorbis", source.channels(), source.sample_rate()),
+            #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
+            DecoderImpl::Flac(source) => ("flac", source.channels(), source.sample_rate()),
+            #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+            DecoderImpl::Mp3(source) => ("mp3", source.channels(), source.sample_rate()),
+            #[cfg(feature = "symphonia")]
+            DecoderImpl::Symphonia(source) => return source.tracks().to_vec(),
+            DecoderImpl::None(_) => return Vec::new(),
+        };
+
+        vec![TrackInfo::new(
+            0,
+            codec.to_owned(),
+            Some(channels),
+            Some(sample_rate),
+            None,
+        )]
+    }
+
+    #[inline]
+    #[allow(unused_variables)]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        match self {
+            #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+            DecoderImpl::Wav(source) => source.try_seek(pos),
+            #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+            DecoderImpl::Vorbis(source) => source.try_seek(pos),
+            #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
+            DecoderImpl::Flac(source) => source.try_seek(pos),
+            #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+            DecoderImpl::Mp3(source) => source.try_seek(pos),
+            #[cfg(feature = "symphonia")]
+            DecoderImpl::Symphonia(source) => source.try_seek(pos),
+            DecoderImpl::None(_) => Err(SeekError::NotSupported {
+                underlying_source: "DecoderImpl::None",
+            }),
+        }
+    }
+}
+
+impl<R, S> Iterator for Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
+    fn next(&mut self) -> Option<S> {
         match &mut self.0 {
             #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
             DecoderImpl::Wav(source) => source.next(),
@@ -294,9 +480,10 @@ where
     }
 }
 
-impl<R> Source for Decoder<R>
+impl<R, S> Source for Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -332,6 +519,23 @@ where
         }
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        match &self.0 {
+            #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+            DecoderImpl::Wav(source) => source.channel_layout(),
+            #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+            DecoderImpl::Vorbis(source) => source.channel_layout(),
+            #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
+            DecoderImpl::Flac(source) => source.channel_layout(),
+            #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+            DecoderImpl::Mp3(source) => source.channel_layout(),
+            #[cfg(feature = "symphonia")]
+            DecoderImpl::Symphonia(source) => source.channel_layout(),
+            DecoderImpl::None(_) => None,
+        }
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         match &self.0 {
@@ -365,16 +569,21 @@ where
             DecoderImpl::None(_) => Some(Duration::default()),
         }
     }
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.0.try_seek(pos)
+    }
 }
 
-impl<R> Iterator for LoopedDecoder<R>
+impl<R, S> Iterator for LoopedDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
+    fn next(&mut self) -> Option<S> {
         if let Some(sample) = match &mut self.0 {
             #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
             DecoderImpl::Wav(source) => source.next(),
@@ -413,7 +622,7 @@ where
                 }
                 #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
                 DecoderImpl::Flac(source) => {
-                    let mut reader = source.into_inner();
+                    let mut reader = source.into_inner()?;
                     reader.seek(SeekFrom::Start(0)).ok()?;
                     let mut source = flac::FlacDecoder::new(reader).ok()?;
                     let sample = source.next();
@@ -421,7 +630,7 @@ where
                 }
                 #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
                 DecoderImpl::Mp3(source) => {
-                    let mut reader = source.into_inner();
+                    let mut reader = source.into_inner()?;
                     reader.seek(SeekFrom::Start(0)).ok()?;
                     let mut source = mp3::Mp3Decoder::new(reader).ok()?;
                     let sample = source.next();
@@ -429,11 +638,13 @@ where
                 }
                 #[cfg(feature = "symphonia")]
                 DecoderImpl::Symphonia(source) => {
-                    let mut reader = Box::new(source).into_inner();
+                    let track_id = source.track_id();
+                    let mut reader = source.into_inner();
                     reader.seek(SeekFrom::Start(0)).ok()?;
-                    let mut source = symphonia::SymphoniaDecoder::new(reader, None).ok()?;
+                    let mut source =
+                        symphonia::SymphoniaDecoder::new(reader, None, Some(track_id)).ok()?;
                     let sample = source.next();
-                    (DecoderImpl::Symphonia(source), sample)
+                    (DecoderImpl::Symphonia(Box::new(source)), sample)
                 }
                 none @ DecoderImpl::None(_) => (none, None),
             };
@@ -460,9 +671,10 @@ where
     }
 }
 
-impl<R> Source for LoopedDecoder<R>
+impl<R, S> Source for LoopedDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -498,6 +710,23 @@ where
         }
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        match &self.0 {
+            #[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+            DecoderImpl::Wav(source) => source.channel_layout(),
+            #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+            DecoderImpl::Vorbis(source) => source.channel_layout(),
+            #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
+            DecoderImpl::Flac(source) => source.channel_layout(),
+            #[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+            DecoderImpl::Mp3(source) => source.channel_layout(),
+            #[cfg(feature = "symphonia")]
+            DecoderImpl::Symphonia(source) => source.channel_layout(),
+            DecoderImpl::None(_) => None,
+        }
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         match &self.0 {
@@ -519,6 +748,10 @@ where
     fn total_duration(&self) -> Option<Duration> {
         None
     }
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.0.try_seek(pos)
+    }
 }
 
 /// Error that can happen when creating a decoder.
@@ -547,6 +780,10 @@ pub enum DecoderError {
     /// No streams were found by the decoder
     #[cfg(feature = "symphonia")]
     NoStreams,
+
+    /// The file has no track with the requested id.
+    #[cfg(feature = "symphonia")]
+    TrackNotFound,
 }
 
 impl fmt::Display for DecoderError {
@@ -563,6 +800,8 @@ impl fmt::Display for DecoderError {
             DecoderError::ResetRequired => "Reset required",
             #[cfg(feature = "symphonia")]
             DecoderError::NoStreams => "No streams",
+            #[cfg(feature = "symphonia")]
+            DecoderError::TrackNotFound => "Track not found",
         };
         write!(f, "{}", text)
     }
@@ -1,44 +1,169 @@
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
 use std::time::Duration;
 
+use crate::source::SeekError;
 use crate::Source;
 
+use super::id3::read_id3v2;
+use super::metadata::Metadata;
+use super::sample::DecoderSample;
+use super::track::GaplessInfo;
+
 use minimp3::{Decoder, Frame};
 
-pub struct Mp3Decoder<R>
+pub struct Mp3Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    decoder: Decoder<R>,
+    // Only `None` if rewinding the stream failed during a seek.
+    decoder: Option<Decoder<R>>,
     current_frame: Frame,
     current_frame_offset: usize,
+    // Number of samples of `current_frame` that are part of the track, which is less than its
+    // length in the last frame if the stream has padding.
+    current_frame_end: usize,
+    // Position of the first sample of `current_frame` in the stream, per channel.
+    current_frame_start: u64,
+    // Samples of the stream that belong to the track, see `StreamLength`.
+    start: u64,
+    end: Option<u64>,
+    sample_rate: u32,
+    gapless: Option<GaplessInfo>,
+    metadata: Metadata,
+    marker: PhantomData<S>,
 }
 
-impl<R> Mp3Decoder<R>
+impl<R, S> Mp3Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     pub fn new(mut data: R) -> Result<Self, R> {
         if !is_mp3(data.by_ref()) {
             return Err(data);
         }
+        let length = read_length(data.by_ref());
+        let metadata = read_metadata(data.by_ref());
         let mut decoder = Decoder::new(data);
         let current_frame = decoder.next_frame().unwrap();
 
-        Ok(Mp3Decoder {
-            decoder,
+        let mut decoder = Mp3Decoder {
+            decoder: Some(decoder),
+            current_frame_end: current_frame.data.len(),
             current_frame,
             current_frame_offset: 0,
-        })
+            current_frame_start: 0,
+            start: length.as_ref().map_or(0, |length| length.start),
+            end: length.as_ref().map(|length| length.end),
+            sample_rate: length.as_ref().map_or(0, |length| length.sample_rate),
+            gapless: length.and_then(|length| length.gapless),
+            metadata,
+            marker: PhantomData,
+        };
+        decoder.update_frame_end();
+        decoder.skip_to(decoder.start);
+        Ok(decoder)
+    }
+
+    /// Returns the encoder delay and padding stored in the LAME tag of the stream.
+    #[inline]
+    pub fn gapless_info(&self) -> Option<GaplessInfo> {
+        self.gapless
+    }
+
+    /// Returns the tags and pictures of the ID3v2 tag of the stream.
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the underlying reader, or `None` if it was lost while seeking.
+    pub fn into_inner(self) -> Option<R> {
+        self.decoder.map(Decoder::into_inner)
+    }
+
+    /// Decodes the next frame into `current_frame`. Returns `false` at the end of the stream.
+    fn load_next_frame(&mut self) -> bool {
+        let decoder = match self.decoder.as_mut() {
+            Some(decoder) => decoder,
+            None => return false,
+        };
+
+        match decoder.next_frame() {
+            Ok(frame) => {
+                self.current_frame_start += self.current_frame_len_per_channel();
+                self.current_frame = frame;
+                self.current_frame_offset = 0;
+                self.update_frame_end();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Restarts decoding from the beginning of the stream.
+    fn rewind(&mut self) -> Result<(), SeekError> {
+        let mut data = match self.decoder.take() {
+            Some(decoder) => decoder.into_inner(),
+            None => return Err(SeekError::DecodeError("the stream was lost".to_owned())),
+        };
+        data.seek(SeekFrom::Start(0))?;
+        let mut decoder = Decoder::new(data);
+        let current_frame = decoder
+            .next_frame()
+            .map_err(|e| SeekError::DecodeError(e.to_string()))?;
+
+        self.decoder = Some(decoder);
+        self.current_frame = current_frame;
+        self.current_frame_offset = 0;
+        self.current_frame_start = 0;
+        self.update_frame_end();
+        Ok(())
     }
-    pub fn into_inner(self) -> R {
-        self.decoder.into_inner()
+
+    /// Skips the samples until the position `target` of the stream, per channel.
+    fn skip_to(&mut self, target: u64) {
+        // Skip whole frames until we reach the one that contains the target sample.
+        loop {
+            if target < self.current_frame_start + self.current_frame_len_per_channel() {
+                let samples_in_frame = (target - self.current_frame_start) as usize;
+                self.current_frame_offset = samples_in_frame * self.current_frame.channels;
+                return;
+            }
+
+            if !self.load_next_frame() {
+                // Seeking past the end of the stream ends the sound.
+                self.current_frame_offset = self.current_frame.data.len();
+                return;
+            }
+        }
+    }
+
+    /// Computes `current_frame_end` by removing the padding at the end of the stream.
+    #[inline]
+    fn update_frame_end(&mut self) {
+        let len = self.current_frame.data.len();
+        self.current_frame_end = match self.end {
+            Some(end) => {
+                let remaining = end.saturating_sub(self.current_frame_start);
+                len.min(remaining as usize * self.current_frame.channels)
+            }
+            None => len,
+        };
+    }
+
+    #[inline]
+    fn current_frame_len_per_channel(&self) -> u64 {
+        (self.current_frame.data.len() / self.current_frame.channels.max(1)) as u64
     }
 }
 
-impl<R> Source for Mp3Decoder<R>
+impl<R, S> Source for Mp3Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -57,30 +182,44 @@ where
 
     #[inline]
     fn total_duration(&self) -> Option<Duration> {
-        None
+        let end = self.end?;
+        Some(samples_to_duration(end - self.start, self.sample_rate))
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let target =
+            self.start + (pos.as_nanos() * self.sample_rate() as u128 / 1_000_000_000) as u64;
+
+        // minimp3 can not seek, so seeking backwards restarts decoding from the beginning.
+        if target < self.current_frame_start {
+            self.rewind()?;
+        }
+
+        self.skip_to(target);
+        Ok(())
     }
 }
 
-impl<R> Iterator for Mp3Decoder<R>
+impl<R, S> Iterator for Mp3Decoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
-        if self.current_frame_offset == self.current_frame.data.len() {
-            match self.decoder.next_frame() {
-                Ok(frame) => self.current_frame = frame,
-                _ => return None,
+    fn next(&mut self) -> Option<S> {
+        while self.current_frame_offset >= self.current_frame_end {
+            // Only the padding is left in this frame.
+            if self.current_frame_end < self.current_frame.data.len() || !self.load_next_frame() {
+                return None;
             }
-            self.current_frame_offset = 0;
         }
 
         let v = self.current_frame.data[self.current_frame_offset];
         self.current_frame_offset += 1;
 
-        Some(v)
+        Some(S::from_i16(v))
     }
 }
 
@@ -96,3 +235,317 @@ where
 
     ok
 }
+
+/// Reads the ID3v2 tag at the start of the stream, then resets it to where it was.
+fn read_metadata<R>(mut data: R) -> Metadata
+where
+    R: Read + Seek,
+{
+    let stream_pos = match data.stream_position() {
+        Ok(pos) => pos,
+        Err(_) => return Metadata::new(),
+    };
+    let metadata = read_id3v2(data.by_ref()).unwrap_or_default();
+    let _ = data.seek(SeekFrom::Start(stream_pos));
+
+    metadata
+}
+
+/// The samples of the decoded stream that belong to the track, per channel.
+///
+/// The frame that contains the Xing/Info or VBRI header is decoded as silence, and so are the
+/// encoder delay and padding stored in the LAME tag. They are all trimmed for gapless playback.
+struct StreamLength {
+    sample_rate: u32,
+    // Position of the first sample of the track.
+    start: u64,
+    // Position of the sample after the last one of the track.
+    end: u64,
+    gapless: Option<GaplessInfo>,
+}
+
+/// Computes the length of the stream, then resets it to where it was.
+///
+/// The number of frames is read from the Xing/Info or VBRI header if there is one. Otherwise all
+/// the frame headers of the stream are scanned, which doesn't require decoding them.
+fn read_length<R>(mut data: R) -> Option<StreamLength>
+where
+    R: Read + Seek,
+{
+    let stream_pos = data.stream_position().ok()?;
+    let length = scan_length(data.by_ref());
+    data.seek(SeekFrom::Start(stream_pos)).ok()?;
+
+    length.ok().flatten()
+}
+
+fn scan_length<R>(mut data: R) -> io::Result<Option<StreamLength>>
+where
+    R: Read + Seek,
+{
+    let first_frame = match find_first_frame(data.by_ref())? {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    data.seek(SeekFrom::Start(first_frame))?;
+    let header = match read_frame_header(data.by_ref())? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let mut first_frame_data = vec![0; header.frame_len];
+    data.seek(SeekFrom::Start(first_frame))?;
+    if read_full(data.by_ref(), &mut first_frame_data)? == header.frame_len {
+        if let Some(vbr_header) = read_vbr_header(&header, &first_frame_data) {
+            let samples_per_frame = header.samples_per_frame as u64;
+            let gapless = vbr_header.gapless.unwrap_or_default();
+            let len = (vbr_header.frames as u64 * samples_per_frame)
+                .saturating_sub(gapless.delay as u64 + gapless.padding as u64);
+            let start = samples_per_frame + gapless.delay as u64;
+            return Ok(Some(StreamLength {
+                sample_rate: header.sample_rate,
+                start,
+                end: start + len,
+                gapless: vbr_header.gapless,
+            }));
+        }
+    }
+
+    // The frames are read one after the other rather than seeked to, as seeking empties the
+    // buffer of a `BufReader`.
+    let mut samples = 0;
+    data.seek(SeekFrom::Start(first_frame))?;
+    loop {
+        match read_frame_header(data.by_ref())? {
+            Some(frame) if frame.sample_rate == header.sample_rate => {
+                samples += frame.samples_per_frame as u64;
+                let body = frame.frame_len.saturating_sub(4) as u64;
+                io::copy(&mut data.by_ref().take(body), &mut io::sink())?;
+            }
+            // The end of the stream, or trailing data such as an ID3v1 tag.
+            _ => break,
+        }
+    }
+
+    Ok(Some(StreamLength {
+        sample_rate: header.sample_rate,
+        start: 0,
+        end: samples,
+        gapless: None,
+    }))
+}
+
+/// Returns the position of the first frame, skipping an ID3v2 tag and garbage in front of it.
+fn find_first_frame<R>(mut data: R) -> io::Result<Option<u64>>
+where
+    R: Read + Seek,
+{
+    let mut start = data.stream_position()?;
+    let mut id3 = [0; 10];
+    if read_full(data.by_ref(), &mut id3)? == id3.len() && &id3[..3] == b"ID3" {
+        let size = id3[6..]
+            .iter()
+            .fold(0u64, |size, &byte| (size << 7) | (byte & 0x7f) as u64);
+        let footer = if id3[5] & 0x10 != 0 { 10 } else { 0 };
+        start += 10 + size + footer;
+    }
+
+    let mut buf = vec![0; MAX_FRAME_SEARCH];
+    data.seek(SeekFrom::Start(start))?;
+    let len = read_full(data.by_ref(), &mut buf)?;
+
+    for offset in 0..len.saturating_sub(4) {
+        let header = match parse_frame_header(&buf[offset..offset + 4]) {
+            Some(header) => header,
+            None => continue,
+        };
+
+        // Make sure that this isn't a false sync by checking that another frame follows.
+        let next = start + (offset + header.frame_len) as u64;
+        data.seek(SeekFrom::Start(next))?;
+        if read_frame_header(data.by_ref())?.is_some() {
+            return Ok(Some(start + offset as u64));
+        }
+    }
+
+    Ok(None)
+}
+
+/// How far after the ID3v2 tag the first frame is searched.
+const MAX_FRAME_SEARCH: usize = 16 * 1024;
+
+/// Information from the header of an MPEG audio frame.
+struct FrameHeader {
+    mpeg1: bool,
+    layer3: bool,
+    mono: bool,
+    sample_rate: u32,
+    samples_per_frame: u32,
+    frame_len: usize,
+}
+
+fn read_frame_header<R>(mut data: R) -> io::Result<Option<FrameHeader>>
+where
+    R: Read,
+{
+    let mut bytes = [0; 4];
+    if read_full(data.by_ref(), &mut bytes)? < bytes.len() {
+        return Ok(None);
+    }
+    Ok(parse_frame_header(&bytes))
+}
+
+fn parse_frame_header(bytes: &[u8]) -> Option<FrameHeader> {
+    if bytes[0] != 0xff || bytes[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+
+    // 0 is MPEG 2.5, 2 is MPEG 2 and 3 is MPEG 1.
+    let version = (bytes[1] >> 3) & 0b11;
+    // 1 is layer III, 2 is layer II and 3 is layer I.
+    let layer = (bytes[1] >> 1) & 0b11;
+    let bitrate_index = (bytes[2] >> 4) as usize;
+    let sample_rate_index = ((bytes[2] >> 2) & 0b11) as usize;
+    let padding = ((bytes[2] >> 1) & 1) as usize;
+    let mono = bytes[3] >> 6 == 0b11;
+
+    if version == 1 || layer == 0 || bitrate_index == 0 || bitrate_index == 15 {
+        return None;
+    }
+    if sample_rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let bitrate = match (mpeg1, layer) {
+        (true, 3) => [
+            32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        (true, 2) => [
+            32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        (true, _) => [
+            32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+        (false, 3) => [
+            32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        (false, _) => [8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    }[bitrate_index - 1]
+        * 1000;
+    let sample_rate = [44100, 48000, 32000][sample_rate_index] >> (3 - version.max(1));
+
+    let samples_per_frame = match layer {
+        3 => 384,
+        1 if !mpeg1 => 576,
+        _ => 1152,
+    };
+    let frame_len = if layer == 3 {
+        (12 * bitrate / sample_rate as usize + padding) * 4
+    } else {
+        samples_per_frame / 8 * bitrate / sample_rate as usize + padding
+    };
+
+    Some(FrameHeader {
+        mpeg1,
+        layer3: layer == 1,
+        mono,
+        sample_rate,
+        samples_per_frame: samples_per_frame as u32,
+        frame_len,
+    })
+}
+
+/// Information from the Xing/Info or VBRI header of the first frame.
+struct VbrHeader {
+    // Number of audio frames in the stream, without the one containing this header.
+    frames: u32,
+    gapless: Option<GaplessInfo>,
+}
+
+/// The decoder outputs the samples with a delay of 528 + 1 samples, which the encoder delay stored
+/// in the LAME tag doesn't include.
+const DECODER_DELAY: u32 = 528 + 1;
+
+fn read_vbr_header(header: &FrameHeader, frame: &[u8]) -> Option<VbrHeader> {
+    if !header.layer3 {
+        return None;
+    }
+
+    // The Xing header follows the side information.
+    let xing = 4 + match (header.mpeg1, header.mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    if let Some(tag) = frame.get(xing..xing + 8) {
+        if &tag[..4] == b"Xing" || &tag[..4] == b"Info" {
+            let flags = tag[7];
+            let mut pos = xing + 8;
+            // Bit 0 of the flags tells whether the number of frames is present.
+            if flags & 1 == 0 {
+                return None;
+            }
+            let frames = frame.get(pos..pos + 4)?;
+            let frames = u32::from_be_bytes([frames[0], frames[1], frames[2], frames[3]]);
+            pos += 4;
+            // Then the number of bytes, the table of contents and the quality.
+            for &(flag, len) in &[(2, 4), (4, 100), (8, 4)] {
+                if flags & flag != 0 {
+                    pos += len;
+                }
+            }
+            return Some(VbrHeader {
+                frames,
+                gapless: frame.get(pos..).and_then(read_lame_gapless),
+            });
+        }
+    }
+
+    // The VBRI header is always 32 bytes after the frame header.
+    match frame.get(36..54) {
+        Some(tag) if &tag[..4] == b"VBRI" => Some(VbrHeader {
+            frames: u32::from_be_bytes([tag[14], tag[15], tag[16], tag[17]]),
+            gapless: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Reads the encoder delay and padding from the LAME tag that follows the Xing/Info header.
+fn read_lame_gapless(tag: &[u8]) -> Option<GaplessInfo> {
+    let tag = tag.get(..24)?;
+    // FFmpeg writes the same tag as LAME.
+    if &tag[..4] != b"LAME" && &tag[..4] != b"Lavf" && &tag[..4] != b"Lavc" {
+        return None;
+    }
+
+    // The delay and padding are two 12 bit values, after the encoder version and other fields.
+    let trim = u32::from_be_bytes([0, tag[21], tag[22], tag[23]]);
+    Some(GaplessInfo {
+        delay: (trim >> 12) + DECODER_DELAY,
+        padding: (trim & 0xfff).saturating_sub(DECODER_DELAY),
+    })
+}
+
+#[inline]
+fn samples_to_duration(samples: u64, sample_rate: u32) -> Duration {
+    Duration::from_nanos(samples * 1_000_000_000 / sample_rate as u64)
+}
+
+/// Reads until `buf` is full or the end of the stream is reached. Returns the number of bytes read.
+fn read_full<R>(mut data: R, buf: &mut [u8]) -> io::Result<usize>
+where
+    R: Read,
+{
+    let mut read = 0;
+    while read < buf.len() {
+        match data.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
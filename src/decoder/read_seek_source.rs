@@ -5,13 +5,15 @@ use symphonia::core::io::MediaSource;
 
 pub struct ReadSeekSource<T: Read + Seek + Send + Sync> {
     inner: T,
+    byte_len: Option<u64>,
 }
 
 impl<T: Read + Seek + Send + Sync> ReadSeekSource<T> {
     /// Instantiates a new `ReadSeekSource<T>` by taking ownership and wrapping the provided
     /// `Read + Seek`er.
-    pub fn new(inner: T) -> Self {
-        ReadSeekSource { inner }
+    pub fn new(mut inner: T) -> Self {
+        let byte_len = stream_len(&mut inner).ok();
+        ReadSeekSource { inner, byte_len }
     }
 }
 
@@ -21,7 +23,7 @@ impl<T: Read + Seek + Send + Sync> MediaSource for ReadSeekSource<T> {
     }
 
     fn byte_len(&self) -> Option<u64> {
-        None
+        self.byte_len
     }
 }
 
@@ -36,3 +38,13 @@ impl<T: Read + Seek + Send + Sync> Seek for ReadSeekSource<T> {
         self.inner.seek(pos)
     }
 }
+
+/// Returns the length of the stream in bytes, then resets it to where it was.
+///
+/// Symphonia needs the length to seek in some formats, but `byte_len` can't seek itself.
+fn stream_len<T: Seek>(inner: &mut T) -> Result<u64> {
+    let stream_pos = inner.stream_position()?;
+    let len = inner.seek(SeekFrom::End(0))?;
+    inner.seek(SeekFrom::Start(stream_pos))?;
+    Ok(len)
+}
@@ -0,0 +1,107 @@
+use std::cmp::Ordering;
+
+use cpal::Sample as CpalSample;
+
+use crate::Sample;
+
+/// Sample type that a `Decoder` can produce.
+///
+/// This trait is implemented for `i16` and `f32` and can't be implemented outside of this crate.
+/// Decoding to `f32` keeps the full precision of high resolution files, such as 24 bit FLAC or
+/// 32 bit float WAV.
+pub trait DecoderSample: Sample + Send + 'static + sealed::Sealed {}
+
+impl DecoderSample for i16 {}
+impl DecoderSample for f32 {}
+
+pub(crate) mod sealed {
+    /// Conversions from the sample formats of the different backends.
+    pub trait Sealed: VorbisSample + SymphoniaSample {
+        /// Converts a 16 bit integer sample.
+        fn from_i16(value: i16) -> Self;
+
+        /// Converts an integer sample that uses `bits_per_sample` bits.
+        fn from_int(value: i32, bits_per_sample: u32) -> Self;
+
+        /// Converts a float sample in the range `[-1.0, 1.0]`.
+        fn from_f32(value: f32) -> Self;
+    }
+
+    /// Samples that lewton can decode to.
+    #[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+    pub trait VorbisSample: lewton::samples::Sample {}
+    #[cfg(not(all(feature = "vorbis", not(feature = "symphonia-vorbis"))))]
+    pub trait VorbisSample {}
+
+    impl VorbisSample for i16 {}
+    impl VorbisSample for f32 {}
+
+    /// Samples that symphonia can decode to.
+    #[cfg(feature = "symphonia")]
+    pub trait SymphoniaSample: symphonia::core::conv::ConvertibleSample {}
+    #[cfg(not(feature = "symphonia"))]
+    pub trait SymphoniaSample {}
+
+    impl SymphoniaSample for i16 {}
+    impl SymphoniaSample for f32 {}
+}
+
+impl sealed::Sealed for i16 {
+    #[inline]
+    fn from_i16(value: i16) -> i16 {
+        value
+    }
+
+    #[inline]
+    fn from_int(value: i32, bits_per_sample: u32) -> i16 {
+        match bits_per_sample.cmp(&16) {
+            Ordering::Less => (value << (16 - bits_per_sample)) as i16,
+            Ordering::Equal => value as i16,
+            Ordering::Greater => (value >> (bits_per_sample - 16)) as i16,
+        }
+    }
+
+    #[inline]
+    fn from_f32(value: f32) -> i16 {
+        // Prefer to clip the input rather than be excessively loud.
+        (value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+    }
+}
+
+impl sealed::Sealed for f32 {
+    #[inline]
+    fn from_i16(value: i16) -> f32 {
+        value.to_f32()
+    }
+
+    #[inline]
+    fn from_int(value: i32, bits_per_sample: u32) -> f32 {
+        value as f32 / (1u64 << (bits_per_sample - 1)) as f32
+    }
+
+    #[inline]
+    fn from_f32(value: f32) -> f32 {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sealed::Sealed;
+
+    #[test]
+    fn int_to_i16() {
+        assert_eq!(<i16 as Sealed>::from_int(-128, 8), -32768);
+        assert_eq!(<i16 as Sealed>::from_int(1234, 16), 1234);
+        assert_eq!(<i16 as Sealed>::from_int(0x7f_ffff, 24), 0x7fff);
+        assert_eq!(<i16 as Sealed>::from_int(i32::MIN, 32), i16::MIN);
+    }
+
+    #[test]
+    fn int_to_f32() {
+        assert_eq!(<f32 as Sealed>::from_int(-128, 8), -1.0);
+        assert_eq!(<f32 as Sealed>::from_int(1 << 22, 24), 0.5);
+        assert_eq!(<f32 as Sealed>::from_int(1, 24), 1.0 / 8_388_608.0);
+        assert_eq!(<f32 as Sealed>::from_int(i32::MIN, 32), -1.0);
+    }
+}
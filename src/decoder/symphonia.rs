@@ -2,19 +2,23 @@ use std::time::Duration;
 use symphonia::{
     core::{
         audio::{AudioBufferRef, SampleBuffer, SignalSpec},
-        codecs::{Decoder, DecoderOptions},
+        codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_NULL},
         errors::Error,
-        formats::{FormatOptions, FormatReader},
+        formats::{FormatOptions, FormatReader, SeekMode, SeekTo, Track},
         io::MediaSourceStream,
-        meta::MetadataOptions,
+        meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey},
         probe::Hint,
         units,
     },
     default::get_probe,
 };
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::Source;
 
+use super::metadata::{Metadata, Picture};
+use super::sample::DecoderSample;
+use super::track::{GaplessInfo, TrackInfo};
 use super::DecoderError;
 
 // Decoder errors are not considered fatal.
@@ -22,17 +26,48 @@ use super::DecoderError;
 // But a decode error in more than 3 consecutive packets is fatal.
 const MAX_DECODE_ERRORS: usize = 3;
 
-pub struct SymphoniaDecoder {
+pub struct SymphoniaDecoder<S>
+where
+    S: DecoderSample,
+{
     decoder: Box<dyn Decoder>,
     current_frame_offset: usize,
+    // Samples of `buffer` after this one are padding.
+    current_frame_end: usize,
     format: Box<dyn FormatReader>,
-    buffer: SampleBuffer<i16>,
+    buffer: SampleBuffer<S>,
     spec: SignalSpec,
+    track_id: u32,
+    total_duration: Option<Duration>,
+    metadata: Metadata,
+    tracks: Vec<TrackInfo>,
+    gapless: Option<GaplessInfo>,
+    trim: Option<Trim>,
 }
 
-impl SymphoniaDecoder {
-    pub fn new(mss: MediaSourceStream, extension: Option<&str>) -> Result<Self, DecoderError> {
-        match SymphoniaDecoder::init(mss, extension) {
+/// Encoder delay and padding that are removed by `SymphoniaDecoder`, for the containers that
+/// symphonia doesn't trim itself. This is the case of MP4 files with an `iTunSMPB` tag.
+struct Trim {
+    delay: u64,
+    // Length of the track without the delay and padding, in frames.
+    len: u64,
+    // Frames that are still to be skipped at the start and to be played.
+    skip: u64,
+    left: u64,
+}
+
+impl<S> SymphoniaDecoder<S>
+where
+    S: DecoderSample,
+{
+    /// Builds a decoder for the track with the given id, or for the default track of the
+    /// container if `track_id` is `None`.
+    pub fn new(
+        mss: MediaSourceStream,
+        extension: Option<&str>,
+        track_id: Option<u32>,
+    ) -> Result<Self, DecoderError> {
+        match SymphoniaDecoder::init(mss, extension, track_id) {
             Err(e) => match e {
                 Error::IoError(e) => Err(DecoderError::IoError(e.to_string())),
                 Error::DecodeError(e) => Err(DecoderError::DecodeError(e)),
@@ -44,10 +79,35 @@ impl SymphoniaDecoder {
                 Error::ResetRequired => Err(DecoderError::ResetRequired),
             },
             Ok(Some(decoder)) => Ok(decoder),
+            Ok(None) if track_id.is_some() => Err(DecoderError::TrackNotFound),
             Ok(None) => Err(DecoderError::NoStreams),
         }
     }
 
+    /// Returns the tags and pictures of the stream.
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the audio tracks of the container.
+    #[inline]
+    pub fn tracks(&self) -> &[TrackInfo] {
+        &self.tracks
+    }
+
+    /// Returns the encoder delay and padding of the track being decoded.
+    #[inline]
+    pub fn gapless_info(&self) -> Option<GaplessInfo> {
+        self.gapless
+    }
+
+    /// Returns the id of the track being decoded.
+    #[inline]
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+
     pub fn into_inner(self: Box<Self>) -> MediaSourceStream {
         self.format.into_inner()
     }
@@ -55,31 +115,74 @@ impl SymphoniaDecoder {
     fn init(
         mss: MediaSourceStream,
         extension: Option<&str>,
-    ) -> symphonia::core::errors::Result<Option<SymphoniaDecoder>> {
+        track_id: Option<u32>,
+    ) -> symphonia::core::errors::Result<Option<SymphoniaDecoder<S>>> {
         let mut hint = Hint::new();
         if let Some(ext) = extension {
             hint.with_extension(ext);
         }
-        let format_opts: FormatOptions = Default::default();
+        // Let the format readers that support it remove the encoder delay and padding.
+        let format_opts = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
         let metadata_opts: MetadataOptions = Default::default();
         let mut probed = get_probe().format(&hint, mss, &format_opts, &metadata_opts)?;
 
-        let stream = match probed.format.default_track() {
+        // Tags can be found both in front of the container, like ID3 tags, and in the container.
+        let mut metadata = Metadata::new();
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            add_revision(&mut metadata, revision);
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            add_revision(&mut metadata, revision);
+        }
+
+        let stream = match track_id {
+            Some(id) => probed.format.tracks().iter().find(|track| track.id == id),
+            None => probed.format.default_track(),
+        };
+        let stream = match stream {
             Some(stream) => stream,
             None => return Ok(None),
         };
+        let track_id = stream.id;
+        let mut total_duration = total_duration(&stream.codec_params);
 
-        let mut decoder = symphonia::default::get_codecs().make(
-            &stream.codec_params,
-            &DecoderOptions {
-                verify: true,
-                ..Default::default()
-            },
-        )?;
+        let params = &stream.codec_params;
+        let mut gapless = None;
+        let mut trim = None;
+        if params.delay.is_some() || params.padding.is_some() {
+            gapless = Some(GaplessInfo {
+                delay: params.delay.unwrap_or(0),
+                padding: params.padding.unwrap_or(0),
+            });
+        } else if let Some((info, len)) = metadata
+            .get("com.apple.iTunes:iTunSMPB")
+            .and_then(parse_itunsmpb)
+        {
+            gapless = Some(info);
+            trim = Some(Trim {
+                delay: info.delay as u64,
+                len,
+                skip: info.delay as u64,
+                left: len,
+            });
+            if let Some(rate) = params.sample_rate {
+                total_duration = Some(Duration::from_nanos(len * 1_000_000_000 / rate as u64));
+            }
+        }
+
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&stream.codec_params, &DecoderOptions { verify: true })?;
 
         let mut decode_errors: usize = 0;
         let decoded = loop {
             let current_frame = probed.format.next_packet()?;
+            // Containers can interleave the packets of several tracks.
+            if current_frame.track_id() != track_id {
+                continue;
+            }
             match decoder.decode(&current_frame) {
                 Ok(decoded) => break decoded,
                 Err(e) => match e {
@@ -98,25 +201,118 @@ impl SymphoniaDecoder {
         let spec = decoded.spec().to_owned();
         let buffer = SymphoniaDecoder::get_buffer(decoded, &spec);
 
-        return Ok(Some(SymphoniaDecoder {
+        // Some containers don't know the format of their tracks, for the track being decoded it
+        // is known from the first packet.
+        let tracks = probed
+            .format
+            .tracks()
+            .iter()
+            .filter(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .map(|track| track_info(track, Some(&spec).filter(|_| track.id == track_id)))
+            .collect();
+
+        let mut decoder = SymphoniaDecoder {
             decoder,
             current_frame_offset: 0,
+            current_frame_end: buffer.len(),
             format: probed.format,
             buffer,
             spec,
-        }));
+            track_id,
+            total_duration,
+            metadata,
+            tracks,
+            gapless,
+            trim,
+        };
+        decoder.trim_frame();
+        Ok(Some(decoder))
+    }
+
+    /// Decodes the next packet that contains samples of the track.
+    ///
+    /// Returns `None` at the end of the track or on a fatal error.
+    fn decode_next(&mut self) -> Option<()> {
+        loop {
+            self.decode_packet()?;
+            if !self.trim_frame() {
+                return None;
+            }
+            if self.current_frame_offset < self.current_frame_end {
+                return Some(());
+            }
+        }
+    }
+
+    /// Removes the samples of the current frame that belong to the encoder delay or padding, if
+    /// they aren't removed by symphonia. Returns `false` if the end of the track was reached.
+    fn trim_frame(&mut self) -> bool {
+        let trim = match self.trim.as_mut() {
+            Some(trim) => trim,
+            None => return true,
+        };
+        if trim.left == 0 {
+            self.current_frame_end = self.current_frame_offset;
+            return false;
+        }
+
+        let channels = self.spec.channels.count().max(1);
+        let frames = ((self.current_frame_end - self.current_frame_offset) / channels) as u64;
+        let skip = trim.skip.min(frames);
+        let keep = (frames - skip).min(trim.left);
+        trim.skip -= skip;
+        trim.left -= keep;
+
+        self.current_frame_offset += skip as usize * channels;
+        self.current_frame_end = self.current_frame_offset + keep as usize * channels;
+        true
+    }
+
+    /// Decodes the next packet, skipping up to `MAX_DECODE_ERRORS` packets that fail to decode.
+    ///
+    /// Returns `None` at the end of the stream or on a fatal error.
+    fn decode_packet(&mut self) -> Option<()> {
+        let mut decode_errors: usize = 0;
+        let decoded = loop {
+            match self.format.next_packet() {
+                Ok(packet) if packet.track_id() != self.track_id => continue,
+                Ok(packet) => match self.decoder.decode(&packet) {
+                    Ok(decoded) => break decoded,
+                    Err(e) => match e {
+                        Error::DecodeError(_) => {
+                            decode_errors += 1;
+                            if decode_errors > MAX_DECODE_ERRORS {
+                                return None;
+                            } else {
+                                continue;
+                            }
+                        }
+                        _ => return None,
+                    },
+                },
+                Err(_) => return None,
+            }
+        };
+        self.spec = decoded.spec().to_owned();
+        self.buffer = SymphoniaDecoder::get_buffer(decoded, &self.spec);
+        self.current_frame_offset = 0;
+        self.current_frame_end = self.buffer.len();
+        Some(())
     }
 
     #[inline]
-    fn get_buffer(decoded: AudioBufferRef, spec: &SignalSpec) -> SampleBuffer<i16> {
+    fn get_buffer(decoded: AudioBufferRef, spec: &SignalSpec) -> SampleBuffer<S> {
         let duration = units::Duration::from(decoded.capacity() as u64);
-        let mut buffer = SampleBuffer::<i16>::new(duration, spec.clone());
+        let mut buffer = SampleBuffer::<S>::new(duration, *spec);
         buffer.copy_interleaved_ref(decoded);
-        return buffer;
+        buffer
     }
 }
 
-impl Source for SymphoniaDecoder {
+impl<S> Source for SymphoniaDecoder<S>
+where
+    S: DecoderSample,
+{
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
         Some(self.buffer.samples().len())
@@ -127,6 +323,16 @@ impl Source for SymphoniaDecoder {
         self.spec.channels.count() as u16
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        // A single channel is always played as mono, whatever its position.
+        if self.spec.channels.count() == 1 {
+            Some(ChannelLayout::MONO)
+        } else {
+            ChannelLayout::from_mask(self.spec.channels.bits())
+        }
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.spec.rate
@@ -134,39 +340,145 @@ impl Source for SymphoniaDecoder {
 
     #[inline]
     fn total_duration(&self) -> Option<Duration> {
-        None
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // The position is relative to the start of the track, after the encoder delay.
+        let time = match &self.trim {
+            Some(trim) => {
+                pos + Duration::from_nanos(trim.delay * 1_000_000_000 / self.sample_rate() as u64)
+            }
+            None => pos,
+        };
+        let seeked_to = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: time.into(),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(|e| SeekError::DecodeError(e.to_string()))?;
+
+        // The decoder must be reset after a seek, and the samples between the packet the format
+        // reader seeked to and the requested position must be skipped.
+        self.decoder.reset();
+        let mut samples_to_skip = seeked_to.required_ts.saturating_sub(seeked_to.actual_ts)
+            as usize
+            * self.channels() as usize;
+        if let Some(trim) = self.trim.as_mut() {
+            trim.skip = 0;
+            trim.left = (trim.delay + trim.len).saturating_sub(seeked_to.actual_ts);
+        }
+
+        loop {
+            if self.decode_next().is_none() {
+                // Seeking past the end of the stream ends the sound.
+                self.current_frame_offset = self.current_frame_end;
+                return Ok(());
+            }
+
+            let frame_len = self.current_frame_end - self.current_frame_offset;
+            if samples_to_skip < frame_len {
+                self.current_frame_offset += samples_to_skip;
+                return Ok(());
+            }
+            samples_to_skip -= frame_len;
+        }
     }
 }
 
-impl Iterator for SymphoniaDecoder {
-    type Item = i16;
+/// Parses the `iTunSMPB` tag that iTunes writes in MP4 files. It contains the encoder delay, the
+/// padding and the length of the track, in hexadecimal. Returns the delay and padding, and the
+/// length.
+fn parse_itunsmpb(value: &str) -> Option<(GaplessInfo, u64)> {
+    let mut fields = value.split_whitespace().skip(1);
+    let delay = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let padding = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let len = u64::from_str_radix(fields.next()?, 16).ok()?;
+    Some((GaplessInfo { delay, padding }, len))
+}
+
+/// Adds the tags and visuals of a metadata revision, translating the known tags to the names used
+/// by Vorbis comments.
+fn add_revision(metadata: &mut Metadata, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let key = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => "TITLE",
+            Some(StandardTagKey::Artist) => "ARTIST",
+            Some(StandardTagKey::Album) => "ALBUM",
+            Some(StandardTagKey::AlbumArtist) => "ALBUMARTIST",
+            Some(StandardTagKey::TrackNumber) => "TRACKNUMBER",
+            Some(StandardTagKey::DiscNumber) => "DISCNUMBER",
+            Some(StandardTagKey::Genre) => "GENRE",
+            Some(StandardTagKey::Composer) => "COMPOSER",
+            Some(StandardTagKey::Date) => "DATE",
+            Some(StandardTagKey::Comment) => "COMMENT",
+            Some(StandardTagKey::Encoder) => "ENCODER",
+            Some(StandardTagKey::ReplayGainTrackGain) => "REPLAYGAIN_TRACK_GAIN",
+            Some(StandardTagKey::ReplayGainTrackPeak) => "REPLAYGAIN_TRACK_PEAK",
+            Some(StandardTagKey::ReplayGainAlbumGain) => "REPLAYGAIN_ALBUM_GAIN",
+            Some(StandardTagKey::ReplayGainAlbumPeak) => "REPLAYGAIN_ALBUM_PEAK",
+            _ => &tag.key,
+        };
+        metadata.push_tag(key.to_uppercase(), tag.value.to_string());
+    }
+
+    for visual in revision.visuals() {
+        metadata.push_picture(Picture::new(
+            visual.media_type.clone(),
+            String::new(),
+            visual.usage == Some(StandardVisualKey::FrontCover),
+            visual.data.to_vec(),
+        ));
+    }
+}
+
+/// Describes a track of the container. The format of the track is taken from `decoded_spec` when
+/// the container doesn't specify it.
+fn track_info(track: &Track, decoded_spec: Option<&SignalSpec>) -> TrackInfo {
+    let params = &track.codec_params;
+    let codec = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map(|descriptor| descriptor.short_name.to_owned())
+        .unwrap_or_else(|| params.codec.to_string());
+
+    TrackInfo::new(
+        track.id,
+        codec,
+        params
+            .channels
+            .or_else(|| decoded_spec.map(|spec| spec.channels))
+            .map(|channels| channels.count() as u16),
+        params
+            .sample_rate
+            .or_else(|| decoded_spec.map(|spec| spec.rate)),
+        track.language.clone(),
+    )
+}
+
+/// Returns the duration of the track, if the container knows it.
+fn total_duration(params: &CodecParameters) -> Option<Duration> {
+    let n_frames = params.n_frames?;
+    match (params.time_base, params.sample_rate) {
+        (Some(time_base), _) => Some(time_base.calc_time(n_frames).into()),
+        (None, Some(rate)) => Some(Duration::from_nanos(n_frames * 1_000_000_000 / rate as u64)),
+        (None, None) => None,
+    }
+}
+
+impl<S> Iterator for SymphoniaDecoder<S>
+where
+    S: DecoderSample,
+{
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
-        if self.current_frame_offset == self.buffer.len() {
-            let mut decode_errors: usize = 0;
-            let decoded = loop {
-                match self.format.next_packet() {
-                    Ok(packet) => match self.decoder.decode(&packet) {
-                        Ok(decoded) => break decoded,
-                        Err(e) => match e {
-                            Error::DecodeError(_) => {
-                                decode_errors += 1;
-                                if decode_errors > MAX_DECODE_ERRORS {
-                                    return None;
-                                } else {
-                                    continue;
-                                }
-                            }
-                            _ => return None,
-                        },
-                    },
-                    Err(_) => return None,
-                }
-            };
-            self.spec = decoded.spec().to_owned();
-            self.buffer = SymphoniaDecoder::get_buffer(decoded, &self.spec);
-            self.current_frame_offset = 0;
+    fn next(&mut self) -> Option<S> {
+        if self.current_frame_offset >= self.current_frame_end {
+            self.decode_next()?;
         }
 
         let sample = self.buffer.samples()[self.current_frame_offset];
@@ -175,3 +487,25 @@ impl Iterator for SymphoniaDecoder {
         Some(sample)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_itunsmpb;
+    use crate::decoder::GaplessInfo;
+
+    #[test]
+    fn itunsmpb() {
+        let value = " 00000000 00000840 000001CA 00000000003F31F6 00000000 00000000 00000000";
+        assert_eq!(
+            parse_itunsmpb(value),
+            Some((
+                GaplessInfo {
+                    delay: 2112,
+                    padding: 458
+                },
+                4141558
+            ))
+        );
+        assert_eq!(parse_itunsmpb("garbage"), None);
+    }
+}
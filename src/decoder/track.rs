@@ -0,0 +1,77 @@
+/// Description of an audio track of a file.
+///
+/// Most files contain a single audio track, but containers such as MP4 or Matroska can hold
+/// several, for example one per language. Use `Decoder::tracks` to list them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackInfo {
+    id: u32,
+    codec: String,
+    channels: Option<u16>,
+    sample_rate: Option<u32>,
+    language: Option<String>,
+}
+
+impl TrackInfo {
+    /// Builds a new `TrackInfo`.
+    #[inline]
+    pub(crate) fn new(
+        id: u32,
+        codec: String,
+        channels: Option<u16>,
+        sample_rate: Option<u32>,
+        language: Option<String>,
+    ) -> TrackInfo {
+        TrackInfo {
+            id,
+            codec,
+            channels,
+            sample_rate,
+            language,
+        }
+    }
+
+    /// Returns the identifier of the track in its container.
+    ///
+    /// This is the id to pass to `Decoder::new_with_track`.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the short name of the codec of the track, such as `aac`, `flac` or `mp3`.
+    #[inline]
+    pub fn codec(&self) -> &str {
+        &self.codec
+    }
+
+    /// Returns the number of channels of the track, if known.
+    #[inline]
+    pub fn channels(&self) -> Option<u16> {
+        self.channels
+    }
+
+    /// Returns the sample rate of the track, if known.
+    #[inline]
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    /// Returns the language of the track, usually as an ISO 639-2 code, if known.
+    #[inline]
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+}
+
+/// Encoder delay and padding of a track, in frames.
+///
+/// Lossy encoders such as MP3 and AAC add silence at the start and at the end of a track. The
+/// decoders remove it, so that consecutive tracks of a gapless album join without a gap or a
+/// click.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GaplessInfo {
+    /// Number of frames at the start of the decoded stream that aren't part of the track.
+    pub delay: u32,
+    /// Number of frames at the end of the decoded stream that aren't part of the track.
+    pub padding: u32,
+}
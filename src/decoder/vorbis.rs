@@ -1,58 +1,72 @@
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::time::Duration;
 use std::vec;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::Source;
 
+use super::metadata::{decode_base64, Metadata, Picture};
+use super::sample::DecoderSample;
+use lewton::header::CommentHeader;
 use lewton::inside_ogg::OggStreamReader;
+use lewton::samples::InterleavedSamples;
 
 /// Decoder for an OGG file that contains Vorbis sound format.
-pub struct VorbisDecoder<R>
+pub struct VorbisDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     stream_reader: OggStreamReader<R>,
-    current_data: vec::IntoIter<i16>,
+    current_data: vec::IntoIter<S>,
+    // Granule position of the last page, which is the length of the stream in frames.
+    last_granule: Option<u64>,
+    metadata: Metadata,
 }
 
-impl<R> VorbisDecoder<R>
+impl<R, S> VorbisDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     /// Attempts to decode the data as ogg/vorbis.
-    pub fn new(mut data: R) -> Result<VorbisDecoder<R>, R> {
+    pub fn new(mut data: R) -> Result<VorbisDecoder<R, S>, R> {
         if !is_vorbis(data.by_ref()) {
             return Err(data);
         }
 
+        let last_granule = read_last_granule(data.by_ref());
         let stream_reader = OggStreamReader::new(data).unwrap();
-        Ok(Self::from_stream_reader(stream_reader))
+        let mut decoder = Self::from_stream_reader(stream_reader);
+        decoder.last_granule = last_granule;
+        Ok(decoder)
     }
     pub fn from_stream_reader(mut stream_reader: OggStreamReader<R>) -> Self {
-        let mut data = match stream_reader.read_dec_packet_itl() {
-            Ok(Some(d)) => d,
-            _ => Vec::new(),
-        };
-
-        // The first packet is always empty, therefore
-        // we need to read the second frame to get some data
-        if let Ok(Some(mut d)) = stream_reader.read_dec_packet_itl() {
-            data.append(&mut d);
-        }
+        let metadata = read_metadata(&stream_reader.comment_hdr);
+        let data = read_first_packets(&mut stream_reader);
 
         VorbisDecoder {
             stream_reader,
             current_data: data.into_iter(),
+            last_granule: None,
+            metadata,
         }
     }
+
+    /// Returns the tags and pictures of the stream.
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
     pub fn into_inner(self) -> OggStreamReader<R> {
         self.stream_reader
     }
 }
 
-impl<R> Source for VorbisDecoder<R>
+impl<R, S> Source for VorbisDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -64,6 +78,13 @@ where
         self.stream_reader.ident_hdr.audio_channels as u16
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        // The samples are put in this order by `read_dec_packet`. The layout of streams with more
+        // than 8 channels is defined by the application.
+        ChannelLayout::default_for(self.channels())
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.stream_reader.ident_hdr.audio_sample_rate
@@ -71,27 +92,42 @@ where
 
     #[inline]
     fn total_duration(&self) -> Option<Duration> {
-        None
+        self.last_granule.map(|granule| {
+            Duration::from_nanos(granule * 1_000_000_000 / self.sample_rate() as u64)
+        })
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let absgp = (pos.as_nanos() * self.sample_rate() as u128 / 1_000_000_000) as u64;
+
+        // lewton seeks with a page granularity, so playback may resume slightly before `pos`.
+        // The granule position 0 belongs to the header pages, which can't be decoded as audio.
+        self.stream_reader
+            .seek_absgp_pg(absgp.max(1))
+            .map_err(|e| SeekError::DecodeError(e.to_string()))?;
+        self.current_data = read_first_packets(&mut self.stream_reader).into_iter();
+        Ok(())
     }
 }
 
-impl<R> Iterator for VorbisDecoder<R>
+impl<R, S> Iterator for VorbisDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
+    fn next(&mut self) -> Option<S> {
         if let Some(sample) = self.current_data.next() {
             if self.current_data.len() == 0 {
-                if let Ok(Some(data)) = self.stream_reader.read_dec_packet_itl() {
+                if let Ok(Some(data)) = read_dec_packet(&mut self.stream_reader) {
                     self.current_data = data.into_iter();
                 }
             }
             Some(sample)
         } else {
-            if let Ok(Some(data)) = self.stream_reader.read_dec_packet_itl() {
+            if let Ok(Some(data)) = read_dec_packet(&mut self.stream_reader) {
                 self.current_data = data.into_iter();
             }
             self.current_data.next()
@@ -104,6 +140,87 @@ where
     }
 }
 
+/// Collects the Vorbis comments. Pictures are stored in `METADATA_BLOCK_PICTURE` comments.
+fn read_metadata(comment_hdr: &CommentHeader) -> Metadata {
+    let mut metadata = Metadata::new();
+    for (key, value) in &comment_hdr.comment_list {
+        let key = key.to_uppercase();
+        if key == "METADATA_BLOCK_PICTURE" {
+            let block = decode_base64(value);
+            if let Some(picture) = block.and_then(|block| Picture::from_flac_block(&block)) {
+                metadata.push_picture(picture);
+            }
+        } else {
+            metadata.push_tag(key, value.clone());
+        }
+    }
+    metadata
+}
+
+/// Decodes the next packet as interleaved samples of type `S`, with the channels in the order of
+/// `ChannelLayout::default_for`.
+fn read_dec_packet<R, S>(
+    stream_reader: &mut OggStreamReader<R>,
+) -> Result<Option<Vec<S>>, lewton::VorbisError>
+where
+    R: Read + Seek,
+    S: DecoderSample,
+{
+    let packet: Option<InterleavedSamples<S>> = stream_reader.read_dec_packet_generic()?;
+    Ok(packet.map(|mut packet| {
+        to_default_order(&mut packet.samples, packet.channel_count);
+        packet.samples
+    }))
+}
+
+/// Reorders the channels of interleaved samples from the Vorbis order (Vorbis I specification,
+/// section 4.3.9) to the order of `ChannelLayout::default_for`, which is the one of WAV files.
+///
+/// For example the Vorbis order of 5.1 is front left, center, front right, rear left, rear right
+/// and LFE.
+fn to_default_order<S>(samples: &mut [S], channels: usize)
+where
+    S: Copy,
+{
+    // Index of the Vorbis channel of each channel of the layout.
+    let order: &[usize] = match channels {
+        3 => &[0, 2, 1],
+        5 => &[0, 2, 1, 3, 4],
+        6 => &[0, 2, 1, 5, 3, 4],
+        7 => &[0, 2, 1, 6, 5, 3, 4],
+        8 => &[0, 2, 1, 7, 5, 6, 3, 4],
+        // Mono, stereo and quadraphonic have the same order in both.
+        _ => return,
+    };
+    for frame in samples.chunks_exact_mut(channels) {
+        let mut vorbis = [frame[0]; 8];
+        vorbis[..channels].copy_from_slice(frame);
+        for (sample, &channel) in frame.iter_mut().zip(order) {
+            *sample = vorbis[channel];
+        }
+    }
+}
+
+/// Decodes the first packets after the start of the stream or after a seek.
+fn read_first_packets<R, S>(stream_reader: &mut OggStreamReader<R>) -> Vec<S>
+where
+    R: Read + Seek,
+    S: DecoderSample,
+{
+    let mut data = match read_dec_packet(stream_reader) {
+        Ok(Some(d)) => d,
+        _ => Vec::new(),
+    };
+
+    // The first packet is always empty, therefore
+    // we need to read the second frame to get some data
+    if let Ok(Some(mut d)) = read_dec_packet(stream_reader) {
+        data.append(&mut d);
+    }
+
+    data
+}
+
 /// Returns true if the stream contains Vorbis data, then resets it to where it was.
 fn is_vorbis<R>(mut data: R) -> bool
 where
@@ -119,3 +236,77 @@ where
     data.seek(SeekFrom::Start(stream_pos)).unwrap();
     true
 }
+
+/// Returns the granule position of the last page of the logical stream that starts at the current
+/// position, then resets the stream to where it was.
+fn read_last_granule<R>(mut data: R) -> Option<u64>
+where
+    R: Read + Seek,
+{
+    let stream_pos = data.stream_position().ok()?;
+    let granule = find_last_granule(data.by_ref(), stream_pos);
+    data.seek(SeekFrom::Start(stream_pos)).ok()?;
+
+    granule.ok().flatten()
+}
+
+fn find_last_granule<R>(mut data: R, stream_pos: u64) -> io::Result<Option<u64>>
+where
+    R: Read + Seek,
+{
+    let mut header = [0; 27];
+    data.read_exact(&mut header)?;
+    if &header[..4] != b"OggS" {
+        return Ok(None);
+    }
+    let serial = &header[14..18];
+
+    // A page is at most 65307 bytes long, so the last page starts within that distance from
+    // the end of the stream.
+    let end = data.seek(SeekFrom::End(0))?;
+    let start = end.saturating_sub(MAX_PAGE_LEN).max(stream_pos);
+    data.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::with_capacity((end - start) as usize);
+    data.take(end - start).read_to_end(&mut tail)?;
+
+    let last_page = tail
+        .windows(27)
+        .rev()
+        .find(|page| &page[..4] == b"OggS" && &page[14..18] == serial && page[6..14] != [0xff; 8]);
+    Ok(last_page.map(|page| {
+        u64::from_le_bytes([
+            page[6], page[7], page[8], page[9], page[10], page[11], page[12], page[13],
+        ])
+    }))
+}
+
+/// The maximum length of an Ogg page: the header, 255 lacing values and 255 segments of 255 bytes.
+const MAX_PAGE_LEN: u64 = 27 + 255 + 255 * 255;
+
+#[cfg(test)]
+mod tests {
+    use super::to_default_order;
+
+    #[test]
+    fn surround_5_1_order() {
+        // Vorbis order: FL, C, FR, RL, RR, LFE.
+        let mut samples = [1, 3, 2, 5, 6, 4, 11, 13, 12, 15, 16, 14];
+        to_default_order(&mut samples, 6);
+        assert_eq!(samples, [1, 2, 3, 4, 5, 6, 11, 12, 13, 14, 15, 16]);
+    }
+
+    #[test]
+    fn surround_7_1_order() {
+        // Vorbis order: FL, C, FR, SL, SR, RL, RR, LFE.
+        let mut samples = [1, 3, 2, 7, 8, 5, 6, 4];
+        to_default_order(&mut samples, 8);
+        assert_eq!(samples, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn stereo_is_unchanged() {
+        let mut samples = [1, 2, 3, 4];
+        to_default_order(&mut samples, 2);
+        assert_eq!(samples, [1, 2, 3, 4]);
+    }
+}
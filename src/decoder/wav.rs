@@ -1,27 +1,33 @@
 use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
 use std::time::Duration;
 
+use crate::source::SeekError;
 use crate::Source;
 
+use super::sample::DecoderSample;
+
 use hound::{SampleFormat, WavReader};
 
 /// Decoder for the WAV format.
-pub struct WavDecoder<R>
+pub struct WavDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    reader: SamplesIterator<R>,
+    reader: SamplesIterator<R, S>,
     total_duration: Duration,
     sample_rate: u32,
     channels: u16,
 }
 
-impl<R> WavDecoder<R>
+impl<R, S> WavDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     /// Attempts to decode the data as WAV.
-    pub fn new(mut data: R) -> Result<WavDecoder<R>, R> {
+    pub fn new(mut data: R) -> Result<WavDecoder<R, S>, R> {
         if !is_wave(data.by_ref()) {
             return Err(data);
         }
@@ -32,6 +38,7 @@ where
         let reader = SamplesIterator {
             reader,
             samples_read: 0,
+            marker: PhantomData,
         };
 
         let sample_rate = spec.sample_rate;
@@ -51,44 +58,36 @@ where
     }
 }
 
-struct SamplesIterator<R>
+struct SamplesIterator<R, S>
 where
     R: Read + Seek,
 {
     reader: WavReader<R>,
     samples_read: u32,
+    marker: PhantomData<S>,
 }
 
-impl<R> Iterator for SamplesIterator<R>
+impl<R, S> Iterator for SamplesIterator<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
+    fn next(&mut self) -> Option<S> {
         let spec = self.reader.spec();
         match (spec.sample_format, spec.bits_per_sample) {
             (SampleFormat::Float, 32) => self.reader.samples().next().map(|value| {
                 self.samples_read += 1;
-                f32_to_i16(value.unwrap_or(0.0))
-            }),
-            (SampleFormat::Int, 8) => self.reader.samples().next().map(|value| {
-                self.samples_read += 1;
-                i8_to_i16(value.unwrap_or(0))
-            }),
-            (SampleFormat::Int, 16) => self.reader.samples().next().map(|value| {
-                self.samples_read += 1;
-                value.unwrap_or(0)
-            }),
-            (SampleFormat::Int, 24) => self.reader.samples().next().map(|value| {
-                self.samples_read += 1;
-                i24_to_i16(value.unwrap_or(0))
-            }),
-            (SampleFormat::Int, 32) => self.reader.samples().next().map(|value| {
-                self.samples_read += 1;
-                i32_to_i16(value.unwrap_or(0))
+                S::from_f32(value.unwrap_or(0.0))
             }),
+            (SampleFormat::Int, bits_per_sample @ (8 | 16 | 24 | 32)) => {
+                self.reader.samples().next().map(|value| {
+                    self.samples_read += 1;
+                    S::from_int(value.unwrap_or(0), bits_per_sample as u32)
+                })
+            }
             (sample_format, bits_per_sample) => panic!(
                 "Unimplemented wav spec: {:?}, {}",
                 sample_format, bits_per_sample
@@ -103,11 +102,17 @@ where
     }
 }
 
-impl<R> ExactSizeIterator for SamplesIterator<R> where R: Read + Seek {}
+impl<R, S> ExactSizeIterator for SamplesIterator<R, S>
+where
+    R: Read + Seek,
+    S: DecoderSample,
+{
+}
 
-impl<R> Source for WavDecoder<R>
+impl<R, S> Source for WavDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -128,16 +133,29 @@ where
     fn total_duration(&self) -> Option<Duration> {
         Some(self.total_duration)
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let file_len = self.reader.reader.duration();
+        let new_pos = (pos.as_secs_f64() * self.sample_rate as f64) as u32;
+        // Seeking past the end is left to the `Seek` implementation by hound, so clamp it.
+        let new_pos = new_pos.min(file_len);
+
+        self.reader.reader.seek(new_pos)?;
+        self.reader.samples_read = new_pos * self.channels as u32;
+        Ok(())
+    }
 }
 
-impl<R> Iterator for WavDecoder<R>
+impl<R, S> Iterator for WavDecoder<R, S>
 where
     R: Read + Seek,
+    S: DecoderSample,
 {
-    type Item = i16;
+    type Item = S;
 
     #[inline]
-    fn next(&mut self) -> Option<i16> {
+    fn next(&mut self) -> Option<S> {
         self.reader.next()
     }
 
@@ -147,7 +165,12 @@ where
     }
 }
 
-impl<R> ExactSizeIterator for WavDecoder<R> where R: Read + Seek {}
+impl<R, S> ExactSizeIterator for WavDecoder<R, S>
+where
+    R: Read + Seek,
+    S: DecoderSample,
+{
+}
 
 /// Returns true if the stream contains WAV data, then resets it to where it was.
 fn is_wave<R>(mut data: R) -> bool
@@ -164,34 +187,3 @@ where
     data.seek(SeekFrom::Start(stream_pos)).unwrap();
     true
 }
-
-/// Returns a 32 bit WAV float as an i16. WAV floats are typically in the range of
-/// [-1.0, 1.0] while i16s are in the range [-32768, 32767]. Note that this
-/// function definitely causes precision loss but hopefully this isn't too
-/// audiable when actually playing?
-fn f32_to_i16(f: f32) -> i16 {
-    // prefer to clip the input rather than be excessively loud.
-    (f.max(-1.0).min(1.0) * i16::max_value() as f32) as i16
-}
-
-/// Returns an 8-bit WAV int as an i16. This scales the sample value by a factor
-/// of 256.
-fn i8_to_i16(i: i8) -> i16 {
-    i as i16 * 256
-}
-
-/// Returns a 24 bit WAV int as an i16. Note that this is a 24 bit integer, not a
-/// 32 bit one. 24 bit ints are in the range [−8,388,608, 8,388,607] while i16s
-/// are in the range [-32768, 32767]. Note that this function definitely causes
-/// precision loss but hopefully this isn't too audiable when actually playing?
-fn i24_to_i16(i: i32) -> i16 {
-    (i >> 8) as i16
-}
-
-/// Returns a 32 bit WAV int as an i16. 32 bit ints are in the range
-/// [-2,147,483,648, 2,147,483,647] while i16s are in the range [-32768, 32767].
-/// Note that this function definitely causes precision loss but hopefully this
-/// isn't too audiable when actually playing?
-fn i32_to_i16(i: i32) -> i16 {
-    (i >> 16) as i16
-}
@@ -0,0 +1,80 @@
+//! Listing of the output devices.
+
+use crate::stream::StreamError;
+use cpal::traits::{DeviceTrait, HostTrait};
+
+/// Description of an output device, as returned by `output_devices`.
+pub struct OutputDeviceInfo {
+    device: cpal::Device,
+    name: String,
+    is_default: bool,
+    default_config: Option<cpal::SupportedStreamConfig>,
+    supported_configs: Vec<cpal::SupportedStreamConfigRange>,
+}
+
+impl OutputDeviceInfo {
+    /// Returns the device, which can be passed to `OutputStream::try_from_device`.
+    #[inline]
+    pub fn device(&self) -> &cpal::Device {
+        &self.device
+    }
+
+    /// Returns the device.
+    #[inline]
+    pub fn into_device(self) -> cpal::Device {
+        self.device
+    }
+
+    /// Returns the name of the device, which can be passed to
+    /// `OutputStream::try_from_device_name`.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if this is the default output device of the system.
+    #[inline]
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    /// Returns the config used by `OutputStream::try_from_device`, if the device has one.
+    #[inline]
+    pub fn default_config(&self) -> Option<&cpal::SupportedStreamConfig> {
+        self.default_config.as_ref()
+    }
+
+    /// Returns the ranges of configs supported by the device.
+    #[inline]
+    pub fn supported_configs(&self) -> &[cpal::SupportedStreamConfigRange] {
+        &self.supported_configs
+    }
+}
+
+/// Lists the output devices of the default host.
+///
+/// Devices whose name can't be read, which are usually unavailable, are skipped.
+pub fn output_devices() -> Result<Vec<OutputDeviceInfo>, StreamError> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+
+    let devices = host
+        .output_devices()?
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let default_config = device.default_output_config().ok();
+            let supported_configs = device
+                .supported_output_configs()
+                .map(|configs| configs.collect())
+                .unwrap_or_default();
+            Some(OutputDeviceInfo {
+                is_default: default_name.as_ref() == Some(&name),
+                device,
+                name,
+                default_config,
+                supported_configs,
+            })
+        })
+        .collect();
+    Ok(devices)
+}
@@ -4,8 +4,16 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::source::{Source, UniformSourceIterator};
-use crate::Sample;
+use crate::lock_free::{AtomicF32, AtomicStack, List, Node};
+use crate::source::{ChannelLayout, CompressorState, Source, UniformSourceIterator};
+use crate::{ChannelMixing, ResampleQuality, Sample};
+use cpal::Sample as CpalSample;
+
+/// Level above which the limiter of the mixer reduces the volume, in dBFS.
+const LIMITER_THRESHOLD_DB: f32 = -1.0;
+
+/// How fast the limiter of the mixer goes back to the normal volume.
+const LIMITER_RELEASE: Duration = Duration::from_millis(100);
 
 /// Builds a new mixer.
 ///
@@ -21,27 +29,43 @@ where
     S: Sample + Send + 'static,
 {
     let input = Arc::new(DynamicMixerController {
-        has_pending: AtomicBool::new(false),
-        pending_sources: Mutex::new(Vec::new()),
+        pending_sources: AtomicStack::new(),
+        finished_sources: AtomicStack::new(),
+        limiter_enabled: AtomicBool::new(false),
+        resample_quality: Mutex::new(ResampleQuality::default()),
+        channel_mixing: Mutex::new(ChannelMixing::default()),
         channels,
         sample_rate,
     });
 
     let output = DynamicMixer {
-        current_sources: Vec::with_capacity(16),
+        current_sources: List::new(),
         input: input.clone(),
         sample_count: 0,
-        still_pending: vec![],
-        still_current: vec![],
+        still_pending: List::new(),
+        limiter: CompressorState::new(
+            LIMITER_THRESHOLD_DB,
+            f32::INFINITY,
+            Duration::from_secs(0),
+            LIMITER_RELEASE,
+        ),
     };
 
     (input, output)
 }
 
 /// The input of the mixer.
+///
+/// The sources are sent to the mixer and back without locking, so that the thread playing the
+/// mixer never waits. The sources that ended are dropped by the next call to `add` or
+/// `collect_finished`, or with the controller.
 pub struct DynamicMixerController<S> {
-    has_pending: AtomicBool,
-    pending_sources: Mutex<Vec<Box<dyn Source<Item = S> + Send>>>,
+    pending_sources: AtomicStack<Box<dyn Source<Item = S> + Send>>,
+    // Sources that ended, sent back by the mixer to be dropped here.
+    finished_sources: AtomicStack<Box<dyn Source<Item = S> + Send>>,
+    limiter_enabled: AtomicBool,
+    resample_quality: Mutex<ResampleQuality>,
+    channel_mixing: Mutex<ChannelMixing>,
     channels: u16,
     sample_rate: u32,
 }
@@ -50,25 +74,254 @@ impl<S> DynamicMixerController<S>
 where
     S: Sample + Send + 'static,
 {
-    /// Adds a new source to mix to the existing ones.
+    /// Adds a new source to mix to the existing ones. Returns a handle to control it while it
+    /// plays.
     #[inline]
-    pub fn add<T>(&self, source: T)
+    pub fn add<T>(&self, source: T) -> SoundHandle
     where
         T: Source<Item = S> + Send + 'static,
     {
-        let uniform_source = UniformSourceIterator::new(source, self.channels, self.sample_rate);
+        let uniform_source = self.convert(source);
+        let controls = Arc::new(SoundControls {
+            stopped: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            volume: AtomicF32::new(1.0),
+            finished: AtomicBool::new(false),
+        });
+        let source = ControlledSource {
+            input: uniform_source,
+            controls: controls.clone(),
+            paused: false,
+            channel: 0,
+        };
+        self.collect_finished();
         self.pending_sources
-            .lock()
-            .unwrap()
-            .push(Box::new(uniform_source) as Box<_>);
-        self.has_pending.store(true, Ordering::SeqCst); // TODO: can we relax this ordering?
+            .push(Node::new(Box::new(source) as Box<_>));
+        SoundHandle { controls }
+    }
+
+    /// Drops the sources that have ended. They are sent back by the thread playing the mixer,
+    /// which doesn't free memory.
+    ///
+    /// Done by `add` as well, so this is only useful if no source is added for a while.
+    #[inline]
+    pub fn collect_finished(&self) {
+        drop(self.finished_sources.take_all());
+    }
+
+    /// Returns the number of channels of the mixer.
+    #[inline]
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Returns the sample rate of the mixer.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Converts a source to the format of the mixer, with the current quality and mixing.
+    ///
+    /// The conversion is set up by the calling thread, so that the thread playing the mixer
+    /// doesn't have to compute filters or matrices for the format of the source.
+    pub(crate) fn convert<T>(&self, source: T) -> UniformSourceIterator<T, S>
+    where
+        T: Source,
+        T::Item: Sample,
+    {
+        let quality = *self.resample_quality.lock().unwrap();
+        let mixing = self.channel_mixing.lock().unwrap().clone();
+        UniformSourceIterator::with_options(
+            source,
+            self.channels,
+            self.sample_rate,
+            quality,
+            mixing,
+        )
+    }
+
+    /// Enables or disables the limiter of the mixer. Disabled by default.
+    ///
+    /// Without the limiter, the sum of loud sounds is clipped. With it, the volume of the mix is
+    /// reduced when it gets close to the maximum, so that it never clips.
+    #[inline]
+    pub fn set_limiter(&self, enabled: bool) {
+        self.limiter_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the algorithm used to convert the sample rate of the sources added from now on.
+    /// `ResampleQuality::Linear` by default.
+    #[inline]
+    pub fn set_resample_quality(&self, quality: ResampleQuality) {
+        *self.resample_quality.lock().unwrap() = quality;
+    }
+
+    /// Sets how the channels of the sources added from now on are converted to the channels of
+    /// the mixer. `ChannelMixing::Simple` by default.
+    #[inline]
+    pub fn set_channel_mixing(&self, mixing: ChannelMixing) {
+        *self.channel_mixing.lock().unwrap() = mixing;
+    }
+}
+
+/// Controls a sound added to the mixer, without the cost of a `Sink`.
+///
+/// Dropping the handle doesn't stop the sound.
+#[derive(Clone, Debug)]
+pub struct SoundHandle {
+    controls: Arc<SoundControls>,
+}
+
+#[derive(Debug)]
+struct SoundControls {
+    stopped: AtomicBool,
+    paused: AtomicBool,
+    volume: AtomicF32,
+    finished: AtomicBool,
+}
+
+impl SoundHandle {
+    /// Stops the sound. It can't be resumed.
+    #[inline]
+    pub fn stop(&self) {
+        self.controls.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Pauses the sound. It plays silence in the mix until it is resumed with `play`.
+    #[inline]
+    pub fn pause(&self) {
+        self.controls.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes the sound after a call to `pause`.
+    #[inline]
+    pub fn play(&self) {
+        self.controls.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns whether the sound is paused.
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.controls.paused.load(Ordering::Relaxed)
+    }
+
+    /// Sets the factor the samples of the sound are multiplied by. 1.0 by default.
+    #[inline]
+    pub fn set_volume(&self, volume: f32) {
+        self.controls.volume.store(volume);
+    }
+
+    /// Returns the volume of the sound.
+    #[inline]
+    pub fn volume(&self) -> f32 {
+        self.controls.volume.load()
+    }
+
+    /// Returns whether the sound has ended, was stopped, or was removed from the mixer because
+    /// the mixer was dropped.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.controls.finished.load(Ordering::Relaxed)
+    }
+}
+
+/// A source of the mixer, controlled by a `SoundHandle`.
+struct ControlledSource<I> {
+    input: I,
+    controls: Arc<SoundControls>,
+    // Whether the current frame is silent. Only changes at frame boundaries, so that the
+    // channels stay in place.
+    paused: bool,
+    // Channel of the next sample.
+    channel: u16,
+}
+
+impl<I> Iterator for ControlledSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        if self.controls.stopped.load(Ordering::Relaxed) {
+            self.controls.finished.store(true, Ordering::Relaxed);
+            return None;
+        }
+        if self.channel == 0 {
+            self.paused = self.controls.paused.load(Ordering::Relaxed);
+        }
+        self.channel = (self.channel + 1) % self.input.channels();
+        if self.paused {
+            return Some(I::Item::zero_value());
+        }
+        let volume = self.controls.volume.load();
+        let sample = match self.input.next() {
+            Some(sample) => sample,
+            None => {
+                // The source is only dropped later, by another thread.
+                self.controls.finished.store(true, Ordering::Relaxed);
+                return None;
+            }
+        };
+        if volume == 1.0 {
+            Some(sample)
+        } else {
+            Some(sample.amplify(volume))
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // The sound can be stopped or paused at any time.
+        (0, None)
+    }
+}
+
+impl<I> Source for ControlledSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl<I> Drop for ControlledSource<I> {
+    fn drop(&mut self) {
+        // The mixer drops the sources that are still playing.
+        self.controls.finished.store(true, Ordering::Relaxed);
     }
 }
 
 /// The output of the mixer. Implements `Source`.
 pub struct DynamicMixer<S> {
     // The current iterator that produces samples.
-    current_sources: Vec<Box<dyn Source<Item = S> + Send>>,
+    current_sources: List<Box<dyn Source<Item = S> + Send>>,
 
     // The pending sounds.
     input: Arc<DynamicMixerController<S>>,
@@ -76,11 +329,11 @@ pub struct DynamicMixer<S> {
     // The number of samples produced so far.
     sample_count: usize,
 
-    // A temporary vec used in start_pending_sources.
-    still_pending: Vec<Box<dyn Source<Item = S> + Send>>,
+    // The sources waiting for their first channel, in the order they were added.
+    still_pending: List<Box<dyn Source<Item = S> + Send>>,
 
-    // A temporary vec used in sum_current_sources.
-    still_current: Vec<Box<dyn Source<Item = S> + Send>>,
+    // The state of the limiter, used if it is enabled by the controller.
+    limiter: CompressorState,
 }
 
 impl<S> Source for DynamicMixer<S>
@@ -116,7 +369,7 @@ where
 
     #[inline]
     fn next(&mut self) -> Option<S> {
-        if self.input.has_pending.load(Ordering::SeqCst) {
+        if !self.input.pending_sources.is_empty() || !self.still_pending.is_empty() {
             self.start_pending_sources();
         }
 
@@ -146,35 +399,46 @@ where
     // in-step with the modulo of the samples produced so far. Otherwise, the
     // sound will play on the wrong channels, e.g. left / right will be reversed.
     fn start_pending_sources(&mut self) {
-        let mut pending = self.input.pending_sources.lock().unwrap(); // TODO: relax ordering?
-
-        for source in pending.drain(..) {
-            let in_step = self.sample_count % source.channels() as usize == 0;
-
-            if in_step {
-                self.current_sources.push(source);
-            } else {
-                self.still_pending.push(source);
-            }
-        }
-        std::mem::swap(&mut self.still_pending, &mut pending);
-
-        let has_pending = !pending.is_empty();
-        self.input.has_pending.store(has_pending, Ordering::SeqCst); // TODO: relax ordering?
+        // The stack returns the last added source first.
+        let mut pending = self.input.pending_sources.take_all();
+        pending.reverse();
+        self.still_pending.append(pending);
+
+        let sample_count = self.sample_count;
+        let in_step = self
+            .still_pending
+            .retain(|source| sample_count % source.channels() as usize != 0);
+        self.current_sources.append(in_step);
     }
 
     fn sum_current_sources(&mut self) -> S {
+        let limiter_enabled = self.input.limiter_enabled.load(Ordering::Relaxed);
         let mut sum = S::zero_value();
-
-        for mut source in self.current_sources.drain(..) {
-            if let Some(value) = source.next() {
-                sum = sum.saturating_add(value);
-                self.still_current.push(source);
+        // The limiter needs the sum before it is clipped.
+        let mut unclipped_sum = 0.0f32;
+
+        let finished = self.current_sources.retain(|source| match source.next() {
+            Some(value) => {
+                if limiter_enabled {
+                    unclipped_sum += value.to_f32();
+                } else {
+                    sum = sum.saturating_add(value);
+                }
+                true
             }
+            None => false,
+        });
+        // Dropping the sources could free memory, which mustn't happen on this thread.
+        self.input.finished_sources.push_list(finished);
+
+        if limiter_enabled {
+            let limited =
+                self.limiter
+                    .process(unclipped_sum, self.input.channels, self.input.sample_rate);
+            CpalSample::from(&limited)
+        } else {
+            sum
         }
-        std::mem::swap(&mut self.still_current, &mut self.current_sources);
-
-        sum
     }
 }
 
@@ -183,6 +447,7 @@ mod tests {
     use crate::buffer::SamplesBuffer;
     use crate::dynamic_mixer;
     use crate::source::Source;
+    use crate::{ChannelMixing, ResampleQuality};
 
     #[test]
     fn basic() {
@@ -264,4 +529,117 @@ mod tests {
 
         assert_eq!(rx.next(), None);
     }
+
+    #[test]
+    fn limiter() {
+        let (tx, rx) = dynamic_mixer::mixer(1, 48000);
+        tx.set_limiter(true);
+
+        tx.add(SamplesBuffer::new(1, 48000, vec![0.8f32; 100]));
+        tx.add(SamplesBuffer::new(1, 48000, vec![0.8f32; 100]));
+
+        let samples: Vec<f32> = rx.collect();
+        assert_eq!(samples.len(), 100);
+        assert!(samples.iter().all(|s| (s - 0.8913).abs() < 1e-3));
+    }
+
+    #[test]
+    fn limiter_with_integer_samples() {
+        let (tx, rx) = dynamic_mixer::mixer(1, 48000);
+        tx.set_limiter(true);
+
+        tx.add(SamplesBuffer::new(1, 48000, vec![30000i16, -30000]));
+        tx.add(SamplesBuffer::new(1, 48000, vec![30000i16, -30000]));
+
+        let samples: Vec<i16> = rx.collect();
+        assert!(samples[0] > 29000 && samples[0] < 29300);
+        assert!(samples[1] < -29000 && samples[1] > -29300);
+    }
+
+    #[test]
+    fn sinc_resampling() {
+        let (tx, rx) = dynamic_mixer::mixer(2, 48000);
+        tx.set_resample_quality(ResampleQuality::Sinc);
+
+        tx.add(SamplesBuffer::new(1, 24000, vec![0.5f32; 2400]));
+
+        let samples: Vec<f32> = rx.collect();
+        assert_eq!(samples.len(), 2 * 4800);
+        // Away from the edges, a constant signal stays constant.
+        assert!(samples[200..9400].iter().all(|s| (s - 0.5).abs() < 1e-3));
+    }
+
+    #[test]
+    fn standard_channel_mixing() {
+        let (tx, rx) = dynamic_mixer::mixer(2, 48000);
+        tx.set_channel_mixing(ChannelMixing::Standard);
+
+        tx.add(SamplesBuffer::new(1, 48000, vec![0.5f32, -0.5]));
+        tx.add(SamplesBuffer::new(
+            6,
+            48000,
+            vec![0.0f32, 0.0, 0.5, 0.0, 0.0, 0.0],
+        ));
+
+        let samples: Vec<f32> = rx.collect();
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        let expected = [h, h, -0.5 * h, -0.5 * h];
+        assert_eq!(samples.len(), expected.len());
+        assert!(samples
+            .iter()
+            .zip(&expected)
+            .all(|(s, e)| (s - e).abs() < 1e-4));
+    }
+
+    #[test]
+    fn sound_handle() {
+        let (tx, mut rx) = dynamic_mixer::mixer(1, 48000);
+
+        let first = tx.add(SamplesBuffer::new(1, 48000, vec![10i16; 6]));
+        let second = tx.add(SamplesBuffer::new(1, 48000, vec![1i16; 4]));
+        assert!(!first.is_finished());
+
+        assert_eq!(rx.next(), Some(11));
+        first.set_volume(0.5);
+        assert_eq!(rx.next(), Some(6));
+        second.pause();
+        assert_eq!(rx.next(), Some(5));
+        second.play();
+        assert_eq!(rx.next(), Some(6));
+        first.stop();
+        assert_eq!(rx.next(), Some(1));
+        assert!(first.is_finished());
+        assert!(!second.is_finished());
+
+        assert_eq!(rx.next(), None);
+        assert!(second.is_finished());
+    }
+
+    #[test]
+    fn pause_keeps_channels_in_place() {
+        let (tx, mut rx) = dynamic_mixer::mixer(2, 48000);
+        let sound = tx.add(SamplesBuffer::new(
+            2,
+            48000,
+            vec![1.0f32, 0.0, 1.0, 0.0, 1.0, 0.0],
+        ));
+
+        // Paused for an odd number of samples, starting in the middle of a frame.
+        assert_eq!(rx.next(), Some(1.0));
+        sound.pause();
+        assert_eq!(rx.by_ref().take(3).collect::<Vec<_>>(), vec![0.0, 0.0, 0.0]);
+        sound.play();
+        assert_eq!(rx.collect::<Vec<_>>(), vec![1.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn collect_finished() {
+        let (tx, mut rx) = dynamic_mixer::mixer(1, 48000);
+        tx.add(SamplesBuffer::new(1, 48000, vec![10i16; 2]));
+        assert_eq!(rx.by_ref().count(), 2);
+
+        assert!(!tx.finished_sources.is_empty());
+        tx.collect_finished();
+        assert!(tx.finished_sources.is_empty());
+    }
 }
@@ -0,0 +1,352 @@
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::source::{Source, UniformSourceIterator};
+use crate::stream::{supported_formats, ErrorCallback, StreamError};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::Sample;
+
+/// Number of chunks of samples that can be waiting to be read from an `InputSource`.
+///
+/// If the source isn't read fast enough, the audio captured once this limit is reached is lost.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Number of chunks that can be in use at the same time: the ones waiting to be read, the one
+/// being read by the `InputSource` and the one being filled by the device.
+const CHUNK_COUNT: usize = CHANNEL_CAPACITY + 2;
+
+/// Number of samples that the chunks of a device can hold before they have to grow.
+const PREALLOCATED_CHUNK_LEN: usize = 4096;
+
+/// Number of frames per chunk produced by `InputStream::from_source`.
+const SIMULATED_CHUNK_FRAMES: usize = 512;
+
+/// Records audio from an input device, such as a microphone. Also see `InputSource`, which
+/// produces the recorded samples.
+///
+/// If this is dropped the recording stops, and the `InputSource` ends once it has returned the
+/// samples that were already captured.
+pub struct InputStream {
+    // `None` if the samples are produced by `InputStream::from_source`.
+    _stream: Option<cpal::Stream>,
+    // Stops the thread of `InputStream::from_source`.
+    stop: Arc<AtomicBool>,
+    error_callback: Arc<Mutex<Option<ErrorCallback>>>,
+}
+
+impl Drop for InputStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Source that produces the audio recorded by an `InputStream`.
+///
+/// Reading a sample blocks until the device has captured it, so this source is typically read
+/// by a thread of its own, or played with an `OutputStream` that paces it.
+pub struct InputSource {
+    receiver: Receiver<Vec<f32>>,
+    // Gives the chunks that have been read back to the device, so that its callback doesn't
+    // allocate.
+    recycle: SyncSender<Vec<f32>>,
+    buffer: Vec<f32>,
+    pos: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl InputStream {
+    /// Returns a new stream & source recording from the given input device.
+    pub fn try_from_device(device: &cpal::Device) -> Result<(Self, InputSource), StreamError> {
+        let default_format = device.default_input_config()?;
+        let error_callback = Arc::new(Mutex::new(None));
+
+        let (stream, source) =
+            new_input_stream_with_format(device, default_format, &error_callback).or_else(
+                |err| {
+                    // look through all supported formats to see if another works
+                    supported_formats(device.supported_input_configs()?.collect())
+                        .filter_map(|format| {
+                            new_input_stream_with_format(device, format, &error_callback).ok()
+                        })
+                        .next()
+                        // return original error if nothing works
+                        .ok_or(StreamError::BuildStreamError(err))
+                },
+            )?;
+        stream.play()?;
+
+        let out = Self {
+            _stream: Some(stream),
+            stop: Arc::new(AtomicBool::new(false)),
+            error_callback,
+        };
+        Ok((out, source))
+    }
+
+    /// Return a new stream & source recording from the default input device.
+    ///
+    /// On failure will fallback to trying any non-default input devices.
+    pub fn try_default() -> Result<(Self, InputSource), StreamError> {
+        let default_device = cpal::default_host()
+            .default_input_device()
+            .ok_or(StreamError::NoDevice)?;
+
+        let default_stream = Self::try_from_device(&default_device);
+
+        default_stream.or_else(|original_err| {
+            // default device didn't work, try other ones
+            let mut devices = match cpal::default_host().input_devices() {
+                Ok(d) => d,
+                Err(_) => return Err(original_err),
+            };
+
+            devices
+                .find_map(|d| Self::try_from_device(&d).ok())
+                .ok_or(original_err)
+        })
+    }
+
+    /// Returns a stream that behaves like an input device capturing `source`.
+    ///
+    /// The samples are produced by a background thread instead of an audio device, which makes
+    /// it possible to test code that records audio on machines that have no microphone. The
+    /// channels and sample rate of the recording are the ones `source` has when this is called.
+    pub fn from_source<S>(source: S) -> (Self, InputSource)
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let mut source: UniformSourceIterator<S, f32> =
+            UniformSourceIterator::new(source, channels, sample_rate);
+
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        // The chunks are allocated by the thread, there is no need to recycle them.
+        let (recycle, _) = mpsc::sync_channel(0);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            let chunk_len = SIMULATED_CHUNK_FRAMES * channels as usize;
+            while !thread_stop.load(Ordering::Acquire) {
+                let chunk: Vec<f32> = source.by_ref().take(chunk_len).collect();
+                if chunk.is_empty() || sender.send(chunk).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let out = Self {
+            _stream: None,
+            stop,
+            error_callback: Arc::new(Mutex::new(None)),
+        };
+        (
+            out,
+            InputSource::new(receiver, recycle, channels, sample_rate),
+        )
+    }
+
+    /// Sets the function called when an error occurs on the device, for example if it is
+    /// unplugged. By default the errors are printed to the standard error.
+    ///
+    /// The callback is called by the audio thread, and shouldn't block.
+    pub fn set_error_callback<F>(&self, callback: F)
+    where
+        F: FnMut(cpal::StreamError) + Send + 'static,
+    {
+        *self.error_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+}
+
+impl InputSource {
+    #[inline]
+    fn new(
+        receiver: Receiver<Vec<f32>>,
+        recycle: SyncSender<Vec<f32>>,
+        channels: u16,
+        sample_rate: u32,
+    ) -> InputSource {
+        InputSource {
+            receiver,
+            recycle,
+            buffer: Vec::new(),
+            pos: 0,
+            channels,
+            sample_rate,
+        }
+    }
+}
+
+impl Iterator for InputSource {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        while self.pos >= self.buffer.len() {
+            // Fails once the stream has been dropped and every chunk has been read.
+            let chunk = self.receiver.recv().ok()?;
+            let used = mem::replace(&mut self.buffer, chunk);
+            if used.capacity() != 0 {
+                // Fails if the stream has been dropped, the chunk is then freed here.
+                let _ = self.recycle.try_send(used);
+            }
+            self.pos = 0;
+        }
+        let sample = self.buffer[self.pos];
+        self.pos += 1;
+        Some(sample)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.buffer.len() - self.pos, None)
+    }
+}
+
+impl Source for InputSource {
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+fn new_input_stream_with_format(
+    device: &cpal::Device,
+    format: cpal::SupportedStreamConfig,
+    error_callback: &Arc<Mutex<Option<ErrorCallback>>>,
+) -> Result<(cpal::Stream, InputSource), cpal::BuildStreamError> {
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+    let (recycle, spare_chunks) = mpsc::sync_channel(CHUNK_COUNT);
+    for _ in 0..CHUNK_COUNT {
+        recycle
+            .send(Vec::with_capacity(PREALLOCATED_CHUNK_LEN))
+            .unwrap();
+    }
+    let source = InputSource::new(receiver, recycle, format.channels(), format.sample_rate().0);
+
+    let error_callback = error_callback.clone();
+    let on_error = move |err| match &mut *error_callback.lock().unwrap() {
+        Some(callback) => callback(err),
+        None => eprintln!("an error occurred on input stream: {}", err),
+    };
+
+    match format.sample_format() {
+        cpal::SampleFormat::F32 => device.build_input_stream::<f32, _, _>(
+            &format.config(),
+            move |data, _| send_chunk(&sender, &spare_chunks, data),
+            on_error,
+        ),
+        cpal::SampleFormat::I16 => device.build_input_stream::<i16, _, _>(
+            &format.config(),
+            move |data, _| send_chunk(&sender, &spare_chunks, data),
+            on_error,
+        ),
+        cpal::SampleFormat::U16 => device.build_input_stream::<u16, _, _>(
+            &format.config(),
+            move |data, _| send_chunk(&sender, &spare_chunks, data),
+            on_error,
+        ),
+    }
+    .map(|stream| (stream, source))
+}
+
+/// Sends the samples captured by the device to the `InputSource`. Never blocks the audio thread:
+/// the samples are dropped if the source is too far behind.
+///
+/// The samples are copied into one of the chunks given back by the source, which only allocates
+/// if the device produces more samples at once than the chunk has ever held.
+fn send_chunk<T>(sender: &SyncSender<Vec<f32>>, spare_chunks: &Receiver<Vec<f32>>, data: &[T])
+where
+    T: Sample,
+{
+    // Every chunk is waiting to be read if there is none left, so the source is too far behind.
+    let mut chunk = match spare_chunks.try_recv() {
+        Ok(chunk) => chunk,
+        Err(_) => return,
+    };
+    chunk.clear();
+    chunk.extend(data.iter().map(|s| s.to_f32()));
+    // A disconnected channel means that the source has been dropped, which is fine.
+    let _ = sender.try_send(chunk);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::{send_chunk, InputSource, InputStream};
+    use crate::source::{SineWave, Source};
+
+    #[test]
+    fn simulated_recording() {
+        let signal = SineWave::new(440.0).take_duration(Duration::from_millis(100));
+        let expected: Vec<f32> = signal.clone().collect();
+
+        let (stream, source) = InputStream::from_source(signal);
+        assert_eq!(source.channels(), 1);
+        assert_eq!(source.sample_rate(), 48000);
+        let recorded: Vec<f32> = source.amplify(0.5).collect();
+        drop(stream);
+
+        assert_eq!(recorded.len(), expected.len());
+        for (r, e) in recorded.iter().zip(&expected) {
+            assert_eq!(*r, e * 0.5);
+        }
+    }
+
+    #[test]
+    fn recording_ends_when_stream_is_dropped() {
+        let (stream, mut source) = InputStream::from_source(SineWave::new(440.0));
+        assert!(source.next().is_some());
+        drop(stream);
+        // The chunks that were already sent are still played.
+        let len = 1 + source.by_ref().count();
+        assert_eq!(len % super::SIMULATED_CHUNK_FRAMES, 0);
+    }
+
+    #[test]
+    fn chunks_are_recycled() {
+        let (sender, receiver) = mpsc::sync_channel(4);
+        let (recycle, spare_chunks) = mpsc::sync_channel(2);
+        for _ in 0..2 {
+            recycle.send(Vec::with_capacity(2)).unwrap();
+        }
+        let mut source = InputSource::new(receiver, recycle, 1, 48000);
+
+        send_chunk(&sender, &spare_chunks, &[1.0f32, 2.0]);
+        send_chunk(&sender, &spare_chunks, &[3.0f32]);
+        // Both chunks are waiting to be read, so these samples are dropped.
+        send_chunk(&sender, &spare_chunks, &[4.0f32]);
+        assert_eq!(
+            source.by_ref().take(3).collect::<Vec<_>>(),
+            vec![1.0, 2.0, 3.0]
+        );
+
+        // The first chunk has been given back once the source moved to the second one.
+        send_chunk(&sender, &spare_chunks, &[5.0f32]);
+        assert!(spare_chunks.try_recv().is_err());
+        drop(sender);
+        assert_eq!(source.collect::<Vec<_>>(), vec![5.0]);
+    }
+}
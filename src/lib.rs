@@ -110,14 +110,20 @@
 //!
 #![cfg_attr(test, deny(missing_docs))]
 pub use cpal::{
-    self, traits::DeviceTrait, Device, Devices, DevicesError, InputDevices, OutputDevices,
-    SupportedStreamConfig,
+    self, traits::DeviceTrait, BufferSize, Device, Devices, DevicesError, InputDevices,
+    OutputDevices, SampleFormat, SampleRate, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange,
 };
 
 mod conversions;
+mod device;
+mod input;
+mod lock_free;
 mod sink;
 mod spatial_sink;
 mod stream;
+#[cfg(feature = "wav")]
+mod wav_output;
 
 pub mod buffer;
 pub mod decoder;
@@ -126,9 +132,19 @@ pub mod queue;
 pub mod source;
 pub mod static_buffer;
 
-pub use crate::conversions::Sample;
+pub use crate::conversions::{ChannelMatrix, ChannelMixing, ResampleQuality, Sample};
 pub use crate::decoder::Decoder;
+pub use crate::device::{output_devices, OutputDeviceInfo};
+pub use crate::dynamic_mixer::SoundHandle;
+pub use crate::input::{InputSource, InputStream};
 pub use crate::sink::Sink;
 pub use crate::source::Source;
 pub use crate::spatial_sink::SpatialSink;
-pub use crate::stream::{OutputStream, OutputStreamHandle, PlayError, StreamError};
+pub use crate::stream::{
+    OfflineOutputStream, OutputStream, OutputStreamBuilder, OutputStreamHandle, PlayError,
+    StreamError,
+};
+#[cfg(feature = "wav")]
+pub use crate::wav_output::{
+    output_to_wav, output_to_wav_with_format, output_to_wav_writer, WavSampleFormat,
+};
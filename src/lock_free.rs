@@ -0,0 +1,355 @@
+//! Primitives shared with the audio thread, which must never block nor allocate.
+//!
+//! The sounds are sent to the audio thread in nodes that are allocated by the thread that adds
+//! them. Once played, the audio thread sends the nodes back to be dropped by another thread.
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+
+/// An `f32` that can be shared between threads.
+#[derive(Debug)]
+pub(crate) struct AtomicF32 {
+    bits: AtomicU32,
+}
+
+impl AtomicF32 {
+    #[inline]
+    pub fn new(value: f32) -> AtomicF32 {
+        AtomicF32 {
+            bits: AtomicU32::new(value.to_bits()),
+        }
+    }
+
+    #[inline]
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn store(&self, value: f32) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// A node of a `List` or an `AtomicStack`.
+#[derive(Debug)]
+pub(crate) struct Node<T> {
+    pub value: T,
+    // Owned by the node when it is in a list, null otherwise.
+    next: *mut Node<T>,
+}
+
+// The next node is owned by the node.
+unsafe impl<T: Send> Send for Node<T> {}
+
+impl<T> Node<T> {
+    #[inline]
+    pub fn new(value: T) -> Box<Node<T>> {
+        Box::new(Node {
+            value,
+            next: ptr::null_mut(),
+        })
+    }
+}
+
+/// A singly linked list owned by one thread. Moving nodes in and out of it doesn't allocate.
+#[derive(Debug)]
+pub(crate) struct List<T> {
+    // Each node comes from `Box::into_raw`, and owns the next one.
+    head: *mut Node<T>,
+}
+
+unsafe impl<T: Send> Send for List<T> {}
+
+impl<T> List<T> {
+    #[inline]
+    pub fn new() -> List<T> {
+        List {
+            head: ptr::null_mut(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    #[inline]
+    pub fn front(&self) -> Option<&T> {
+        // Safe because the list owns its nodes.
+        unsafe { self.head.as_ref().map(|node| &node.value) }
+    }
+
+    #[inline]
+    pub fn push_front(&mut self, mut node: Box<Node<T>>) {
+        node.next = self.head;
+        self.head = Box::into_raw(node);
+    }
+
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<Box<Node<T>>> {
+        if self.head.is_null() {
+            return None;
+        }
+        // Safe because the list owns its nodes.
+        let mut node = unsafe { Box::from_raw(self.head) };
+        self.head = mem::replace(&mut node.next, ptr::null_mut());
+        Some(node)
+    }
+
+    /// Moves the nodes of `other` to the end of the list.
+    pub fn append(&mut self, mut other: List<T>) {
+        let mut tail = &mut self.head;
+        // Safe because the list owns its nodes.
+        while let Some(node) = unsafe { tail.as_mut() } {
+            tail = &mut node.next;
+        }
+        *tail = mem::replace(&mut other.head, ptr::null_mut());
+    }
+
+    /// Reverses the order of the nodes.
+    pub fn reverse(&mut self) {
+        let mut reversed = List::new();
+        while let Some(node) = self.pop_front() {
+            reversed.push_front(node);
+        }
+        *self = reversed;
+    }
+
+    /// Calls `f` on each value, in order, and moves the nodes for which it returns `false` to
+    /// another list, which is returned.
+    pub fn retain<F>(&mut self, mut f: F) -> List<T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let mut removed = List::new();
+        let mut link: *mut *mut Node<T> = &mut self.head;
+        // Safe because the list owns its nodes, and `link` points to the head or to the `next`
+        // of a node of the list.
+        unsafe {
+            while !(*link).is_null() {
+                let node = *link;
+                if f(&mut (*node).value) {
+                    link = &mut (*node).next;
+                } else {
+                    *link = mem::replace(&mut (*node).next, ptr::null_mut());
+                    removed.push_front(Box::from_raw(node));
+                }
+            }
+        }
+        removed.reverse();
+        removed
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+/// A stack that any thread can push nodes to without locking, and that is emptied at once.
+#[derive(Debug)]
+pub(crate) struct AtomicStack<T> {
+    // Like the head of a `List`.
+    head: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send> Send for AtomicStack<T> {}
+unsafe impl<T: Send> Sync for AtomicStack<T> {}
+
+impl<T> AtomicStack<T> {
+    #[inline]
+    pub fn new() -> AtomicStack<T> {
+        AtomicStack {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).is_null()
+    }
+
+    #[inline]
+    pub fn push(&self, node: Box<Node<T>>) {
+        let mut list = List::new();
+        list.push_front(node);
+        self.push_list(list);
+    }
+
+    /// Pushes all the nodes of a list. They are taken back in the reverse order.
+    pub fn push_list(&self, mut list: List<T>) {
+        let first = mem::replace(&mut list.head, ptr::null_mut());
+        if first.is_null() {
+            return;
+        }
+        let mut last = first;
+        // Safe because the nodes are owned by `first`, and not shared yet.
+        unsafe {
+            while !(*last).next.is_null() {
+                last = (*last).next;
+            }
+        }
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // `head` is only owned by the nodes if the exchange succeeds.
+            unsafe { (*last).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, first, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Removes all the nodes of the stack, the last pushed first.
+    #[inline]
+    pub fn take_all(&self) -> List<T> {
+        List {
+            head: self.head.swap(ptr::null_mut(), Ordering::Acquire),
+        }
+    }
+}
+
+impl<T> Drop for AtomicStack<T> {
+    fn drop(&mut self) {
+        drop(self.take_all());
+    }
+}
+
+/// Holds at most one node, which any thread can replace or take without locking.
+#[derive(Debug)]
+pub(crate) struct AtomicSlot<T> {
+    // Comes from `Box::into_raw`, or is null if the slot is empty.
+    node: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send> Send for AtomicSlot<T> {}
+unsafe impl<T: Send> Sync for AtomicSlot<T> {}
+
+impl<T> AtomicSlot<T> {
+    #[inline]
+    pub fn new() -> AtomicSlot<T> {
+        AtomicSlot {
+            node: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    #[inline]
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.node.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Puts a node in the slot, and returns the one it replaces.
+    #[inline]
+    pub fn replace(&self, node: Box<Node<T>>) -> Option<Box<Node<T>>> {
+        AtomicSlot::from_raw(self.node.swap(Box::into_raw(node), Ordering::AcqRel))
+    }
+
+    /// Empties the slot.
+    #[inline]
+    pub fn take(&self) -> Option<Box<Node<T>>> {
+        AtomicSlot::from_raw(self.node.swap(ptr::null_mut(), Ordering::Acquire))
+    }
+
+    #[inline]
+    fn from_raw(node: *mut Node<T>) -> Option<Box<Node<T>>> {
+        if node.is_null() {
+            None
+        } else {
+            // Safe because the node was owned by the slot.
+            Some(unsafe { Box::from_raw(node) })
+        }
+    }
+}
+
+impl<T> Drop for AtomicSlot<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{AtomicSlot, AtomicStack, List, Node};
+
+    fn values(mut list: List<u32>) -> Vec<u32> {
+        let mut values = Vec::new();
+        while let Some(node) = list.pop_front() {
+            values.push(node.value);
+        }
+        values
+    }
+
+    #[test]
+    fn list() {
+        let mut list = List::new();
+        list.push_front(Node::new(2));
+        list.push_front(Node::new(1));
+        let mut other = List::new();
+        other.push_front(Node::new(3));
+        list.append(other);
+        assert_eq!(list.front(), Some(&1));
+
+        let removed = list.retain(|v| *v != 2);
+        assert_eq!(values(removed), [2]);
+        list.reverse();
+        assert_eq!(values(list), [3, 1]);
+    }
+
+    #[test]
+    fn stack() {
+        let stack = AtomicStack::new();
+        assert!(stack.is_empty());
+        stack.push(Node::new(1));
+        let mut list = List::new();
+        list.push_front(Node::new(3));
+        list.push_front(Node::new(2));
+        stack.push_list(list);
+        assert_eq!(values(stack.take_all()), [2, 3, 1]);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn slot() {
+        let slot = AtomicSlot::new();
+        assert!(slot.is_empty());
+        assert!(slot.replace(Node::new(1)).is_none());
+        assert_eq!(slot.replace(Node::new(2)).map(|node| node.value), Some(1));
+        assert_eq!(slot.take().map(|node| node.value), Some(2));
+        assert!(slot.take().is_none());
+    }
+
+    #[test]
+    fn concurrent_pushes() {
+        let stack = Arc::new(AtomicStack::new());
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let stack = stack.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        stack.push(Node::new(t * 1000 + i));
+                    }
+                })
+            })
+            .collect();
+
+        let mut taken = Vec::new();
+        for thread in threads {
+            taken.extend(values(stack.take_all()));
+            thread.join().unwrap();
+        }
+        taken.extend(values(stack.take_all()));
+        taken.sort_unstable();
+        assert_eq!(taken, (0..4000).collect::<Vec<_>>());
+    }
+}
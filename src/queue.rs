@@ -1,11 +1,13 @@
 //! Queue that plays sounds one after the other.
 
+use std::cell::RefCell;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::{mpsc, Arc};
 use std::time::Duration;
 
-use crate::source::{Empty, Source, Zero};
+use crate::lock_free::{AtomicStack, List, Node};
+use crate::source::{ChannelLayout, Empty, SeekError, Source};
 use crate::Sample;
 
 /// Builds a new queue. It consists of an input and an output.
@@ -16,7 +18,8 @@ use crate::Sample;
 /// The parameter indicates how the queue should behave if the queue becomes empty:
 ///
 /// - If you pass `true`, then the queue is infinite and will play a silence instead until you add
-///   a new sound.
+///   a new sound. The silence is played one frame at a time, so that a new sound starts playing
+///   right away.
 /// - If you pass `false`, then the queue will report that it has finished playing.
 ///
 pub fn queue<S>(keep_alive_if_empty: bool) -> (Arc<SourcesQueueInput<S>>, SourcesQueueOutput<S>)
@@ -24,14 +27,18 @@ where
     S: Sample + Send + 'static,
 {
     let input = Arc::new(SourcesQueueInput {
-        next_sounds: Mutex::new(Vec::new()),
+        incoming: AtomicStack::new(),
+        played: AtomicStack::new(),
         keep_alive_if_empty: AtomicBool::new(keep_alive_if_empty),
     });
 
     let output = SourcesQueueOutput {
-        current: Box::new(Empty::<S>::new()) as Box<_>,
-        signal_after_end: None,
+        current: None,
+        empty: Empty::new(),
+        next_sounds: RefCell::new(List::new()),
         input: input.clone(),
+        frame_left: 0,
+        silence: None,
     };
 
     (input, output)
@@ -40,8 +47,16 @@ where
 // TODO: consider reimplementing this with `from_factory`
 
 /// The input of the queue.
+///
+/// The sounds are sent to the output and back without locking, so that the thread playing the
+/// output never waits. The sounds that were played are dropped by the next call to `append` or
+/// `collect_finished`, or with the input.
 pub struct SourcesQueueInput<S> {
-    next_sounds: Mutex<Vec<(Box<dyn Source<Item = S> + Send>, Option<Sender<()>>)>>,
+    // Sounds appended since the output last looked, the last one first.
+    incoming: AtomicStack<QueuedSound<S>>,
+
+    // Sounds sent back by the output to be dropped here.
+    played: AtomicStack<QueuedSound<S>>,
 
     // See constructor.
     keep_alive_if_empty: AtomicBool,
@@ -57,28 +72,33 @@ where
     where
         T: Source<Item = S> + Send + 'static,
     {
-        self.next_sounds
-            .lock()
-            .unwrap()
-            .push((Box::new(source) as Box<_>, None));
+        self.push(Box::new(source) as Box<_>, None);
     }
 
     /// Adds a new source to the end of the queue.
     ///
-    /// The `Receiver` will be signalled when the sound has finished playing.
+    /// The `Receiver` will be signalled when the sound has finished playing. Unlike the rest of
+    /// the queue, sending the signal briefly locks the channel on the thread playing the output.
     #[inline]
     pub fn append_with_signal<T>(&self, source: T) -> Receiver<()>
     where
         T: Source<Item = S> + Send + 'static,
     {
-        let (tx, rx) = mpsc::channel();
-        self.next_sounds
-            .lock()
-            .unwrap()
-            .push((Box::new(source) as Box<_>, Some(tx)));
+        // The output sends the signal without blocking nor allocating.
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.push(Box::new(source) as Box<_>, Some(tx));
         rx
     }
 
+    /// Drops the sounds that were played. They are sent back by the thread playing the output,
+    /// which doesn't free memory.
+    ///
+    /// Done by `append` as well, so this is only useful if no sound is added for a while.
+    #[inline]
+    pub fn collect_finished(&self) {
+        drop(self.played.take_all());
+    }
+
     /// Sets whether the queue stays alive if there's no more sound to play.
     ///
     /// See also the constructor.
@@ -86,71 +106,118 @@ where
         self.keep_alive_if_empty
             .store(keep_alive_if_empty, Ordering::Release);
     }
+
+    fn push(
+        &self,
+        sound: Box<dyn Source<Item = S> + Send>,
+        signal_after_end: Option<SyncSender<()>>,
+    ) {
+        self.collect_finished();
+        self.incoming.push(Node::new(QueuedSound {
+            sound,
+            signal_after_end,
+            end_signalled: false,
+        }));
+    }
+}
+
+/// A sound of the queue.
+struct QueuedSound<S> {
+    sound: Box<dyn Source<Item = S> + Send>,
+
+    // Signalled when the sound has finished playing.
+    signal_after_end: Option<SyncSender<()>>,
+    end_signalled: bool,
 }
 
 /// The output of the queue. Implements `Source`.
+///
+/// The boundary between two sounds is always a frame boundary, so that the channels and the
+/// sample rate of the next sound are reported as soon as the previous one has been played.
 pub struct SourcesQueueOutput<S> {
-    // The current iterator that produces samples.
-    current: Box<dyn Source<Item = S> + Send>,
+    // The current iterator that produces samples, or `None` to play `empty`.
+    current: Option<Box<Node<QueuedSound<S>>>>,
+    empty: Empty<S>,
 
-    // Signal this sender before picking from `next`.
-    signal_after_end: Option<Sender<()>>,
+    // The next sounds, in order. More may be waiting in `input`.
+    next_sounds: RefCell<List<QueuedSound<S>>>,
 
-    // The next sounds.
     input: Arc<SourcesQueueInput<S>>,
+
+    // Number of samples left in the frame that was started by the last call to `next`.
+    frame_left: usize,
+
+    // Channels and sample rate of the silence that is played instead of `current`, either
+    // because the queue is empty or to complete the frame of a sound that ended early.
+    silence: Option<(u16, u32)>,
 }
 
+/// Length of the frames of a sound that reports neither its frame length nor its length.
+const THRESHOLD: usize = 512;
+
 impl<S> Source for SourcesQueueOutput<S>
 where
     S: Sample + Send + 'static,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
-        // This function is non-trivial because the boundary between two sounds in the queue should
-        // be a frame boundary as well.
-        //
-        // The current sound is free to return `None` for `current_frame_len()`, in which case
-        // we *should* return the number of samples remaining the current sound.
-        // This can be estimated with `size_hint()`.
-        //
-        // If the `size_hint` is `None` as well, we are in the worst case scenario. To handle this
-        // situation we force a frame to have a maximum number of samples indicate by this
-        // constant.
-        const THRESHOLD: usize = 512;
-
-        // Try the current `current_frame_len`.
-        if let Some(val) = self.current.current_frame_len() {
-            if val != 0 {
-                return Some(val);
-            }
+        if self.frame_left != 0 {
+            return Some(self.frame_left);
         }
-
-        // Try the size hint.
-        let (lower_bound, _) = self.current.size_hint();
-        // The iterator default implementation just returns 0.
-        // That's a problematic value, so skip it.
-        if lower_bound > 0 {
-            return Some(lower_bound);
+        if let Some(len) = self.peek_next(|next| frame_len(next)) {
+            return Some(len);
         }
 
-        // Otherwise we use the constant value.
-        Some(THRESHOLD)
+        // The silence is played one frame at a time, so that a new sound can start right away.
+        match self.silence {
+            Some((channels, _)) => Some(channels as usize),
+            None => Some(frame_len(self.current())),
+        }
     }
 
     #[inline]
     fn channels(&self) -> u16 {
-        self.current.channels()
+        if let Some(channels) = self.peek_next(|next| next.channels()) {
+            return channels;
+        }
+        match self.silence {
+            Some((channels, _)) => channels,
+            None => self.current().channels(),
+        }
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        if let Some(layout) = self.peek_next(|next| next.channel_layout()) {
+            return layout;
+        }
+        match self.silence {
+            Some(_) => None,
+            None => self.current().channel_layout(),
+        }
     }
 
     #[inline]
     fn sample_rate(&self) -> u32 {
-        self.current.sample_rate()
+        if let Some(sample_rate) = self.peek_next(|next| next.sample_rate()) {
+            return sample_rate;
+        }
+        match self.silence {
+            Some((_, sample_rate)) => sample_rate,
+            None => self.current().sample_rate(),
+        }
     }
 
     #[inline]
     fn total_duration(&self) -> Option<Duration> {
         None
     }
+
+    /// Seeks in the sound that is currently playing.
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.current_mut().try_seek(pos)
+    }
 }
 
 impl<S> Iterator for SourcesQueueOutput<S>
@@ -162,22 +229,35 @@ where
     #[inline]
     fn next(&mut self) -> Option<S> {
         loop {
+            if self.frame_left == 0 {
+                self.start_frame()?;
+            }
+
+            if self.silence.is_some() {
+                self.frame_left -= 1;
+                return Some(S::zero_value());
+            }
+
             // Basic situation that will happen most of the time.
-            if let Some(sample) = self.current.next() {
+            if let Some(sample) = self.current_mut().next() {
+                self.frame_left -= 1;
+                if self.frame_left == 0 && has_ended(self.current()) {
+                    // Switch now, so that the format of the next sound is reported before its
+                    // first sample is requested.
+                    self.go_next();
+                }
                 return Some(sample);
             }
 
-            // Since `self.current` has finished, we need to pick the next sound.
+            // The sound has ended before the end of its frame.
             // In order to avoid inlining this expensive operation, the code is in another function.
-            if self.go_next().is_err() {
-                return None;
-            }
+            self.sound_ended()?;
         }
     }
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.current.size_hint().0, None)
+        (self.current().size_hint().0, None)
     }
 }
 
@@ -185,40 +265,193 @@ impl<S> SourcesQueueOutput<S>
 where
     S: Sample + Send + 'static,
 {
-    // Called when `current` is empty and we must jump to the next element.
-    // Returns `Ok` if the sound should continue playing, or an error if it should stop.
+    /// Plays the silence before the first sound in the given format, instead of the one of
+    /// `Empty`.
+    #[inline]
+    pub(crate) fn set_initial_format(&mut self, channels: u16, sample_rate: u32) {
+        if self.current.is_none() {
+            self.silence = Some((channels, sample_rate));
+        }
+    }
+
+    #[inline]
+    fn current(&self) -> &dyn Source<Item = S> {
+        match &self.current {
+            Some(current) => &*current.value.sound,
+            None => &self.empty,
+        }
+    }
+
+    #[inline]
+    fn current_mut(&mut self) -> &mut dyn Source<Item = S> {
+        match &mut self.current {
+            Some(current) => &mut *current.value.sound,
+            None => &mut self.empty,
+        }
+    }
+
+    // Moves the sounds that were appended to the input to `next_sounds`.
+    fn pull_incoming(&self) {
+        if self.input.incoming.is_empty() {
+            return;
+        }
+        // The stack returns the last appended sound first.
+        let mut incoming = self.input.incoming.take_all();
+        incoming.reverse();
+        self.next_sounds.borrow_mut().append(incoming);
+    }
+
+    // If the next frame is going to be read from the next sound of the queue, calls `f` with it.
+    //
+    // This is the case at the end of a frame if the current sound has ended or if a silence is
+    // playing, and lets the format of the next frame be reported before `next` switches to it.
+    fn peek_next<T, F>(&self, f: F) -> Option<T>
+    where
+        F: FnOnce(&dyn Source<Item = S>) -> T,
+    {
+        if self.frame_left != 0 || (self.silence.is_none() && !has_ended(self.current())) {
+            return None;
+        }
+        self.pull_incoming();
+        let next = self.next_sounds.borrow();
+        next.front().map(|next| f(&*next.sound))
+    }
+
+    // Called at a frame boundary. Returns `None` if the queue has finished playing.
+    fn start_frame(&mut self) -> Option<()> {
+        if self.silence.is_some() || has_ended(self.current()) {
+            self.go_next();
+        }
+        if self.silence.is_none() && has_ended(self.current()) {
+            return None;
+        }
+
+        self.frame_left = match self.silence {
+            Some((channels, _)) => channels as usize,
+            None => frame_len(self.current()),
+        };
+        Some(())
+    }
+
+    // Called when `current` has ended in the middle of a frame.
+    //
+    // The next sound continues the frame if it has the same format. Otherwise the frame is
+    // completed with silence, and the next sound starts at the next frame.
+    fn sound_ended(&mut self) -> Option<()> {
+        let format = (self.current().channels(), self.current().sample_rate());
+        self.pull_incoming();
+        let next_format = self
+            .next_sounds
+            .get_mut()
+            .front()
+            .map(|next| (next.sound.channels(), next.sound.sample_rate()));
+
+        match next_format {
+            Some(next_format) if next_format == format => {
+                self.go_next();
+            }
+            Some(_) => {
+                self.signal_end();
+                self.finish_current();
+                self.silence = Some(format);
+            }
+            None if self.input.keep_alive_if_empty.load(Ordering::Acquire) => {
+                self.go_next();
+            }
+            None => {
+                self.signal_end();
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    // Picks the next sound to play. If there is none, either plays a silence in the format of the
+    // previous sound or, if the queue shouldn't be kept alive, leaves the current sound as it is.
     //
     // This method is separate so that it is not inlined.
-    fn go_next(&mut self) -> Result<(), ()> {
-        if let Some(signal_after_end) = self.signal_after_end.take() {
-            let _ = signal_after_end.send(());
+    fn go_next(&mut self) {
+        self.signal_end();
+        // The sound has ended, but it may not have returned `None` yet if it reported its end with
+        // its frame length. Wrappers such as `Done` rely on it.
+        let _ = self.current_mut().next();
+
+        self.pull_incoming();
+        let next = self.next_sounds.get_mut().pop_front();
+        match next {
+            Some(next) => {
+                self.finish_current();
+                self.current = Some(next);
+                self.silence = None;
+            }
+            None if self.silence.is_none()
+                && self.input.keep_alive_if_empty.load(Ordering::Acquire) =>
+            {
+                self.silence = Some((self.current().channels(), self.current().sample_rate()));
+                self.finish_current();
+            }
+            None => (),
         }
+    }
 
-        let (next, signal_after_end) = {
-            let mut next = self.input.next_sounds.lock().unwrap();
-
-            if next.len() == 0 {
-                if self.input.keep_alive_if_empty.load(Ordering::Acquire) {
-                    // Play a short silence in order to avoid spinlocking.
-                    let silence = Zero::<S>::new(1, 44100); // TODO: meh
-                    (
-                        Box::new(silence.take_duration(Duration::from_millis(10))) as Box<_>,
-                        None,
-                    )
-                } else {
-                    return Err(());
+    fn signal_end(&mut self) {
+        if let Some(current) = &mut self.current {
+            let current = &mut current.value;
+            if !current.end_signalled {
+                if let Some(signal_after_end) = &current.signal_after_end {
+                    // Only the first signal matters, so a full channel can be ignored.
+                    let _ = signal_after_end.try_send(());
                 }
-            } else {
-                next.remove(0)
+                current.end_signalled = true;
             }
-        };
+        }
+    }
 
-        self.current = next;
-        self.signal_after_end = signal_after_end;
-        Ok(())
+    // Sends the current sound back to the input, which drops it. Dropping it here could free
+    // memory, which mustn't happen on the audio thread.
+    fn finish_current(&mut self) {
+        if let Some(current) = self.current.take() {
+            self.input.played.push(current);
+        }
     }
 }
 
+/// Returns the length of the next frame of a sound.
+fn frame_len<S>(source: &dyn Source<Item = S>) -> usize
+where
+    S: Sample,
+{
+    // The sound is free to return `None` for `current_frame_len()`, in which case we *should*
+    // return the number of samples remaining the sound.
+    // This can be estimated with `size_hint()`.
+    //
+    // If the `size_hint` is `None` as well, we are in the worst case scenario. To handle this
+    // situation we force a frame to have a maximum number of samples indicate by `THRESHOLD`.
+    if let Some(val) = source.current_frame_len() {
+        if val != 0 {
+            return val;
+        }
+    }
+
+    // The iterator default implementation just returns 0.
+    // That's a problematic value, so skip it.
+    let (lower_bound, _) = source.size_hint();
+    if lower_bound > 0 {
+        return lower_bound;
+    }
+
+    THRESHOLD
+}
+
+/// Returns true if the source tells that it has no more samples.
+#[inline]
+fn has_ended<S>(source: &dyn Source<Item = S>) -> bool
+where
+    S: Sample,
+{
+    source.current_frame_len() == Some(0) || source.size_hint().1 == Some(0)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::buffer::SamplesBuffer;
@@ -226,7 +459,6 @@ mod tests {
     use crate::source::Source;
 
     #[test]
-    #[ignore] // FIXME: samples rate and channel not updated immediately after transition
     fn basic() {
         let (tx, mut rx) = queue::queue(false);
 
@@ -270,7 +502,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // TODO: not yet implemented
     fn no_delay_when_added() {
         let (tx, mut rx) = queue::queue(true);
 
@@ -1,18 +1,25 @@
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::Receiver;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use crate::dynamic_mixer::DynamicMixerController;
+use crate::lock_free::{AtomicF32, AtomicSlot, AtomicStack, Node};
+use crate::source::{Done, SeekError};
 use crate::stream::{OutputStreamHandle, PlayError};
-use crate::{queue, source::Done, Sample, Source};
+use crate::{queue, Sample, Source};
 
 /// Handle to an device that outputs sounds.
 ///
 /// Dropping the `Sink` stops all sounds. You can use `detach` if you want the sounds to continue
 /// playing.
+///
+/// The audio thread doesn't free memory, so it sends the sounds that ended back to be dropped by
+/// the next call to `append`, `len`, `empty` or `sleep_until_end`, or with the sink.
 pub struct Sink {
     queue_tx: Arc<queue::SourcesQueueInput<f32>>,
-    sleep_until_end: Mutex<Option<Receiver<()>>>,
+    // Converts the sounds to the format of the stream. Dangling if the sink is idle.
+    mixer: Weak<DynamicMixerController<f32>>,
 
     controls: Arc<Controls>,
     sound_count: Arc<AtomicUsize>,
@@ -20,19 +27,92 @@ pub struct Sink {
     detached: bool,
 }
 
+// Read by the audio thread, so nothing in it is locked.
 struct Controls {
     pause: AtomicBool,
-    volume: Mutex<f32>,
+    volume: AtomicF32,
     stopped: AtomicBool,
-    speed: Mutex<f32>,
+    speed: AtomicF32,
+    playback_rate: AtomicF32,
+    seek: AtomicSlot<SeekOrder>,
+    // Orders sent back by the audio thread once performed, to be dropped by `try_seek`.
+    performed_seeks: AtomicStack<SeekOrder>,
+    // In nanoseconds.
+    position: AtomicU64,
+}
+
+/// How long `Sink::try_seek` waits for the audio thread to perform the seek.
+const SEEK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A seek requested by `Sink::try_seek`, performed on the audio thread.
+struct SeekOrder {
+    pos: Duration,
+    feedback: Arc<SeekFeedback>,
+    // Allocated by `try_seek`, so that the result is sent back without allocating.
+    result: Option<Box<Node<Result<(), SeekError>>>>,
+}
+
+// Shared by a `SeekOrder` and the `try_seek` call waiting for it.
+struct SeekFeedback {
+    result: AtomicSlot<Result<(), SeekError>>,
+    // Set by `try_seek` when it stops waiting, so that the order is dropped instead of
+    // performed. The order itself can't be removed, as the slot may already hold another one.
+    cancelled: AtomicBool,
+    // Woken up once the result is sent.
+    waiter: Thread,
+}
+
+impl SeekOrder {
+    #[inline]
+    fn new(pos: Duration) -> SeekOrder {
+        SeekOrder {
+            pos,
+            feedback: Arc::new(SeekFeedback {
+                result: AtomicSlot::new(),
+                cancelled: AtomicBool::new(false),
+                waiter: thread::current(),
+            }),
+            result: Some(Node::new(Ok(()))),
+        }
+    }
+
+    #[inline]
+    fn attempt<S>(&mut self, source: &mut S)
+    where
+        S: Source,
+        S::Item: Sample,
+    {
+        if self.feedback.cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        let res = source.try_seek(self.pos);
+        self.send(res);
+    }
+
+    // Sends the result to `try_seek` without locking nor allocating. Only the first one is sent.
+    #[inline]
+    fn send(&mut self, res: Result<(), SeekError>) {
+        if let Some(mut result) = self.result.take() {
+            result.value = res;
+            // The slot is empty, since each order sends a single result.
+            drop(self.feedback.result.replace(result));
+            self.feedback.waiter.unpark();
+        }
+    }
 }
 
 impl Sink {
     /// Builds a new `Sink`, beginning playback on a stream.
     #[inline]
     pub fn try_new(stream: &OutputStreamHandle) -> Result<Sink, PlayError> {
-        let (sink, queue_rx) = Sink::new_idle();
-        stream.play_raw(queue_rx)?;
+        let mixer = stream.mixer().ok_or(PlayError::NoDevice)?;
+        let (mut sink, mut queue_rx) = Sink::new_idle();
+        // The sounds are converted to the format of the stream when they are appended, so that
+        // the mixer never has to compute filters or matrices when the queue moves to the next
+        // sound.
+        queue_rx.set_initial_format(mixer.channels(), mixer.sample_rate());
+        sink.mixer = Arc::downgrade(&mixer);
+        mixer.add(queue_rx);
         Ok(sink)
     }
 
@@ -43,12 +123,16 @@ impl Sink {
 
         let sink = Sink {
             queue_tx,
-            sleep_until_end: Mutex::new(None),
+            mixer: Weak::new(),
             controls: Arc::new(Controls {
                 pause: AtomicBool::new(false),
-                volume: Mutex::new(1.0),
+                volume: AtomicF32::new(1.0),
                 stopped: AtomicBool::new(false),
-                speed: Mutex::new(1.0),
+                speed: AtomicF32::new(1.0),
+                playback_rate: AtomicF32::new(1.0),
+                seek: AtomicSlot::new(),
+                performed_seeks: AtomicStack::new(),
+                position: AtomicU64::new(0),
             }),
             sound_count: Arc::new(AtomicUsize::new(0)),
             detached: false,
@@ -67,6 +151,8 @@ impl Sink {
         let controls = self.controls.clone();
 
         let source = source
+            .track_position()
+            .time_stretch(1.0)
             .speed(1.0)
             .pausable(false)
             .amplify(1.0)
@@ -74,21 +160,57 @@ impl Sink {
             .periodic_access(Duration::from_millis(5), move |src| {
                 if controls.stopped.load(Ordering::SeqCst) {
                     src.stop();
+                    controls.position.store(0, Ordering::Relaxed);
                 } else {
-                    src.inner_mut().set_factor(*controls.volume.lock().unwrap());
+                    src.inner_mut().set_factor(controls.volume.load());
                     src.inner_mut()
                         .inner_mut()
                         .set_paused(controls.pause.load(Ordering::SeqCst));
                     src.inner_mut()
                         .inner_mut()
                         .inner_mut()
-                        .set_factor(*controls.speed.lock().unwrap());
+                        .set_factor(controls.speed.load());
+                    src.inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .set_rate(controls.playback_rate.load());
+
+                    // Seek below `Speed` and `TimeStretch`, so that the position is in the time
+                    // of the sound and not in the time of the output.
+                    let tracked = src
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut();
+                    if let Some(mut seek) = controls.seek.take() {
+                        seek.value.attempt(tracked);
+                        controls.performed_seeks.push(seek);
+                    }
+                    controls
+                        .position
+                        .store(tracked.get_pos().as_nanos() as u64, Ordering::Relaxed);
                 }
             })
             .convert_samples();
+        match self.mixer.upgrade() {
+            Some(mixer) => {
+                mixer.collect_finished();
+                self.push(mixer.convert(source));
+            }
+            None => self.push(source),
+        }
+    }
+
+    // Adds a sound to the queue, counting it until it ends.
+    fn push<S>(&self, source: S)
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
         self.sound_count.fetch_add(1, Ordering::Relaxed);
         let source = Done::new(source, self.sound_count.clone());
-        *self.sleep_until_end.lock().unwrap() = Some(self.queue_tx.append_with_signal(source));
+        self.queue_tx.append(source);
     }
 
     /// Gets the volume of the sound.
@@ -97,7 +219,7 @@ impl Sink {
     /// multiply each sample by this value.
     #[inline]
     pub fn volume(&self) -> f32 {
-        *self.controls.volume.lock().unwrap()
+        self.controls.volume.load()
     }
 
     /// Changes the volume of the sound.
@@ -106,7 +228,7 @@ impl Sink {
     /// multiply each sample by this value.
     #[inline]
     pub fn set_volume(&self, value: f32) {
-        *self.controls.volume.lock().unwrap() = value;
+        self.controls.volume.store(value);
     }
 
     /// Gets the speed of the sound.
@@ -115,7 +237,7 @@ impl Sink {
     /// change the play speed of the sound.
     #[inline]
     pub fn speed(&self) -> f32 {
-        *self.controls.speed.lock().unwrap()
+        self.controls.speed.load()
     }
 
     /// Changes the speed of the sound.
@@ -124,7 +246,91 @@ impl Sink {
     /// change the play speed of the sound.
     #[inline]
     pub fn set_speed(&self, value: f32) {
-        *self.controls.speed.lock().unwrap() = value;
+        self.controls.speed.store(value);
+    }
+
+    /// Gets the playback rate of the sound.
+    ///
+    /// The value `1.0` is the "normal" rate. Unlike the speed, the playback rate doesn't change
+    /// the pitch of the sound.
+    #[inline]
+    pub fn playback_rate(&self) -> f32 {
+        self.controls.playback_rate.load()
+    }
+
+    /// Changes the playback rate of the sound, without changing its pitch.
+    ///
+    /// The value `1.0` is the "normal" rate, `2.0` plays the sound twice as fast. This is meant
+    /// for rates between `0.5` and `2.0`, as used to play speech faster or slower. Use
+    /// `set_speed` to change the pitch along with the tempo. Rates that aren't positive and finite
+    /// are treated as `1.0`.
+    #[inline]
+    pub fn set_playback_rate(&self, value: f32) {
+        self.controls.playback_rate.store(value);
+    }
+
+    /// Attempts to seek to a given position in the current sound.
+    ///
+    /// The seek is performed by the audio thread, and this method blocks until it is done. If
+    /// the sink has no sound to play, nothing happens and `Ok(())` is returned.
+    ///
+    /// Returns `SeekError::TimedOut` if the seek isn't done within a second, for example
+    /// because the `OfflineOutputStream` of the sink is rendered by the thread calling this
+    /// method, and `SeekError::Superseded` if another call replaced this seek before it was
+    /// performed. See `Source::try_seek` for the other errors.
+    ///
+    /// Since the sound is seeked by the audio thread, the output stops while it seeks. This is
+    /// quick for most sounds, but the MP3 and FLAC decoders can't jump to a position: they
+    /// decode the sound up to it, from the beginning when seeking backwards. Seeking far into a
+    /// long file can then take longer than the period of the stream, and cause a glitch. If it
+    /// takes longer than a second, `SeekError::TimedOut` is returned even though the seek still
+    /// completes afterwards.
+    pub fn try_seek(&self, pos: Duration) -> Result<(), SeekError> {
+        let order = SeekOrder::new(pos);
+        let feedback = order.feedback.clone();
+        drop(self.controls.performed_seeks.take_all());
+        if let Some(mut previous) = self.controls.seek.replace(Node::new(order)) {
+            // Another seek was waiting to be attempted.
+            previous.value.send(Err(SeekError::Superseded));
+        }
+
+        let deadline = Instant::now() + SEEK_TIMEOUT;
+        loop {
+            if let Some(result) = feedback.result.take() {
+                if result.value.is_ok() {
+                    self.controls
+                        .position
+                        .store(pos.as_nanos() as u64, Ordering::Relaxed);
+                }
+                return result.value;
+            }
+
+            // The queue ran out of sounds, so nobody is going to pick up the order.
+            if self.empty() {
+                feedback.cancelled.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                feedback.cancelled.store(true, Ordering::Relaxed);
+                return Err(SeekError::TimedOut);
+            }
+            // Woken up by the result, but the queue may run out of sounds meanwhile.
+            thread::park_timeout((deadline - now).min(Duration::from_millis(5)));
+        }
+    }
+
+    /// Returns the position of the sound that is currently playing.
+    ///
+    /// This is the duration of the sound that has been played, not counting the time spent
+    /// paused. Changes of the speed or playback rate do not affect it: playing one second of a
+    /// sound at speed `2.0` advances the position by one second.
+    ///
+    /// The position is updated every few milliseconds by the audio thread, and is reset to zero
+    /// when the sink is stopped.
+    #[inline]
+    pub fn get_pos(&self) -> Duration {
+        Duration::from_nanos(self.controls.position.load(Ordering::Relaxed))
     }
 
     /// Resumes playback of a paused sink.
@@ -165,10 +371,13 @@ impl Sink {
     }
 
     /// Sleeps the current thread until the sound ends.
+    ///
+    /// Checks every few milliseconds whether the sink is empty, so that the audio thread doesn't
+    /// have to wake this thread up.
     #[inline]
     pub fn sleep_until_end(&self) {
-        if let Some(sleep_until_end) = self.sleep_until_end.lock().unwrap().take() {
-            let _ = sleep_until_end.recv();
+        while !self.empty() {
+            thread::sleep(Duration::from_millis(5));
         }
     }
 
@@ -181,8 +390,18 @@ impl Sink {
     /// Returns the number of sounds currently in the queue.
     #[inline]
     pub fn len(&self) -> usize {
+        self.collect_finished();
         self.sound_count.load(Ordering::Relaxed)
     }
+
+    // Drops the sounds and the seek orders sent back by the audio thread.
+    fn collect_finished(&self) {
+        self.queue_tx.collect_finished();
+        drop(self.controls.performed_seeks.take_all());
+        if let Some(mixer) = self.mixer.upgrade() {
+            mixer.collect_finished();
+        }
+    }
 }
 
 impl Drop for Sink {
@@ -193,12 +412,18 @@ impl Drop for Sink {
         if !self.detached {
             self.controls.stopped.store(true, Ordering::Relaxed);
         }
+        self.collect_finished();
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
     use crate::buffer::SamplesBuffer;
+    use crate::source::SeekError;
     use crate::{Sink, Source};
 
     #[test]
@@ -229,7 +454,7 @@ mod tests {
 
         assert_eq!(queue_rx.next(), Some(0.0));
 
-        assert_eq!(sink.empty(), true);
+        assert!(sink.empty());
     }
 
     #[test]
@@ -249,4 +474,124 @@ mod tests {
             assert_eq!(queue_rx.next(), src.next());
         }
     }
+
+    #[test]
+    fn test_get_pos() {
+        let (sink, mut queue_rx) = Sink::new_idle();
+
+        let v: Vec<f32> = (0..5000).map(|i| i as f32 / 5000.0).collect();
+        sink.append(SamplesBuffer::new(1, 1000, v));
+        assert_eq!(sink.get_pos(), Duration::from_secs(0));
+
+        for _ in 0..1001 {
+            queue_rx.next();
+        }
+        assert_eq!(sink.get_pos(), Duration::from_secs(1));
+
+        sink.stop();
+        for _ in 0..5 {
+            queue_rx.next();
+        }
+        assert_eq!(sink.get_pos(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_empty_drops_ended_sounds() {
+        let (sink, mut queue_rx) = Sink::new_idle();
+        let token = Arc::new(());
+        let held = token.clone();
+        sink.append(
+            SamplesBuffer::new(1, 1000, vec![0.0f32; 10]).periodic_access(
+                Duration::from_secs(1),
+                move |_| {
+                    let _ = &held;
+                },
+            ),
+        );
+        for _ in 0..20 {
+            queue_rx.next();
+        }
+        assert_eq!(Arc::strong_count(&token), 2);
+        assert!(sink.empty());
+        assert_eq!(Arc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn test_try_seek() {
+        let (sink, mut queue_rx) = Sink::new_idle();
+        let sink = Arc::new(sink);
+
+        let v: Vec<f32> = (0..5000).map(|i| i as f32 / 5000.0).collect();
+        sink.append(SamplesBuffer::new(1, 1000, v));
+        for _ in 0..3000 {
+            queue_rx.next();
+        }
+
+        let seeker = {
+            let sink = sink.clone();
+            thread::spawn(move || sink.try_seek(Duration::from_secs(1)))
+        };
+
+        // Wait for the order to be placed, then play the sound until it has been performed.
+        while sink.controls.seek.is_empty() {
+            thread::yield_now();
+        }
+        let sample = loop {
+            let sample = queue_rx.next().unwrap();
+            if sample < 0.5 {
+                break sample;
+            }
+        };
+        assert!((0.2..0.202).contains(&sample));
+        assert!(seeker.join().unwrap().is_ok());
+
+        let pos = sink.get_pos();
+        assert!((Duration::from_secs(1)..Duration::from_millis(1010)).contains(&pos));
+    }
+
+    #[test]
+    fn test_try_seek_timeout() {
+        let (sink, mut queue_rx) = Sink::new_idle();
+        let v: Vec<f32> = (0..5000).map(|i| i as f32 / 5000.0).collect();
+        sink.append(SamplesBuffer::new(1, 1000, v));
+        assert!(matches!(
+            sink.try_seek(Duration::from_secs(1)),
+            Err(SeekError::TimedOut)
+        ));
+
+        // The order is left in place, but not performed once the sound plays.
+        assert!(!sink.controls.seek.is_empty());
+        assert_eq!(queue_rx.next(), Some(0.0));
+        assert!(sink.controls.seek.is_empty());
+        assert!(queue_rx.take(10).all(|sample| sample < 0.01));
+    }
+
+    #[test]
+    fn test_try_seek_superseded() {
+        let (sink, mut queue_rx) = Sink::new_idle();
+        let sink = Arc::new(sink);
+        sink.append(SamplesBuffer::new(1, 1000, vec![0.0f32; 5000]));
+
+        let seek = |pos| {
+            let sink = sink.clone();
+            thread::spawn(move || sink.try_seek(Duration::from_secs(pos)))
+        };
+        let first = seek(1);
+        while sink.controls.seek.is_empty() {
+            thread::yield_now();
+        }
+        let second = seek(2);
+        assert!(matches!(first.join().unwrap(), Err(SeekError::Superseded)));
+
+        while !second.is_finished() {
+            queue_rx.next();
+        }
+        assert!(second.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_try_seek_empty() {
+        let (sink, _queue_rx) = Sink::new_idle();
+        assert!(sink.try_seek(Duration::from_secs(1)).is_ok());
+    }
 }
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use crate::source::compressor::time_coefficient;
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Duration over which the RMS level of the sound is measured.
+const RMS_WINDOW: Duration = Duration::from_millis(50);
+
+/// Internal function that builds a `AutomaticGainControl` object.
+pub fn automatic_gain_control<I>(
+    input: I,
+    target_level: f32,
+    attack: Duration,
+    release: Duration,
+    max_gain: f32,
+) -> AutomaticGainControl<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    AutomaticGainControl {
+        input,
+        target_level,
+        attack,
+        release,
+        max_gain,
+        attack_coeff: 0.0,
+        release_coeff: 0.0,
+        rms_coeff: 0.0,
+        channels: 0,
+        sample_rate: 0,
+        mean_square: 0.0,
+        gain: 1.0,
+    }
+}
+
+/// Filter that amplifies or attenuates the sound so that its RMS level stays close to a target.
+///
+/// The gain is the same for all the channels, so that the stereo image doesn't move.
+#[derive(Clone, Debug)]
+pub struct AutomaticGainControl<I> {
+    input: I,
+    target_level: f32,
+    attack: Duration,
+    release: Duration,
+    max_gain: f32,
+    // Computed from `attack` and `release` for the format below.
+    attack_coeff: f32,
+    release_coeff: f32,
+    rms_coeff: f32,
+    channels: u16,
+    sample_rate: u32,
+    // Average of the squares of the recent samples.
+    mean_square: f32,
+    gain: f32,
+}
+
+impl<I> AutomaticGainControl<I> {
+    /// Sets the RMS level the sound is brought to, between 0.0 and 1.0.
+    #[inline]
+    pub fn set_target_level(&mut self, target_level: f32) {
+        self.target_level = target_level;
+    }
+
+    /// Sets the maximum factor the sound can be amplified by, so that silence and background
+    /// noise aren't amplified too much.
+    #[inline]
+    pub fn set_max_gain(&mut self, max_gain: f32) {
+        self.max_gain = max_gain;
+    }
+
+    /// Sets how fast the gain is reduced when the sound gets louder.
+    #[inline]
+    pub fn set_attack(&mut self, attack: Duration) {
+        self.attack = attack;
+        self.channels = 0;
+    }
+
+    /// Sets how fast the gain is raised when the sound gets quieter.
+    #[inline]
+    pub fn set_release(&mut self, release: Duration) {
+        self.release = release;
+        self.channels = 0;
+    }
+
+    /// Returns the gain currently applied to the sound.
+    #[inline]
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> Iterator for AutomaticGainControl<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        let channels = self.input.channels();
+        let sample_rate = self.input.sample_rate();
+        if channels != self.channels || sample_rate != self.sample_rate {
+            // The samples of all the channels go through the same detector.
+            let rate = sample_rate as f32 * channels.max(1) as f32;
+            self.attack_coeff = time_coefficient(self.attack, rate);
+            self.release_coeff = time_coefficient(self.release, rate);
+            self.rms_coeff = time_coefficient(RMS_WINDOW, rate);
+            self.channels = channels;
+            self.sample_rate = sample_rate;
+        }
+
+        let sample = self.input.next()?.to_f32();
+
+        let square = sample * sample;
+        self.mean_square = square + self.rms_coeff * (self.mean_square - square);
+
+        let level = self.mean_square.sqrt();
+        let target_gain = if level * self.max_gain > self.target_level {
+            self.target_level / level
+        } else {
+            self.max_gain
+        };
+        let coeff = if target_gain < self.gain {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.gain = target_gain + coeff * (self.gain - target_gain);
+
+        Some(CpalSample::from(&(sample * self.gain)))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for AutomaticGainControl<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for AutomaticGainControl<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::source::{SineWave, Source};
+
+    fn peak<S>(source: S) -> f32
+    where
+        S: Source<Item = f32>,
+    {
+        source
+            .skip(96000)
+            .take(4800)
+            .fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    fn agc<S>(source: S) -> impl Source<Item = f32>
+    where
+        S: Source<Item = f32>,
+    {
+        source.automatic_gain_control(
+            0.25,
+            Duration::from_millis(10),
+            Duration::from_millis(500),
+            4.0,
+        )
+    }
+
+    #[test]
+    fn reaches_target_level() {
+        // The RMS level of a sine is its amplitude divided by the square root of 2.
+        let expected = 0.25 * std::f32::consts::SQRT_2;
+
+        let quiet = peak(agc(SineWave::new(440.0).amplify(0.2)));
+        assert!((quiet - expected).abs() < 0.05, "{}", quiet);
+
+        let loud = peak(agc(SineWave::new(440.0)));
+        assert!((loud - expected).abs() < 0.05, "{}", loud);
+    }
+
+    #[test]
+    fn gain_is_limited() {
+        let peak = peak(agc(SineWave::new(440.0).amplify(0.01)));
+        assert!((peak - 0.04).abs() < 0.005, "{}", peak);
+    }
+}
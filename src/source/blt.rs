@@ -1,63 +1,166 @@
 use std::f32::consts::PI;
 use std::time::Duration;
 
-use crate::Source;
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
 
 // Implemented following http://www.musicdsp.org/files/Audio-EQ-Cookbook.txt
 
 /// Internal function that builds a `BltFilter` object.
 pub fn low_pass<I>(input: I, freq: u32) -> BltFilter<I>
 where
-    I: Source<Item = f32>,
+    I: Source,
+    I::Item: Sample,
 {
-    BltFilter {
-        input,
-        formula: BltFormula::LowPass { freq, q: 0.5 },
-        applier: None,
-        x_n1: 0.0,
-        x_n2: 0.0,
-        y_n1: 0.0,
-        y_n2: 0.0,
-    }
+    low_pass_with_q(input, freq, 0.5)
 }
 
 pub fn high_pass<I>(input: I, freq: u32) -> BltFilter<I>
 where
-    I: Source<Item = f32>,
+    I: Source,
+    I::Item: Sample,
 {
-    BltFilter {
-        input,
-        formula: BltFormula::HighPass { freq, q: 0.5 },
-        applier: None,
-        x_n1: 0.0,
-        x_n2: 0.0,
-        y_n1: 0.0,
-        y_n2: 0.0,
-    }
+    high_pass_with_q(input, freq, 0.5)
+}
+
+pub fn low_pass_with_q<I>(input: I, freq: u32, q: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::LowPass { freq, q })
+}
+
+pub fn high_pass_with_q<I>(input: I, freq: u32, q: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::HighPass { freq, q })
 }
 
+pub fn band_pass<I>(input: I, freq: u32, q: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::BandPass { freq, q })
+}
+
+pub fn notch<I>(input: I, freq: u32, q: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::Notch { freq, q })
+}
+
+pub fn all_pass<I>(input: I, freq: u32, q: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::AllPass { freq, q })
+}
+
+pub fn peaking_eq<I>(input: I, freq: u32, q: f32, gain_db: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::Peaking { freq, q, gain_db })
+}
+
+pub fn low_shelf<I>(input: I, freq: u32, q: f32, gain_db: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::LowShelf { freq, q, gain_db })
+}
+
+pub fn high_shelf<I>(input: I, freq: u32, q: f32, gain_db: f32) -> BltFilter<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    BltFilter::new(input, BltFormula::HighShelf { freq, q, gain_db })
+}
+
+/// Biquad filter, built with the bilinear transform.
+///
+/// `freq` is the cutoff or center frequency in Hz, `q` the quality factor and `gain_db` the gain
+/// of the peaking and shelf filters, in dB.
 #[derive(Clone, Debug)]
 pub struct BltFilter<I> {
     input: I,
-    formula: BltFormula,
-    applier: Option<BltApplier>,
-    x_n1: f32,
-    x_n2: f32,
-    y_n1: f32,
-    y_n2: f32,
+    biquad: Biquad,
+    // Channel of the next sample.
+    channel: usize,
 }
 
 impl<I> BltFilter<I> {
+    #[inline]
+    fn new(input: I, formula: BltFormula) -> BltFilter<I> {
+        BltFilter {
+            input,
+            biquad: Biquad::new(formula),
+            channel: 0,
+        }
+    }
+
     /// Modifies this filter so that it becomes a low-pass filter.
     pub fn to_low_pass(&mut self, freq: u32) {
-        self.formula = BltFormula::LowPass { freq, q: 0.5 };
-        self.applier = None;
+        self.to_low_pass_with_q(freq, 0.5);
     }
 
     /// Modifies this filter so that it becomes a high-pass filter
     pub fn to_high_pass(&mut self, freq: u32) {
-        self.formula = BltFormula::HighPass { freq, q: 0.5 };
-        self.applier = None;
+        self.to_high_pass_with_q(freq, 0.5);
+    }
+
+    /// Modifies this filter so that it becomes a low-pass filter with the given Q.
+    pub fn to_low_pass_with_q(&mut self, freq: u32, q: f32) {
+        self.biquad.set_formula(BltFormula::LowPass { freq, q });
+    }
+
+    /// Modifies this filter so that it becomes a high-pass filter with the given Q.
+    pub fn to_high_pass_with_q(&mut self, freq: u32, q: f32) {
+        self.biquad.set_formula(BltFormula::HighPass { freq, q });
+    }
+
+    /// Modifies this filter so that it becomes a band-pass filter.
+    pub fn to_band_pass(&mut self, freq: u32, q: f32) {
+        self.biquad.set_formula(BltFormula::BandPass { freq, q });
+    }
+
+    /// Modifies this filter so that it becomes a notch filter.
+    pub fn to_notch(&mut self, freq: u32, q: f32) {
+        self.biquad.set_formula(BltFormula::Notch { freq, q });
+    }
+
+    /// Modifies this filter so that it becomes an all-pass filter.
+    pub fn to_all_pass(&mut self, freq: u32, q: f32) {
+        self.biquad.set_formula(BltFormula::AllPass { freq, q });
+    }
+
+    /// Modifies this filter so that it becomes a peaking equalizer.
+    pub fn to_peaking_eq(&mut self, freq: u32, q: f32, gain_db: f32) {
+        self.biquad
+            .set_formula(BltFormula::Peaking { freq, q, gain_db });
+    }
+
+    /// Modifies this filter so that it becomes a low shelf filter.
+    pub fn to_low_shelf(&mut self, freq: u32, q: f32, gain_db: f32) {
+        self.biquad
+            .set_formula(BltFormula::LowShelf { freq, q, gain_db });
+    }
+
+    /// Modifies this filter so that it becomes a high shelf filter.
+    pub fn to_high_shelf(&mut self, freq: u32, q: f32, gain_db: f32) {
+        self.biquad
+            .set_formula(BltFormula::HighShelf { freq, q, gain_db });
     }
 
     /// Returns a reference to the inner source.
@@ -81,39 +184,29 @@ impl<I> BltFilter<I> {
 
 impl<I> Iterator for BltFilter<I>
 where
-    I: Source<Item = f32>,
+    I: Source,
+    I::Item: Sample,
 {
-    type Item = f32;
+    type Item = I::Item;
 
     #[inline]
-    fn next(&mut self) -> Option<f32> {
-        let last_in_frame = self.input.current_frame_len() == Some(1);
-
-        if self.applier.is_none() {
-            self.applier = Some(self.formula.to_applier(self.input.sample_rate()));
+    fn next(&mut self) -> Option<I::Item> {
+        // The format can only change between two frames.
+        if self.channel == 0 {
+            self.biquad
+                .set_format(self.input.channels(), self.input.sample_rate());
         }
+        let last_in_frame = self.input.current_frame_len() == Some(1);
 
-        let sample = match self.input.next() {
-            None => return None,
-            Some(s) => s,
-        };
-
-        let result = self
-            .applier
-            .as_ref()
-            .unwrap()
-            .apply(sample, self.x_n1, self.x_n2, self.y_n1, self.y_n2);
-
-        self.y_n2 = self.y_n1;
-        self.x_n2 = self.x_n1;
-        self.y_n1 = result;
-        self.x_n1 = sample;
+        let sample = self.input.next()?;
+        let result = self.biquad.process(sample.to_f32(), self.channel);
 
-        if last_in_frame {
-            self.applier = None;
+        self.channel += 1;
+        if last_in_frame || self.channel >= self.biquad.channels() {
+            self.channel = 0;
         }
 
-        Some(result)
+        Some(CpalSample::from(&result))
     }
 
     #[inline]
@@ -122,11 +215,17 @@ where
     }
 }
 
-impl<I> ExactSizeIterator for BltFilter<I> where I: Source<Item = f32> + ExactSizeIterator {}
+impl<I> ExactSizeIterator for BltFilter<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
 
 impl<I> Source for BltFilter<I>
 where
-    I: Source<Item = f32>,
+    I: Source,
+    I::Item: Sample,
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
@@ -138,6 +237,11 @@ where
         self.input.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.input.sample_rate()
@@ -147,56 +251,210 @@ where
     fn total_duration(&self) -> Option<Duration> {
         self.input.total_duration()
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        Ok(())
+    }
 }
 
+/// A biquad filter and its state. Shared by `BltFilter` and `Equalizer`.
+///
+/// The samples of the channels are interleaved, so the filter keeps a separate history for each
+/// channel.
 #[derive(Clone, Debug)]
-enum BltFormula {
+pub(crate) struct Biquad {
+    formula: BltFormula,
+    // Computed from the formula at the first sample after the formula or the sample rate changed.
+    applier: Option<BltApplier>,
+    sample_rate: u32,
+    states: Vec<BiquadState>,
+}
+
+/// The previous inputs and outputs of a biquad filter, for one channel.
+#[derive(Clone, Debug, Default)]
+struct BiquadState {
+    x_n1: f32,
+    x_n2: f32,
+    y_n1: f32,
+    y_n2: f32,
+}
+
+impl Biquad {
+    #[inline]
+    pub(crate) fn new(formula: BltFormula) -> Biquad {
+        Biquad {
+            formula,
+            applier: None,
+            sample_rate: 0,
+            states: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn formula(&self) -> &BltFormula {
+        &self.formula
+    }
+
+    /// Returns the number of channels the filter was set up for by `set_format`.
+    #[inline]
+    pub(crate) fn channels(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Changes the formula, keeping the history of the filter.
+    #[inline]
+    pub(crate) fn set_formula(&mut self, formula: BltFormula) {
+        self.formula = formula;
+        self.applier = None;
+    }
+
+    /// Sets up the filter for the format of the input. Must be called before the first sample
+    /// and may be called at frame boundaries. The history of the filter is reset if the format
+    /// changed.
+    #[inline]
+    pub(crate) fn set_format(&mut self, channels: u16, sample_rate: u32) {
+        let channels = channels.max(1) as usize;
+        if channels != self.states.len() || sample_rate != self.sample_rate {
+            self.states = vec![BiquadState::default(); channels];
+            self.sample_rate = sample_rate;
+            self.applier = None;
+        }
+    }
+
+    /// Filters the next sample of the given channel.
+    #[inline]
+    pub(crate) fn process(&mut self, sample: f32, channel: usize) -> f32 {
+        let Biquad {
+            formula,
+            applier,
+            sample_rate,
+            states,
+        } = self;
+        let applier = applier.get_or_insert_with(|| formula.to_applier(*sample_rate));
+        let state = &mut states[channel];
+
+        let result = applier.apply(sample, state.x_n1, state.x_n2, state.y_n1, state.y_n2);
+
+        state.y_n2 = state.y_n1;
+        state.x_n2 = state.x_n1;
+        state.y_n1 = result;
+        state.x_n1 = sample;
+
+        result
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum BltFormula {
     LowPass { freq: u32, q: f32 },
     HighPass { freq: u32, q: f32 },
+    BandPass { freq: u32, q: f32 },
+    Notch { freq: u32, q: f32 },
+    AllPass { freq: u32, q: f32 },
+    Peaking { freq: u32, q: f32, gain_db: f32 },
+    LowShelf { freq: u32, q: f32, gain_db: f32 },
+    HighShelf { freq: u32, q: f32, gain_db: f32 },
 }
 
 impl BltFormula {
-    fn to_applier(&self, sampling_frequency: u32) -> BltApplier {
+    /// Returns the cutoff or center frequency of the filter.
+    #[inline]
+    pub(crate) fn freq(&self) -> u32 {
         match *self {
-            BltFormula::LowPass { freq, q } => {
-                let w0 = 2.0 * PI * freq as f32 / sampling_frequency as f32;
+            BltFormula::LowPass { freq, .. }
+            | BltFormula::HighPass { freq, .. }
+            | BltFormula::BandPass { freq, .. }
+            | BltFormula::Notch { freq, .. }
+            | BltFormula::AllPass { freq, .. }
+            | BltFormula::Peaking { freq, .. }
+            | BltFormula::LowShelf { freq, .. }
+            | BltFormula::HighShelf { freq, .. } => freq,
+        }
+    }
+
+    fn to_applier(&self, sampling_frequency: u32) -> BltApplier {
+        let w0 = 2.0 * PI * self.freq() as f32 / sampling_frequency as f32;
+        let cos_w0 = w0.cos();
 
+        // Coefficients in the order b0, b1, b2, a0, a1, a2.
+        let (b0, b1, b2, a0, a1, a2) = match *self {
+            BltFormula::LowPass { q, .. } => {
                 let alpha = w0.sin() / (2.0 * q);
-                let b1 = 1.0 - w0.cos();
+                let b1 = 1.0 - cos_w0;
                 let b0 = b1 / 2.0;
-                let b2 = b0;
-                let a0 = 1.0 + alpha;
-                let a1 = -2.0 * w0.cos();
-                let a2 = 1.0 - alpha;
-
-                BltApplier {
-                    b0: b0 / a0,
-                    b1: b1 / a0,
-                    b2: b2 / a0,
-                    a1: a1 / a0,
-                    a2: a2 / a0,
-                }
+                (b0, b1, b0, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
             }
-            BltFormula::HighPass { freq, q } => {
-                let w0 = 2.0 * PI * freq as f32 / sampling_frequency as f32;
-                let cos_w0 = w0.cos();
+            BltFormula::HighPass { q, .. } => {
                 let alpha = w0.sin() / (2.0 * q);
-
                 let b0 = (1.0 + cos_w0) / 2.0;
                 let b1 = -1.0 - cos_w0;
-                let b2 = b0;
-                let a0 = 1.0 + alpha;
-                let a1 = -2.0 * cos_w0;
-                let a2 = 1.0 - alpha;
-
-                BltApplier {
-                    b0: b0 / a0,
-                    b1: b1 / a0,
-                    b2: b2 / a0,
-                    a1: a1 / a0,
-                    a2: a2 / a0,
-                }
+                (b0, b1, b0, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+            }
+            BltFormula::BandPass { q, .. } => {
+                // Constant 0 dB peak gain.
+                let alpha = w0.sin() / (2.0 * q);
+                (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+            }
+            BltFormula::Notch { q, .. } => {
+                let alpha = w0.sin() / (2.0 * q);
+                let b1 = -2.0 * cos_w0;
+                (1.0, b1, 1.0, 1.0 + alpha, b1, 1.0 - alpha)
+            }
+            BltFormula::AllPass { q, .. } => {
+                let alpha = w0.sin() / (2.0 * q);
+                let b1 = -2.0 * cos_w0;
+                (1.0 - alpha, b1, 1.0 + alpha, 1.0 + alpha, b1, 1.0 - alpha)
+            }
+            BltFormula::Peaking { q, gain_db, .. } => {
+                let a = 10f32.powf(gain_db / 40.0);
+                let alpha = w0.sin() / (2.0 * q);
+                let b1 = -2.0 * cos_w0;
+                (
+                    1.0 + alpha * a,
+                    b1,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    b1,
+                    1.0 - alpha / a,
+                )
+            }
+            BltFormula::LowShelf { q, gain_db, .. } => {
+                let a = 10f32.powf(gain_db / 40.0);
+                let alpha = w0.sin() / (2.0 * q);
+                let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                    (a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha,
+                )
+            }
+            BltFormula::HighShelf { q, gain_db, .. } => {
+                let a = 10f32.powf(gain_db / 40.0);
+                let alpha = w0.sin() / (2.0 * q);
+                let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                    (a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha,
+                )
             }
+        };
+
+        BltApplier {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
         }
     }
 }
@@ -216,3 +474,71 @@ impl BltApplier {
         self.b0 * x_n + self.b1 * x_n1 + self.b2 * x_n2 - self.a1 * y_n1 - self.a2 * y_n2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::SamplesBuffer;
+    use crate::source::{SineWave, Source};
+
+    /// Returns the peak amplitude of a 1kHz sine wave once it went through `filter`.
+    fn gain<F, S>(filter: F) -> f32
+    where
+        F: FnOnce(SineWave) -> S,
+        S: Source<Item = f32>,
+    {
+        // Skip the first 100ms, while the filter settles.
+        filter(SineWave::new(1000.0))
+            .skip(4800)
+            .take(4800)
+            .fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn responses_at_1khz() {
+        let close = |value: f32, expected: f32| (value - expected).abs() < 0.02;
+
+        assert!(close(gain(|s| s.band_pass(1000, 1.0)), 1.0));
+        assert!(gain(|s| s.band_pass(4000, 4.0)) < 0.1);
+        assert!(gain(|s| s.notch(1000, 1.0)) < 0.01);
+        assert!(close(gain(|s| s.all_pass(500, 1.0)), 1.0));
+        assert!(close(gain(|s| s.peaking_eq(1000, 1.0, 6.0)), 1.995));
+        assert!(close(gain(|s| s.peaking_eq(1000, 1.0, -6.0)), 0.501));
+        assert!(close(gain(|s| s.low_shelf(8000, 0.707, 6.0)), 1.995));
+        assert!(close(gain(|s| s.low_shelf(50, 0.707, 6.0)), 1.0));
+        assert!(close(gain(|s| s.high_shelf(50, 0.707, -6.0)), 0.501));
+        assert!(close(gain(|s| s.high_shelf(8000, 0.707, -6.0)), 1.0));
+        assert!(gain(|s| s.low_pass_with_q(100, 0.707)) < 0.02);
+        assert!(gain(|s| s.high_pass_with_q(10000, 0.707)) < 0.02);
+    }
+
+    #[test]
+    fn channels_are_filtered_separately() {
+        // A 1kHz sine on the left channel, and silence on the right one.
+        let left = SineWave::new(1000.0).take(4800);
+        let samples: Vec<f32> = left.flat_map(|s| vec![s, 0.0]).collect();
+        let source = SamplesBuffer::new(2, 48000, samples);
+
+        let filtered: Vec<f32> = source.low_pass(2000).collect();
+        assert!(filtered.iter().step_by(2).any(|&s| s.abs() > 0.5));
+        assert!(filtered.iter().skip(1).step_by(2).all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn state_is_reset_when_format_changes() {
+        let (tx, rx) = crate::queue::queue(false);
+        tx.append(SamplesBuffer::new(1, 48000, vec![1.0f32; 480]));
+        tx.append(SamplesBuffer::new(2, 44100, vec![0.0f32; 4]));
+
+        let filtered: Vec<f32> = rx.low_pass(1000).collect();
+        assert_eq!(filtered.len(), 484);
+        assert!(filtered[479] > 0.5);
+        assert_eq!(&filtered[480..], &[0.0; 4]);
+    }
+
+    #[test]
+    fn integer_samples() {
+        let source = SamplesBuffer::new(1, 48000, vec![i16::MAX / 2; 4800]);
+        let filtered: Vec<i16> = source.low_pass(100).collect();
+        assert!((filtered[4799] - i16::MAX / 2).abs() < 100);
+    }
+}
@@ -3,6 +3,7 @@ use std::mem;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::source::ChannelLayout;
 use crate::{Sample, Source};
 
 /// Internal function that builds a `Buffered` object.
@@ -62,6 +63,7 @@ where
 {
     data: Vec<I::Item>,
     channels: u16,
+    layout: Option<ChannelLayout>,
     rate: u32,
     next: Mutex<Arc<Frame<I>>>,
 }
@@ -109,6 +111,7 @@ where
     }
 
     let channels = input.channels();
+    let layout = input.channel_layout();
     let rate = input.sample_rate();
     let data: Vec<I::Item> = input
         .by_ref()
@@ -122,6 +125,7 @@ where
     Arc::new(Frame::Data(FrameData {
         data,
         channels,
+        layout,
         rate,
         next: Mutex::new(Arc::new(Frame::Input(Mutex::new(Some(input))))),
     }))
@@ -226,6 +230,15 @@ where
         }
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        match *self.current_frame {
+            Frame::Data(FrameData { layout, .. }) => layout,
+            Frame::End => None,
+            Frame::Input(_) => unreachable!(),
+        }
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         match *self.current_frame {
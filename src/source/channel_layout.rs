@@ -0,0 +1,237 @@
+/// Position of the speaker a channel is meant to be played on.
+///
+/// The positions are in the order of the channels of WAV files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    /// Front left.
+    FrontLeft,
+    /// Front right.
+    FrontRight,
+    /// Front center.
+    FrontCenter,
+    /// Low-frequency effects, or subwoofer.
+    LowFrequency,
+    /// Back left, the left surround of 5.1.
+    BackLeft,
+    /// Back right, the right surround of 5.1.
+    BackRight,
+    /// Between the front left and the front center.
+    FrontLeftOfCenter,
+    /// Between the front center and the front right.
+    FrontRightOfCenter,
+    /// Back center.
+    BackCenter,
+    /// Side left, the left surround of 7.1.
+    SideLeft,
+    /// Side right, the right surround of 7.1.
+    SideRight,
+    /// Above the listener.
+    TopCenter,
+    /// Above the front left.
+    TopFrontLeft,
+    /// Above the front center.
+    TopFrontCenter,
+    /// Above the front right.
+    TopFrontRight,
+    /// Above the back left.
+    TopBackLeft,
+    /// Above the back center.
+    TopBackCenter,
+    /// Above the back right.
+    TopBackRight,
+}
+
+const SPEAKERS: [Speaker; 18] = [
+    Speaker::FrontLeft,
+    Speaker::FrontRight,
+    Speaker::FrontCenter,
+    Speaker::LowFrequency,
+    Speaker::BackLeft,
+    Speaker::BackRight,
+    Speaker::FrontLeftOfCenter,
+    Speaker::FrontRightOfCenter,
+    Speaker::BackCenter,
+    Speaker::SideLeft,
+    Speaker::SideRight,
+    Speaker::TopCenter,
+    Speaker::TopFrontLeft,
+    Speaker::TopFrontCenter,
+    Speaker::TopFrontRight,
+    Speaker::TopBackLeft,
+    Speaker::TopBackCenter,
+    Speaker::TopBackRight,
+];
+
+impl Speaker {
+    /// Returns the bit of the speaker in the channel mask of WAV files.
+    #[inline]
+    pub const fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// The speaker positions of the channels of a sound.
+///
+/// Like in WAV files, the channels are in the order of the `Speaker` variants, so a layout is a
+/// set of speakers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelLayout {
+    mask: u32,
+}
+
+impl ChannelLayout {
+    /// A single channel, played on the front center speaker.
+    pub const MONO: ChannelLayout = ChannelLayout {
+        mask: Speaker::FrontCenter.mask(),
+    };
+
+    /// Front left and front right.
+    pub const STEREO: ChannelLayout = ChannelLayout {
+        mask: Speaker::FrontLeft.mask() | Speaker::FrontRight.mask(),
+    };
+
+    /// Front left, front right, back left and back right.
+    pub const QUAD: ChannelLayout = ChannelLayout {
+        mask: ChannelLayout::STEREO.mask | Speaker::BackLeft.mask() | Speaker::BackRight.mask(),
+    };
+
+    /// Front left, front right, front center, low-frequency effects, back left and back right.
+    pub const SURROUND_5_1: ChannelLayout = ChannelLayout {
+        mask: ChannelLayout::QUAD.mask | Speaker::FrontCenter.mask() | Speaker::LowFrequency.mask(),
+    };
+
+    /// 5.1, with side left and side right.
+    pub const SURROUND_7_1: ChannelLayout = ChannelLayout {
+        mask: ChannelLayout::SURROUND_5_1.mask
+            | Speaker::SideLeft.mask()
+            | Speaker::SideRight.mask(),
+    };
+
+    /// Builds a layout from a WAV channel mask. Returns `None` if the mask is empty or has bits
+    /// that don't match a `Speaker`.
+    #[inline]
+    pub fn from_mask(mask: u32) -> Option<ChannelLayout> {
+        if mask == 0 || mask >> SPEAKERS.len() != 0 {
+            None
+        } else {
+            Some(ChannelLayout { mask })
+        }
+    }
+
+    /// Builds a layout from its speakers. Their order doesn't matter.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `speakers` is empty.
+    ///
+    pub fn from_speakers(speakers: &[Speaker]) -> ChannelLayout {
+        assert!(!speakers.is_empty());
+        ChannelLayout {
+            mask: speakers.iter().fold(0, |mask, s| mask | s.mask()),
+        }
+    }
+
+    /// Returns the usual layout for a number of channels, the one of WAV and FLAC files, or
+    /// `None` if there isn't one.
+    pub fn default_for(channels: u16) -> Option<ChannelLayout> {
+        let layout = match channels {
+            1 => ChannelLayout::MONO,
+            2 => ChannelLayout::STEREO,
+            3 => ChannelLayout::from_speakers(&[
+                Speaker::FrontLeft,
+                Speaker::FrontRight,
+                Speaker::FrontCenter,
+            ]),
+            4 => ChannelLayout::QUAD,
+            5 => ChannelLayout {
+                mask: ChannelLayout::QUAD.mask | Speaker::FrontCenter.mask(),
+            },
+            6 => ChannelLayout::SURROUND_5_1,
+            7 => ChannelLayout::from_speakers(&[
+                Speaker::FrontLeft,
+                Speaker::FrontRight,
+                Speaker::FrontCenter,
+                Speaker::LowFrequency,
+                Speaker::BackCenter,
+                Speaker::SideLeft,
+                Speaker::SideRight,
+            ]),
+            8 => ChannelLayout::SURROUND_7_1,
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    /// Returns the WAV channel mask of the layout.
+    #[inline]
+    pub fn mask(self) -> u32 {
+        self.mask
+    }
+
+    /// Returns the number of channels.
+    #[inline]
+    pub fn channels(self) -> u16 {
+        self.mask.count_ones() as u16
+    }
+
+    /// Returns whether one of the channels is played on `speaker`.
+    #[inline]
+    pub fn contains(self, speaker: Speaker) -> bool {
+        self.mask & speaker.mask() != 0
+    }
+
+    /// Returns the index of the channel played on `speaker`.
+    #[inline]
+    pub fn channel_of(self, speaker: Speaker) -> Option<u16> {
+        if self.contains(speaker) {
+            Some((self.mask & (speaker.mask() - 1)).count_ones() as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the speaker of the channel `channel`.
+    #[inline]
+    pub fn speaker(self, channel: u16) -> Option<Speaker> {
+        self.speakers().nth(channel as usize)
+    }
+
+    /// Returns the speakers of the channels, in order.
+    #[inline]
+    pub fn speakers(self) -> impl Iterator<Item = Speaker> {
+        SPEAKERS.iter().copied().filter(move |&s| self.contains(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelLayout, Speaker};
+
+    #[test]
+    fn speaker_order() {
+        let layout = ChannelLayout::SURROUND_5_1;
+        assert_eq!(layout.channels(), 6);
+        assert_eq!(layout.mask(), 0x3f);
+        assert_eq!(layout.speaker(2), Some(Speaker::FrontCenter));
+        assert_eq!(layout.speaker(6), None);
+        assert_eq!(layout.channel_of(Speaker::BackRight), Some(5));
+        assert_eq!(layout.channel_of(Speaker::SideLeft), None);
+    }
+
+    #[test]
+    fn from_speakers_ignores_order() {
+        let layout = ChannelLayout::from_speakers(&[Speaker::FrontRight, Speaker::FrontLeft]);
+        assert_eq!(layout, ChannelLayout::STEREO);
+        assert_eq!(ChannelLayout::from_mask(0), None);
+        assert_eq!(ChannelLayout::from_mask(1 << 20), None);
+    }
+
+    #[test]
+    fn default_layouts() {
+        for channels in 1..=8 {
+            let layout = ChannelLayout::default_for(channels).unwrap();
+            assert_eq!(layout.channels(), channels);
+        }
+        assert_eq!(ChannelLayout::default_for(9), None);
+    }
+}
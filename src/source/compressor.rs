@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Internal function that builds a `Compressor` object.
+pub fn compress<I>(
+    input: I,
+    threshold_db: f32,
+    ratio: f32,
+    attack: Duration,
+    release: Duration,
+) -> Compressor<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    Compressor {
+        input,
+        state: CompressorState::new(threshold_db, ratio, attack, release),
+    }
+}
+
+/// Filter that reduces the volume of the sound when it is louder than a threshold.
+///
+/// The gain is the same for all the channels, so that the stereo image doesn't move.
+#[derive(Clone, Debug)]
+pub struct Compressor<I> {
+    input: I,
+    state: CompressorState,
+}
+
+impl<I> Compressor<I> {
+    /// Sets the level above which the volume is reduced, in dBFS.
+    #[inline]
+    pub fn set_threshold(&mut self, threshold_db: f32) {
+        self.state.threshold_db = threshold_db;
+    }
+
+    /// Sets the ratio of the compression. With a ratio of 4, a sound 8 dB above the threshold
+    /// comes out 2 dB above it. An infinite ratio makes a limiter.
+    #[inline]
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.state.ratio = ratio;
+    }
+
+    /// Sets how fast the volume is reduced once the sound goes above the threshold.
+    #[inline]
+    pub fn set_attack(&mut self, attack: Duration) {
+        self.state.attack = attack;
+        self.state.reset_coefficients();
+    }
+
+    /// Sets how fast the volume goes back to normal once the sound goes below the threshold.
+    #[inline]
+    pub fn set_release(&mut self, release: Duration) {
+        self.state.release = release;
+        self.state.reset_coefficients();
+    }
+
+    /// Returns the current reduction of the volume, in dB.
+    #[inline]
+    pub fn gain_reduction(&self) -> f32 {
+        self.state.reduction_db
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> Iterator for Compressor<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        let channels = self.input.channels();
+        let sample_rate = self.input.sample_rate();
+        let sample = self.input.next()?;
+        let result = self.state.process(sample.to_f32(), channels, sample_rate);
+        Some(CpalSample::from(&result))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for Compressor<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for Compressor<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+/// Settings and state of a compressor. Shared by `Compressor` and the limiter of the mixer.
+#[derive(Clone, Debug)]
+pub(crate) struct CompressorState {
+    threshold_db: f32,
+    ratio: f32,
+    attack: Duration,
+    release: Duration,
+    // Computed from `attack` and `release` for the format below.
+    attack_coeff: f32,
+    release_coeff: f32,
+    channels: u16,
+    sample_rate: u32,
+    // Current reduction of the volume, in dB.
+    reduction_db: f32,
+}
+
+impl CompressorState {
+    #[inline]
+    pub(crate) fn new(
+        threshold_db: f32,
+        ratio: f32,
+        attack: Duration,
+        release: Duration,
+    ) -> CompressorState {
+        CompressorState {
+            threshold_db,
+            ratio,
+            attack,
+            release,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            channels: 0,
+            sample_rate: 0,
+            reduction_db: 0.0,
+        }
+    }
+
+    /// Forces the coefficients to be computed again at the next sample.
+    #[inline]
+    fn reset_coefficients(&mut self) {
+        self.channels = 0;
+    }
+
+    /// Compresses the next sample of a source with the given format.
+    #[inline]
+    pub(crate) fn process(&mut self, sample: f32, channels: u16, sample_rate: u32) -> f32 {
+        if channels != self.channels || sample_rate != self.sample_rate {
+            // The samples of all the channels go through the same detector.
+            let rate = sample_rate as f32 * channels.max(1) as f32;
+            self.attack_coeff = time_coefficient(self.attack, rate);
+            self.release_coeff = time_coefficient(self.release, rate);
+            self.channels = channels;
+            self.sample_rate = sample_rate;
+        }
+
+        let level_db = 20.0 * sample.abs().max(1e-9).log10();
+        let over_db = level_db - self.threshold_db;
+        let target_db = if over_db > 0.0 {
+            over_db * (1.0 - 1.0 / self.ratio)
+        } else {
+            0.0
+        };
+
+        let coeff = if target_db > self.reduction_db {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.reduction_db = target_db + coeff * (self.reduction_db - target_db);
+
+        sample * db_to_amplitude(-self.reduction_db)
+    }
+}
+
+/// Returns the coefficient of a one-pole smoother that covers about 63% of the way to its
+/// target in `time`, when called `rate` times per second.
+#[inline]
+pub(crate) fn time_coefficient(time: Duration, rate: f32) -> f32 {
+    let samples = time.as_secs_f32() * rate;
+    if samples > 0.0 {
+        (-1.0 / samples).exp()
+    } else {
+        0.0
+    }
+}
+
+#[inline]
+fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::buffer::SamplesBuffer;
+    use crate::source::{SineWave, Source};
+
+    fn peak<S>(source: S) -> f32
+    where
+        S: Source<Item = f32>,
+    {
+        source
+            .skip(24000)
+            .take(4800)
+            .fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn quiet_sound_is_unchanged() {
+        let source = SineWave::new(440.0).amplify(0.1).compress(
+            -12.0,
+            4.0,
+            Duration::from_millis(5),
+            Duration::from_millis(100),
+        );
+        assert!((peak(source) - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn loud_sound_is_compressed() {
+        // 12 dB above the threshold, reduced by 9 dB at the peaks.
+        let source = SineWave::new(440.0).compress(
+            -12.0,
+            4.0,
+            Duration::from_millis(5),
+            Duration::from_millis(200),
+        );
+        let peak = peak(source);
+        assert!(peak > 0.3 && peak < 0.5, "{}", peak);
+    }
+
+    #[test]
+    fn limiter_never_exceeds_threshold() {
+        let source = SineWave::new(440.0).amplify(3.0).limit(
+            -1.0,
+            Duration::from_secs(0),
+            Duration::from_millis(50),
+        );
+        assert!(source.take(48000).all(|s| s.abs() <= 0.8913 + 1e-4));
+    }
+
+    #[test]
+    fn integer_samples() {
+        let mut source = SamplesBuffer::new(1, 48000, vec![i16::MAX; 4800]).limit(
+            -6.0,
+            Duration::from_secs(0),
+            Duration::from_millis(50),
+        );
+        // -6 dBFS is slightly above half of the maximum.
+        assert!(source.all(|s| s > 16000 && s < 16450));
+    }
+}
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 /// Internal function that builds a `Delay` object.
@@ -8,16 +9,24 @@ where
     I: Source,
     I::Item: Sample,
 {
-    let duration_ns = duration.as_secs() * 1000000000 + duration.subsec_nanos() as u64;
-    let samples = duration_ns * input.sample_rate() as u64 / 1000000000 * input.channels() as u64;
-
     Delay {
+        remaining_samples: remaining_samples(duration, &input),
         input,
-        remaining_samples: samples as usize,
         requested_duration: duration,
     }
 }
 
+/// Returns the number of silent samples needed to fill `duration` in the format of `input`.
+fn remaining_samples<I>(duration: Duration, input: &I) -> usize
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let duration_ns = duration.as_secs() * 1000000000 + duration.subsec_nanos() as u64;
+    let samples = duration_ns * input.sample_rate() as u64 / 1000000000 * input.channels() as u64;
+    samples as usize
+}
+
 /// A source that delays the given source by a certain amount.
 #[derive(Clone, Debug)]
 pub struct Delay<I> {
@@ -94,6 +103,11 @@ where
         self.input.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.input.sample_rate()
@@ -105,4 +119,16 @@ where
             .total_duration()
             .map(|val| val + self.requested_duration)
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        if pos < self.requested_duration {
+            self.input.try_seek(Duration::from_secs(0))?;
+            self.remaining_samples = remaining_samples(self.requested_duration - pos, &self.input);
+        } else {
+            self.input.try_seek(pos - self.requested_duration)?;
+            self.remaining_samples = 0;
+        }
+        Ok(())
+    }
 }
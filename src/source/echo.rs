@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Internal function that builds a `Echo` object.
+pub fn echo<I>(input: I, delay: Duration, decay: f32) -> Echo<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    Echo {
+        input,
+        delay,
+        decay,
+        buffer: Vec::new(),
+        pos: 0,
+        channels: 0,
+        sample_rate: 0,
+        channel: 0,
+    }
+}
+
+/// Filter that repeats the sound after a delay, each repetition quieter than the previous one.
+#[derive(Clone, Debug)]
+pub struct Echo<I> {
+    input: I,
+    delay: Duration,
+    decay: f32,
+    // The last `delay` of the output, interleaved like the samples of the input.
+    buffer: Vec<f32>,
+    pos: usize,
+    // Format of the current frame, and channel of the next sample.
+    channels: u16,
+    sample_rate: u32,
+    channel: usize,
+}
+
+impl<I> Echo<I> {
+    /// Sets the time between two repetitions. The echoes that are already playing are lost.
+    #[inline]
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+        // The buffer is resized at the next frame.
+        self.channels = 0;
+    }
+
+    /// Sets the factor applied to the volume of each repetition. Must be lower than 1.0 for the
+    /// echo to fade out.
+    #[inline]
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay;
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> Echo<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    /// Clears the buffer if the format of the input changed.
+    fn update_format(&mut self) {
+        let channels = self.input.channels().max(1);
+        let sample_rate = self.input.sample_rate();
+        if channels != self.channels || sample_rate != self.sample_rate {
+            let frames = (self.delay.as_secs_f64() * sample_rate as f64) as usize;
+            self.buffer = vec![0.0; frames * channels as usize];
+            self.pos = 0;
+            self.channels = channels;
+            self.sample_rate = sample_rate;
+        }
+    }
+}
+
+impl<I> Iterator for Echo<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        // The format can only change between two frames.
+        if self.channel == 0 {
+            self.update_format();
+        }
+        let last_in_frame = self.input.current_frame_len() == Some(1);
+
+        let sample = self.input.next()?.to_f32();
+        let result = match self.buffer.get_mut(self.pos) {
+            Some(delayed) => {
+                let result = sample + self.decay * *delayed;
+                *delayed = result;
+                self.pos = (self.pos + 1) % self.buffer.len();
+                result
+            }
+            None => sample,
+        };
+
+        self.channel += 1;
+        if last_in_frame || self.channel >= self.channels as usize {
+            self.channel = 0;
+        }
+
+        Some(CpalSample::from(&result))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for Echo<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for Echo<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        // The echoes of the previous position must not be heard at the new one.
+        self.buffer.iter_mut().for_each(|s| *s = 0.0);
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::buffer::SamplesBuffer;
+    use crate::source::Source;
+
+    #[test]
+    fn repeats_with_decay() {
+        let mut impulse = vec![0.0f32; 40];
+        impulse[0] = 1.0;
+        let source = SamplesBuffer::new(1, 1000, impulse).echo(Duration::from_millis(10), 0.5);
+
+        let output: Vec<f32> = source.collect();
+        assert_eq!(output.len(), 40);
+        for (i, &s) in output.iter().enumerate() {
+            let expected = match i {
+                0 => 1.0,
+                10 => 0.5,
+                20 => 0.25,
+                30 => 0.125,
+                _ => 0.0,
+            };
+            assert_eq!(s, expected, "sample {}", i);
+        }
+    }
+
+    #[test]
+    fn channels_are_separate() {
+        let mut samples = vec![0i16; 40];
+        samples[1] = 1000;
+        let source = SamplesBuffer::new(2, 1000, samples).echo(Duration::from_millis(5), 0.5);
+
+        let output: Vec<i16> = source.collect();
+        assert!(output.iter().step_by(2).all(|&s| s == 0));
+        assert_eq!(output[11], 500);
+        assert_eq!(output[21], 250);
+    }
+}
@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use crate::source::blt::{Biquad, BltFormula};
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Center frequencies of the bands of the presets, one octave apart.
+const PRESET_FREQUENCIES: [u32; 10] = [31, 62, 125, 250, 500, 1000, 2000, 4000, 8000, 16000];
+
+/// Quality factor of the bands of the presets, which gives them a width of one octave.
+const PRESET_Q: f32 = std::f32::consts::SQRT_2;
+
+/// Internal function that builds an `Equalizer` object.
+pub fn equalizer<I>(input: I, bands: Vec<EqBand>) -> Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let filters = bands
+        .iter()
+        .map(|band| Biquad::new(band.formula()))
+        .collect();
+    Equalizer {
+        input,
+        bands,
+        filters,
+        channels: 1,
+        sample_rate: 0,
+        channel: 0,
+    }
+}
+
+/// A band of an `Equalizer`, which is a peaking filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EqBand {
+    /// Center frequency of the band, in Hz.
+    pub freq: u32,
+    /// Quality factor of the band. The higher, the narrower the band.
+    pub q: f32,
+    /// Gain applied to the frequencies of the band, in dB.
+    pub gain_db: f32,
+}
+
+impl EqBand {
+    #[inline]
+    fn formula(&self) -> BltFormula {
+        BltFormula::Peaking {
+            freq: self.freq,
+            q: self.q,
+            gain_db: self.gain_db,
+        }
+    }
+}
+
+/// Gains of a 10-band equalizer, with bands from 31 Hz to 16 kHz one octave apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EqualizerPreset {
+    /// No change.
+    Flat,
+    /// Louder low frequencies.
+    BassBoost,
+    /// Louder high frequencies.
+    TrebleBoost,
+    /// Emphasizes the frequencies of the human voice.
+    Vocal,
+    /// Louder low and high frequencies.
+    Rock,
+    /// Emphasizes the mid frequencies.
+    Pop,
+}
+
+impl EqualizerPreset {
+    /// Returns the gain of each band, in dB, from the lowest frequency to the highest.
+    pub fn gains(self) -> [f32; 10] {
+        match self {
+            EqualizerPreset::Flat => [0.0; 10],
+            EqualizerPreset::BassBoost => [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            EqualizerPreset::TrebleBoost => [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 2.0, 4.0, 5.0, 6.0],
+            EqualizerPreset::Vocal => [-2.0, -2.0, -1.0, 0.0, 2.0, 4.0, 4.0, 2.0, 0.0, -1.0],
+            EqualizerPreset::Rock => [5.0, 4.0, 3.0, 1.0, -1.0, -1.0, 1.0, 3.0, 4.0, 5.0],
+            EqualizerPreset::Pop => [-1.0, 0.0, 2.0, 3.0, 4.0, 3.0, 2.0, 0.0, -1.0, -1.0],
+        }
+    }
+
+    /// Returns the bands of the preset.
+    pub fn bands(self) -> Vec<EqBand> {
+        PRESET_FREQUENCIES
+            .iter()
+            .zip(self.gains().iter())
+            .map(|(&freq, &gain_db)| EqBand {
+                freq,
+                q: PRESET_Q,
+                gain_db,
+            })
+            .collect()
+    }
+}
+
+/// Multi-band equalizer, made of one peaking filter per band.
+///
+/// The bands whose frequency is above half the sample rate of the source are ignored.
+#[derive(Clone, Debug)]
+pub struct Equalizer<I> {
+    input: I,
+    bands: Vec<EqBand>,
+    filters: Vec<Biquad>,
+    // Format of the current frame, and channel of the next sample.
+    channels: u16,
+    sample_rate: u32,
+    channel: usize,
+}
+
+impl<I> Equalizer<I> {
+    /// Returns the bands of the equalizer.
+    #[inline]
+    pub fn bands(&self) -> &[EqBand] {
+        &self.bands
+    }
+
+    /// Sets the gain of a band, in dB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set_gain(&mut self, index: usize, gain_db: f32) {
+        let band = EqBand {
+            gain_db,
+            ..self.bands[index]
+        };
+        self.set_band(index, band);
+    }
+
+    /// Replaces a band.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set_band(&mut self, index: usize, band: EqBand) {
+        self.bands[index] = band;
+        self.filters[index].set_formula(band.formula());
+    }
+
+    /// Replaces all the bands. The bands that keep their index keep their state, so that the
+    /// change doesn't click.
+    pub fn set_bands(&mut self, bands: Vec<EqBand>) {
+        self.filters.truncate(bands.len());
+        for (index, band) in bands.iter().enumerate() {
+            match self.filters.get_mut(index) {
+                Some(filter) => filter.set_formula(band.formula()),
+                None => {
+                    let mut filter = Biquad::new(band.formula());
+                    filter.set_format(self.channels, self.sample_rate);
+                    self.filters.push(filter);
+                }
+            }
+        }
+        self.bands = bands;
+    }
+
+    /// Replaces all the bands with the ones of a preset.
+    #[inline]
+    pub fn set_preset(&mut self, preset: EqualizerPreset) {
+        self.set_bands(preset.bands());
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> Iterator for Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        // The format can only change between two frames.
+        if self.channel == 0 {
+            self.channels = self.input.channels().max(1);
+            self.sample_rate = self.input.sample_rate();
+            for filter in &mut self.filters {
+                filter.set_format(self.channels, self.sample_rate);
+            }
+        }
+        let last_in_frame = self.input.current_frame_len() == Some(1);
+
+        let mut sample = self.input.next()?.to_f32();
+        for filter in &mut self.filters {
+            if filter.formula().freq() as u64 * 2 < self.sample_rate as u64 {
+                sample = filter.process(sample, self.channel);
+            }
+        }
+
+        self.channel += 1;
+        if last_in_frame || self.channel >= self.channels as usize {
+            self.channel = 0;
+        }
+
+        Some(CpalSample::from(&sample))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for Equalizer<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EqBand, EqualizerPreset};
+    use crate::source::{SineWave, Source};
+
+    fn peak<S>(source: S) -> f32
+    where
+        S: Source<Item = f32>,
+    {
+        source
+            .skip(4800)
+            .take(4800)
+            .fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn flat_preset_is_transparent() {
+        let source = SineWave::new(440.0).equalizer(EqualizerPreset::Flat.bands());
+        assert_eq!(source.bands().len(), 10);
+        assert!((peak(source) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn boosts_band() {
+        let mut source = SineWave::new(1000.0).equalizer(EqualizerPreset::Flat.bands());
+        source.set_gain(5, 6.0);
+        assert!((peak(source) - 1.995).abs() < 0.05);
+
+        // The band is above the Nyquist frequency and is ignored.
+        let band = EqBand {
+            freq: 30000,
+            q: 1.0,
+            gain_db: 12.0,
+        };
+        let source = SineWave::new(1000.0).equalizer(vec![band]);
+        assert!((peak(source) - 1.0).abs() < 0.01);
+
+        let band = EqBand {
+            freq: u32::MAX,
+            ..band
+        };
+        let source = SineWave::new(1000.0).equalizer(vec![band]);
+        assert!((peak(source) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn presets() {
+        let bass = peak(SineWave::new(62.0).equalizer(EqualizerPreset::BassBoost.bands()));
+        assert!(bass > 1.5);
+
+        let mut source = SineWave::new(62.0).equalizer(EqualizerPreset::BassBoost.bands());
+        source.set_preset(EqualizerPreset::TrebleBoost);
+        assert!((peak(source) - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,317 @@
+use std::time::Duration;
+
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Delays of the comb filters, in samples at 44.1kHz.
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+
+/// Delays of the all-pass filters, in samples at 44.1kHz.
+const ALL_PASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+
+/// Added to the delays of every other channel, so that the channels don't sound the same.
+const STEREO_SPREAD: usize = 23;
+
+/// Sample rate of the tunings above.
+const TUNING_SAMPLE_RATE: u32 = 44100;
+
+/// Gain applied to the input of the filters, which would otherwise saturate.
+const INPUT_GAIN: f32 = 0.015;
+
+/// Gain applied to the output of the filters.
+const WET_GAIN: f32 = 3.0;
+
+/// Internal function that builds a `Freeverb` object.
+pub fn freeverb<I>(input: I, room_size: f32, damping: f32, wet: f32) -> Freeverb<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    Freeverb {
+        input,
+        room_size,
+        damping,
+        wet,
+        channel_reverbs: Vec::new(),
+        sample_rate: 0,
+        channel: 0,
+    }
+}
+
+/// Filter that adds the reverberation of a room to the sound, with the Freeverb algorithm: a
+/// network of comb and all-pass filters for each channel.
+///
+/// The reverberation stops with the source. Append some silence to the source to hear all of it.
+#[derive(Clone, Debug)]
+pub struct Freeverb<I> {
+    input: I,
+    room_size: f32,
+    damping: f32,
+    wet: f32,
+    // One network of filters per channel.
+    channel_reverbs: Vec<ChannelReverb>,
+    // Format of the current frame, and channel of the next sample.
+    sample_rate: u32,
+    channel: usize,
+}
+
+impl<I> Freeverb<I> {
+    /// Sets the size of the room, between 0.0 and 1.0. The bigger the room, the longer the sound
+    /// reverberates.
+    #[inline]
+    pub fn set_room_size(&mut self, room_size: f32) {
+        self.room_size = room_size;
+    }
+
+    /// Sets how much the walls absorb the high frequencies, between 0.0 and 1.0.
+    #[inline]
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+    }
+
+    /// Sets the proportion of reverberated sound in the output, between 0.0 (only the original
+    /// sound) and 1.0 (only the reverberation).
+    #[inline]
+    pub fn set_wet(&mut self, wet: f32) {
+        self.wet = wet;
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> Iterator for Freeverb<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        // The format can only change between two frames.
+        if self.channel == 0 {
+            let channels = self.input.channels().max(1) as usize;
+            let sample_rate = self.input.sample_rate();
+            if channels != self.channel_reverbs.len() || sample_rate != self.sample_rate {
+                self.channel_reverbs = (0..channels)
+                    .map(|channel| ChannelReverb::new(sample_rate, (channel % 2) * STEREO_SPREAD))
+                    .collect();
+                self.sample_rate = sample_rate;
+            }
+        }
+        let last_in_frame = self.input.current_frame_len() == Some(1);
+
+        let sample = self.input.next()?.to_f32();
+        let feedback = self.room_size.clamp(0.0, 1.0) * 0.28 + 0.7;
+        let damping = self.damping.clamp(0.0, 1.0) * 0.4;
+        let reverb = self.channel_reverbs[self.channel].process(sample, feedback, damping);
+        let result = sample * (1.0 - self.wet) + reverb * self.wet * WET_GAIN;
+
+        self.channel += 1;
+        if last_in_frame || self.channel >= self.channel_reverbs.len() {
+            self.channel = 0;
+        }
+
+        Some(CpalSample::from(&result))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for Freeverb<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for Freeverb<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        // The reverberation of the previous position must not be heard at the new one.
+        self.channel_reverbs.clear();
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// The filters of one channel.
+#[derive(Clone, Debug)]
+struct ChannelReverb {
+    combs: Vec<Comb>,
+    all_passes: Vec<AllPass>,
+}
+
+impl ChannelReverb {
+    fn new(sample_rate: u32, spread: usize) -> ChannelReverb {
+        let delay = |tuning: usize| {
+            let samples = (tuning + spread) as u64 * sample_rate as u64 / TUNING_SAMPLE_RATE as u64;
+            (samples as usize).max(1)
+        };
+        ChannelReverb {
+            combs: COMB_TUNING.iter().map(|&t| Comb::new(delay(t))).collect(),
+            all_passes: ALL_PASS_TUNING
+                .iter()
+                .map(|&t| AllPass::new(delay(t)))
+                .collect(),
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, sample: f32, feedback: f32, damping: f32) -> f32 {
+        let input = sample * INPUT_GAIN;
+        let mut output = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(input, feedback, damping))
+            .sum();
+        for all_pass in &mut self.all_passes {
+            output = all_pass.process(output);
+        }
+        output
+    }
+}
+
+/// Feedback comb filter with a low-pass filter in the feedback loop.
+#[derive(Clone, Debug)]
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    filter_store: f32,
+}
+
+impl Comb {
+    fn new(delay: usize) -> Comb {
+        Comb {
+            buffer: vec![0.0; delay],
+            pos: 0,
+            filter_store: 0.0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.pos];
+        self.filter_store = output * (1.0 - damping) + self.filter_store * damping;
+        self.buffer[self.pos] = input + self.filter_store * feedback;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// Schroeder all-pass filter, which makes the echoes of the combs denser.
+#[derive(Clone, Debug)]
+struct AllPass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl AllPass {
+    fn new(delay: usize) -> AllPass {
+        AllPass {
+            buffer: vec![0.0; delay],
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = input + delayed * 0.5;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::SamplesBuffer;
+    use crate::source::Source;
+
+    /// Returns the energy of the reverberation of an impulse, between 0.5s and 1s.
+    fn tail_energy(room_size: f32, damping: f32, wet: f32) -> f32 {
+        let mut impulse = vec![0.0f32; 48000];
+        impulse[0] = 1.0;
+        SamplesBuffer::new(1, 48000, impulse)
+            .freeverb(room_size, damping, wet)
+            .skip(24000)
+            .map(|s| s * s)
+            .sum()
+    }
+
+    #[test]
+    fn dry_is_unchanged() {
+        let samples = vec![0.5f32, -0.25, 0.125, 0.0];
+        let source = SamplesBuffer::new(2, 44100, samples.clone()).freeverb(0.5, 0.5, 0.0);
+        assert_eq!(source.collect::<Vec<_>>(), samples);
+    }
+
+    #[test]
+    fn bigger_rooms_reverberate_longer() {
+        let small = tail_energy(0.2, 0.5, 1.0);
+        let big = tail_energy(0.9, 0.5, 1.0);
+        assert!(small > 0.0);
+        assert!(big > small * 10.0, "{} {}", small, big);
+    }
+
+    #[test]
+    fn channels_are_separate() {
+        let mut samples = vec![0i16; 2 * 4800];
+        samples[0] = i16::MAX;
+        let output: Vec<i16> = SamplesBuffer::new(2, 48000, samples)
+            .freeverb(0.5, 0.5, 0.5)
+            .collect();
+        assert!(output.iter().step_by(2).any(|&s| s != 0));
+        assert!(output.iter().skip(1).step_by(2).all(|&s| s == 0));
+    }
+}
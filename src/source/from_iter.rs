@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use crate::source::ChannelLayout;
 use crate::{Sample, Source};
 
 /// Builds a source that chains sources provided by an iterator.
@@ -121,6 +122,13 @@ where
         }
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.current_source
+            .as_ref()
+            .and_then(|src| src.channel_layout())
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         if let Some(src) = &self.current_source {
@@ -2,6 +2,7 @@ use std::cmp;
 use std::time::Duration;
 
 use crate::source::uniform::UniformSourceIterator;
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 /// Internal function that builds a `Mix` object.
@@ -103,6 +104,11 @@ where
         self.input1.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input1.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.input1.sample_rate()
@@ -118,4 +124,12 @@ where
             _ => None,
         }
     }
+
+    /// Seeks both sources. This isn't atomic: if the second one fails, the first one has already
+    /// been seeked, and the two are out of sync until the next successful seek.
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input1.try_seek(pos)?;
+        self.input2.try_seek(pos)
+    }
 }
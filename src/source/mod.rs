@@ -1,23 +1,34 @@
 //! Sources of sound and various filters.
 
+use std::error::Error;
+use std::fmt;
 use std::time::Duration;
 
 use crate::Sample;
 
+pub use self::agc::AutomaticGainControl;
 pub use self::amplify::Amplify;
 pub use self::blt::BltFilter;
 pub use self::buffered::Buffered;
+pub use self::channel_layout::{ChannelLayout, Speaker};
 pub use self::channel_volume::ChannelVolume;
+pub use self::compressor::Compressor;
+pub(crate) use self::compressor::CompressorState;
 pub use self::crossfade::Crossfade;
 pub use self::delay::Delay;
 pub use self::done::Done;
+pub use self::echo::Echo;
 pub use self::empty::Empty;
+pub use self::equalizer::{EqBand, Equalizer, EqualizerPreset};
 pub use self::fadein::FadeIn;
+pub use self::freeverb::Freeverb;
 pub use self::from_factory::{from_factory, FromFactoryIter};
 pub use self::from_iter::{from_iter, FromIter};
 pub use self::mix::Mix;
 pub use self::pausable::Pausable;
 pub use self::periodic::PeriodicAccess;
+pub use self::pitch_shift::PitchShift;
+pub use self::position::TrackPosition;
 pub use self::repeat::Repeat;
 pub use self::samples_converter::SamplesConverter;
 pub use self::sine::SineWave;
@@ -26,23 +37,32 @@ pub use self::spatial::Spatial;
 pub use self::speed::Speed;
 pub use self::stoppable::Stoppable;
 pub use self::take::TakeDuration;
+pub use self::time_stretch::TimeStretch;
 pub use self::uniform::UniformSourceIterator;
 pub use self::zero::Zero;
 
+mod agc;
 mod amplify;
 mod blt;
 mod buffered;
+mod channel_layout;
 mod channel_volume;
+mod compressor;
 mod crossfade;
 mod delay;
 mod done;
+mod echo;
 mod empty;
+mod equalizer;
 mod fadein;
+mod freeverb;
 mod from_factory;
 mod from_iter;
 mod mix;
 mod pausable;
 mod periodic;
+mod pitch_shift;
+mod position;
 mod repeat;
 mod samples_converter;
 mod sine;
@@ -51,6 +71,7 @@ mod spatial;
 mod speed;
 mod stoppable;
 mod take;
+mod time_stretch;
 mod uniform;
 mod zero;
 
@@ -139,11 +160,36 @@ where
     /// Returns the rate at which the source should be played. In number of samples per second.
     fn sample_rate(&self) -> u32;
 
+    /// Returns the speaker position of each channel, if known. When it isn't, the channels are
+    /// assumed to be in the usual order of `ChannelLayout::default_for`.
+    ///
+    /// Like `channels()`, it can only change between two frames.
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        None
+    }
+
     /// Returns the total duration of this source, if known.
     ///
     /// `None` indicates at the same time "infinite" or "unknown".
     fn total_duration(&self) -> Option<Duration>;
 
+    /// Attempts to seek to a given position in the source.
+    ///
+    /// The position is relative to the start of the source. Filters forward the call to the
+    /// source they wrap, adjusting the position when they change the timing of the sound.
+    ///
+    /// Returns `SeekError::NotSupported` if the source (or one of the sources it wraps) can not
+    /// seek. The source is left untouched in that case, except for sources that combine several
+    /// others, such as `mix`, which may have seeked some of them. Seeking beyond the end of the
+    /// source either ends it or returns an error, depending on the source.
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let _ = pos;
+        Err(SeekError::NotSupported {
+            underlying_source: std::any::type_name::<Self>(),
+        })
+    }
+
     /// Stores the source in a buffer in addition to returning it. This iterator can be cloned.
     #[inline]
     fn buffered(self) -> Buffered<Self>
@@ -266,7 +312,51 @@ where
         speed::speed(self, ratio)
     }
 
-    /// Adds a basic reverb effect.
+    /// Changes the tempo of the sound without changing its pitch. `2.0` plays the sound twice as
+    /// fast. Unlike `speed`, this modifies the samples. Rates that aren't positive and finite are
+    /// treated as `1.0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rodio::source::{SineWave, Source};
+    ///
+    /// // Still a 440Hz tone, but the source ends twice as early.
+    /// let source = SineWave::new(440.0).take_duration(std::time::Duration::from_secs(2));
+    /// let source = source.time_stretch(2.0);
+    /// ```
+    #[inline]
+    fn time_stretch(self, rate: f32) -> TimeStretch<Self>
+    where
+        Self: Sized,
+    {
+        time_stretch::time_stretch(self, rate)
+    }
+
+    /// Changes the pitch of the sound by `semitones` without changing its duration. `12.0`
+    /// raises the sound by an octave. Unlike `speed`, the sound ends at the same time.
+    ///
+    /// The shift can be changed while the sound plays with `PitchShift::set_semitones` or
+    /// `PitchShift::set_factor`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rodio::source::{SineWave, Source};
+    ///
+    /// // Sounds like a 660Hz tone.
+    /// let source = SineWave::new(440.0).pitch_shift(7.0);
+    /// ```
+    #[inline]
+    fn pitch_shift(self, semitones: f32) -> PitchShift<Self>
+    where
+        Self: Sized,
+    {
+        pitch_shift::pitch_shift(self, semitones)
+    }
+
+    /// Adds a basic reverb effect, made of a single echo. See `freeverb` for the reverberation of
+    /// a room, and `echo` for repeated echoes.
     ///
     /// This function requires the source to implement `Clone`. This can be done by using
     /// `buffered()`.
@@ -287,6 +377,39 @@ where
         self.mix(echo)
     }
 
+    /// Adds the reverberation of a room to the sound.
+    ///
+    /// `room_size` sets how long the sound reverberates and `damping` how much the walls absorb
+    /// the high frequencies, both between 0.0 and 1.0. `wet` is the proportion of reverberated
+    /// sound in the output, between 0.0 and 1.0. They can be changed while the sound plays, for
+    /// example with `periodic_access`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rodio::source::{SineWave, Source};
+    ///
+    /// let source = SineWave::new(440.0).freeverb(0.8, 0.5, 0.3);
+    /// ```
+    #[inline]
+    fn freeverb(self, room_size: f32, damping: f32, wet: f32) -> Freeverb<Self>
+    where
+        Self: Sized,
+    {
+        freeverb::freeverb(self, room_size, damping, wet)
+    }
+
+    /// Repeats the sound every `delay`, multiplying the volume of each repetition by `decay`.
+    ///
+    /// Unlike `reverb`, this doesn't require the source to implement `Clone`.
+    #[inline]
+    fn echo(self, delay: Duration, decay: f32) -> Echo<Self>
+    where
+        Self: Sized,
+    {
+        echo::echo(self, delay, decay)
+    }
+
     /// Converts the samples of this source to another type.
     #[inline]
     fn convert_samples<D>(self) -> SamplesConverter<Self, D>
@@ -307,6 +430,16 @@ where
         pausable::pausable(self, initially_paused)
     }
 
+    /// Keeps track of the elapsed time of the sound, which can be read with
+    /// `TrackPosition::get_pos`. Seeking through the returned source updates the position.
+    #[inline]
+    fn track_position(self) -> TrackPosition<Self>
+    where
+        Self: Sized,
+    {
+        position::track_position(self)
+    }
+
     /// Makes the sound stoppable.
     // TODO: add example
     #[inline]
@@ -323,7 +456,6 @@ where
     fn low_pass(self, freq: u32) -> BltFilter<Self>
     where
         Self: Sized,
-        Self: Source<Item = f32>,
     {
         blt::low_pass(self, freq)
     }
@@ -332,10 +464,164 @@ where
     fn high_pass(self, freq: u32) -> BltFilter<Self>
     where
         Self: Sized,
-        Self: Source<Item = f32>,
     {
         blt::high_pass(self, freq)
     }
+
+    /// Applies a low-pass filter with the given Q to the source.
+    #[inline]
+    fn low_pass_with_q(self, freq: u32, q: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::low_pass_with_q(self, freq, q)
+    }
+
+    /// Applies a high-pass filter with the given Q to the source.
+    #[inline]
+    fn high_pass_with_q(self, freq: u32, q: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::high_pass_with_q(self, freq, q)
+    }
+
+    /// Applies a band-pass filter centered on `freq` to the source. The gain at `freq` is 0 dB.
+    #[inline]
+    fn band_pass(self, freq: u32, q: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::band_pass(self, freq, q)
+    }
+
+    /// Applies a notch filter to the source, which removes the frequencies around `freq`.
+    #[inline]
+    fn notch(self, freq: u32, q: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::notch(self, freq, q)
+    }
+
+    /// Applies an all-pass filter to the source, which changes the phase of the frequencies around
+    /// `freq` without changing their amplitude.
+    #[inline]
+    fn all_pass(self, freq: u32, q: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::all_pass(self, freq, q)
+    }
+
+    /// Amplifies or attenuates the frequencies around `freq` by `gain_db` dB.
+    #[inline]
+    fn peaking_eq(self, freq: u32, q: f32, gain_db: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::peaking_eq(self, freq, q, gain_db)
+    }
+
+    /// Amplifies or attenuates the frequencies below `freq` by `gain_db` dB.
+    #[inline]
+    fn low_shelf(self, freq: u32, q: f32, gain_db: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::low_shelf(self, freq, q, gain_db)
+    }
+
+    /// Amplifies or attenuates the frequencies above `freq` by `gain_db` dB.
+    #[inline]
+    fn high_shelf(self, freq: u32, q: f32, gain_db: f32) -> BltFilter<Self>
+    where
+        Self: Sized,
+    {
+        blt::high_shelf(self, freq, q, gain_db)
+    }
+
+    /// Applies a multi-band equalizer to the source. See `EqualizerPreset` for a 10-band layout.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rodio::source::{EqualizerPreset, SineWave, Source};
+    ///
+    /// let source = SineWave::new(440.0).equalizer(EqualizerPreset::BassBoost.bands());
+    /// ```
+    #[inline]
+    fn equalizer(self, bands: Vec<EqBand>) -> Equalizer<Self>
+    where
+        Self: Sized,
+    {
+        equalizer::equalizer(self, bands)
+    }
+
+    /// Reduces the volume of the sound when it goes above `threshold_db` dBFS, by `ratio`.
+    ///
+    /// With a ratio of 4, a sound 8 dB above the threshold comes out 2 dB above it. `attack` and
+    /// `release` set how fast the volume is reduced once the sound goes above the threshold, and
+    /// how fast it goes back to normal once the sound goes below it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rodio::source::{SineWave, Source};
+    ///
+    /// let source = SineWave::new(440.0).compress(
+    ///     -12.0,
+    ///     4.0,
+    ///     Duration::from_millis(5),
+    ///     Duration::from_millis(100),
+    /// );
+    /// ```
+    #[inline]
+    fn compress(
+        self,
+        threshold_db: f32,
+        ratio: f32,
+        attack: Duration,
+        release: Duration,
+    ) -> Compressor<Self>
+    where
+        Self: Sized,
+    {
+        compressor::compress(self, threshold_db, ratio, attack, release)
+    }
+
+    /// Prevents the sound from going above `threshold_db` dBFS. This is a compressor with an
+    /// infinite ratio.
+    ///
+    /// With a zero `attack`, no sample ever goes above the threshold.
+    #[inline]
+    fn limit(self, threshold_db: f32, attack: Duration, release: Duration) -> Compressor<Self>
+    where
+        Self: Sized,
+    {
+        compressor::compress(self, threshold_db, f32::INFINITY, attack, release)
+    }
+
+    /// Amplifies or attenuates the sound so that its RMS level stays close to `target_level`,
+    /// between 0.0 and 1.0.
+    ///
+    /// `attack` sets how fast the gain is reduced when the sound gets louder, and `release` how
+    /// fast it is raised when the sound gets quieter. The sound is never amplified by more than
+    /// `max_gain`, so that silence and background noise stay quiet.
+    #[inline]
+    fn automatic_gain_control(
+        self,
+        target_level: f32,
+        attack: Duration,
+        release: Duration,
+        max_gain: f32,
+    ) -> AutomaticGainControl<Self>
+    where
+        Self: Sized,
+    {
+        agc::automatic_gain_control(self, target_level, attack, release, max_gain)
+    }
 }
 
 impl<S> Source for Box<dyn Source<Item = S>>
@@ -352,6 +638,11 @@ where
         (**self).channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        (**self).channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         (**self).sample_rate()
@@ -361,6 +652,11 @@ where
     fn total_duration(&self) -> Option<Duration> {
         (**self).total_duration()
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        (**self).try_seek(pos)
+    }
 }
 
 impl<S> Source for Box<dyn Source<Item = S> + Send>
@@ -377,6 +673,11 @@ where
         (**self).channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        (**self).channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         (**self).sample_rate()
@@ -386,6 +687,11 @@ where
     fn total_duration(&self) -> Option<Duration> {
         (**self).total_duration()
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        (**self).try_seek(pos)
+    }
 }
 
 impl<S> Source for Box<dyn Source<Item = S> + Send + Sync>
@@ -402,6 +708,11 @@ where
         (**self).channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        (**self).channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         (**self).sample_rate()
@@ -411,4 +722,62 @@ where
     fn total_duration(&self) -> Option<Duration> {
         (**self).total_duration()
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        (**self).try_seek(pos)
+    }
+}
+
+/// Error that can happen when seeking a source.
+#[derive(Debug)]
+pub enum SeekError {
+    /// The source does not support seeking.
+    NotSupported {
+        /// Type name of the source that can not seek.
+        underlying_source: &'static str,
+    },
+
+    /// An IO error occurred while seeking the underlying stream.
+    IoError(std::io::Error),
+
+    /// The decoder could not seek in the stream or could not decode the data at the new position.
+    DecodeError(String),
+
+    /// The seek was requested from a `Sink`, but the sound wasn't played in time for it to be
+    /// performed, for example because the stream isn't rendering.
+    TimedOut,
+
+    /// The seek was requested from a `Sink`, but replaced by another one before it was
+    /// performed.
+    Superseded,
+}
+
+impl fmt::Display for SeekError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeekError::NotSupported { underlying_source } => {
+                write!(f, "Seeking is not supported by {}", underlying_source)
+            }
+            SeekError::IoError(e) => e.fmt(f),
+            SeekError::DecodeError(msg) => write!(f, "{}", msg),
+            SeekError::TimedOut => write!(f, "The seek was not performed in time"),
+            SeekError::Superseded => write!(f, "The seek was replaced by another one"),
+        }
+    }
+}
+
+impl Error for SeekError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SeekError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SeekError {
+    fn from(err: std::io::Error) -> Self {
+        SeekError::IoError(err)
+    }
 }
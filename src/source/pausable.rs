@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 /// Internal function that builds a `Pausable` object.
@@ -106,6 +107,11 @@ where
         self.input.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.input.sample_rate()
@@ -115,4 +121,9 @@ where
     fn total_duration(&self) -> Option<Duration> {
         self.input.total_duration()
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
 }
@@ -0,0 +1,388 @@
+use std::mem;
+use std::time::Duration;
+
+use crate::source::time_stretch::scale_duration;
+use crate::source::{ChannelLayout, SeekError, TimeStretch};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Internal function that builds a `PitchShift` object.
+pub fn pitch_shift<I>(input: I, semitones: f32) -> PitchShift<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let factor = valid_factor(semitones_to_factor(semitones));
+    PitchShift {
+        input: input.time_stretch(1.0 / factor),
+        factor,
+        resampling: false,
+        channels: 1,
+        sample_rate: 0,
+        previous: Vec::new(),
+        next: Vec::new(),
+        position: 0.0,
+        frame: Vec::new(),
+        frame_pos: 0,
+        channel: 0,
+    }
+}
+
+/// Filter that changes the pitch of the sound without changing its duration.
+///
+/// The sound is time stretched by the pitch factor, then resampled back to its duration. With
+/// no shift, the samples are passed through unchanged.
+#[derive(Clone, Debug)]
+pub struct PitchShift<I> {
+    input: TimeStretch<I>,
+    factor: f32,
+    // Whether the samples are resampled or passed through.
+    resampling: bool,
+    // Format of the resampled samples.
+    channels: u16,
+    sample_rate: u32,
+    // The two frames of the input around `position`.
+    previous: Vec<f32>,
+    next: Vec<f32>,
+    position: f64,
+    // The frame being returned.
+    frame: Vec<f32>,
+    frame_pos: usize,
+    // Channel of the next sample, when the samples are passed through.
+    channel: usize,
+}
+
+impl<I> PitchShift<I> {
+    /// Modifies the shift, in semitones. `12.0` raises the sound by an octave.
+    ///
+    /// Shifts whose factor isn't positive and finite, such as NaN, are treated as no shift.
+    #[inline]
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.set_factor(semitones_to_factor(semitones));
+    }
+
+    /// Modifies the shift, as a factor applied to the frequencies. `2.0` raises the sound by an
+    /// octave.
+    ///
+    /// The factor must be positive and finite; other values are treated as `1.0`.
+    #[inline]
+    pub fn set_factor(&mut self, factor: f32) {
+        let factor = valid_factor(factor);
+        self.factor = factor;
+        self.input.set_rate(1.0 / factor);
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        self.input.inner()
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        self.input.inner_mut()
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input.into_inner()
+    }
+}
+
+impl<I> PitchShift<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    /// Reads the next frame of the stretched input into `next`. Fails if the input ended or
+    /// changed its format.
+    fn read_frame(&mut self) -> bool {
+        if self.input.channels() != self.channels || self.input.sample_rate() != self.sample_rate {
+            return false;
+        }
+        self.next.clear();
+        for _ in 0..self.channels {
+            match self.input.next() {
+                Some(sample) => self.next.push(sample.to_f32()),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Starts resampling the input, which must be at a frame boundary.
+    fn start(&mut self) -> bool {
+        self.channels = self.input.channels();
+        self.sample_rate = self.input.sample_rate();
+        self.position = 1.0;
+        self.resampling = self.channels > 0 && self.read_frame();
+        self.resampling
+    }
+
+    /// Computes the next frame into `frame`. Stops resampling if the shift went back to zero, or
+    /// if the input ended or changed its format.
+    fn resample(&mut self) {
+        self.frame.clear();
+        self.frame_pos = 0;
+
+        if self.factor == 1.0 {
+            // Skips the rest of the interval, which is shorter than a frame.
+            mem::swap(&mut self.frame, &mut self.next);
+            self.resampling = false;
+            return;
+        }
+
+        while self.position >= 1.0 {
+            mem::swap(&mut self.previous, &mut self.next);
+            if !self.read_frame() {
+                mem::swap(&mut self.frame, &mut self.previous);
+                self.resampling = false;
+                return;
+            }
+            self.position -= 1.0;
+        }
+
+        let position = self.position as f32;
+        self.frame.extend(
+            self.previous
+                .iter()
+                .zip(&self.next)
+                .map(|(&a, &b)| a + (b - a) * position),
+        );
+        self.position += self.factor as f64;
+    }
+
+    /// Returns the next sample of the input, unchanged.
+    #[inline]
+    fn next_passthrough(&mut self) -> Option<I::Item> {
+        let channels = self.input.channels() as usize;
+        let last_in_frame = self.input.current_frame_len() == Some(1);
+
+        let sample = self.input.next()?;
+
+        self.channel += 1;
+        if last_in_frame || self.channel >= channels {
+            self.channel = 0;
+        }
+        Some(sample)
+    }
+
+    #[inline]
+    fn has_frame(&self) -> bool {
+        self.frame_pos < self.frame.len()
+    }
+}
+
+impl<I> Iterator for PitchShift<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        if !self.has_frame() {
+            // Samples can only start being resampled at a frame boundary.
+            if !self.resampling && (self.factor == 1.0 || self.channel != 0 || !self.start()) {
+                return self.next_passthrough();
+            }
+            self.resample();
+            if !self.has_frame() {
+                return self.next_passthrough();
+            }
+        }
+
+        let sample = self.frame[self.frame_pos];
+        self.frame_pos += 1;
+        // Resample the next frame now, so that its format can be reported.
+        if !self.has_frame() && self.resampling {
+            self.resample();
+        }
+        Some(CpalSample::from(&sample))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.has_frame() || self.resampling {
+            (self.frame.len() - self.frame_pos, None)
+        } else {
+            self.input.size_hint()
+        }
+    }
+}
+
+impl<I> Source for PitchShift<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.has_frame() {
+            Some(self.frame.len() - self.frame_pos)
+        } else {
+            self.input.current_frame_len()
+        }
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        if self.has_frame() {
+            self.channels
+        } else {
+            self.input.channels()
+        }
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        if self.has_frame() {
+            self.sample_rate
+        } else {
+            self.input.sample_rate()
+        }
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.inner().total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // The stretched input is `factor` times longer than the sound.
+        let pos = scale_duration(pos, self.factor).ok_or(SeekError::NotSupported {
+            underlying_source: std::any::type_name::<Self>(),
+        })?;
+        self.input.try_seek(pos)?;
+        self.resampling = false;
+        self.frame.clear();
+        self.frame_pos = 0;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+#[inline]
+fn semitones_to_factor(semitones: f32) -> f32 {
+    2f32.powf(semitones / 12.0)
+}
+
+/// Replaces the factors that would never or instantly end the sound with `1.0`.
+#[inline]
+fn valid_factor(factor: f32) -> f32 {
+    if factor > 0.0 && factor.is_finite() {
+        factor
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::buffer::SamplesBuffer;
+    use crate::source::{SineWave, Source};
+
+    /// Returns the number of times the samples change sign, per second.
+    fn crossings_per_second(samples: &[f32], sample_rate: u32) -> f32 {
+        let crossings = samples
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+        crossings as f32 * sample_rate as f32 / samples.len() as f32
+    }
+
+    fn shift(semitones: f32) -> Vec<f32> {
+        SineWave::new(440.0)
+            .take_duration(Duration::from_secs(1))
+            .pitch_shift(semitones)
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_without_shift() {
+        let samples: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        let source = SamplesBuffer::new(2, 44100, samples.clone()).pitch_shift(0.0);
+        assert_eq!(source.collect::<Vec<_>>(), samples);
+    }
+
+    #[test]
+    fn octave_up() {
+        let samples = shift(12.0);
+        assert!(
+            (samples.len() as i32 - 48000).abs() < 2400,
+            "{}",
+            samples.len()
+        );
+        let crossings = crossings_per_second(&samples, 48000);
+        assert!((crossings - 1760.0).abs() < 40.0, "{}", crossings);
+    }
+
+    #[test]
+    fn octave_down() {
+        let samples = shift(-12.0);
+        assert!(
+            (samples.len() as i32 - 48000).abs() < 2400,
+            "{}",
+            samples.len()
+        );
+        let crossings = crossings_per_second(&samples, 48000);
+        assert!((crossings - 440.0).abs() < 20.0, "{}", crossings);
+    }
+
+    #[test]
+    fn shift_change_while_playing() {
+        let mut source = SineWave::new(440.0)
+            .take_duration(Duration::from_secs(1))
+            .pitch_shift(7.0);
+        let first: Vec<f32> = source.by_ref().take(24000).collect();
+        source.set_factor(1.0);
+        let rest: Vec<f32> = source.collect();
+        let len = first.len() + rest.len();
+        assert!((len as i32 - 48000).abs() < 2400, "{}", len);
+        assert!((crossings_per_second(&rest, 48000) - 880.0).abs() < 40.0);
+    }
+
+    #[test]
+    fn stereo() {
+        let left = SineWave::new(440.0).take_duration(Duration::from_millis(500));
+        let samples: Vec<f32> = left.flat_map(|s| vec![s, 0.0]).collect();
+        let output: Vec<f32> = SamplesBuffer::new(2, 48000, samples)
+            .pitch_shift(5.0)
+            .collect();
+        assert_eq!(output.len() % 2, 0);
+        assert!(output.iter().skip(1).step_by(2).all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn invalid_factors() {
+        for &factor in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let source = || {
+                let mut source = SamplesBuffer::new(1, 48000, vec![0.5f32; 48000]).pitch_shift(7.0);
+                source.set_factor(factor);
+                source
+            };
+            assert_eq!(source().total_duration(), Some(Duration::from_secs(1)));
+            assert_eq!(source().count(), 48000, "{}", factor);
+            let mut seeked = source();
+            assert!(seeked.try_seek(Duration::from_millis(500)).is_ok());
+            assert_eq!(seeked.count(), 24000, "{}", factor);
+        }
+    }
+
+    #[test]
+    fn invalid_semitones() {
+        let source = SamplesBuffer::new(1, 48000, vec![0.5f32; 48000]).pitch_shift(f32::NAN);
+        assert_eq!(source.count(), 48000);
+    }
+}
@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+
+/// Internal function that builds a `TrackPosition` object.
+pub fn track_position<I>(source: I) -> TrackPosition<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    TrackPosition {
+        input: source,
+        samples_counted: 0,
+        offset_duration: 0.0,
+        current_frame_sample_rate: 0,
+        current_frame_channels: 0,
+        current_frame_len: None,
+    }
+}
+
+/// Keeps track of the elapsed time of the source.
+#[derive(Clone, Debug)]
+pub struct TrackPosition<I> {
+    input: I,
+    samples_counted: usize,
+    // Duration of all the frames that have been completely played.
+    offset_duration: f64,
+    current_frame_sample_rate: u32,
+    current_frame_channels: u16,
+    current_frame_len: Option<usize>,
+}
+
+impl<I> TrackPosition<I> {
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> TrackPosition<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    /// Returns the position of the source, that is the duration of the samples that have been
+    /// returned so far, or the position that was seeked to plus the duration of the samples
+    /// returned after the seek.
+    #[inline]
+    pub fn get_pos(&self) -> Duration {
+        let seconds = if self.current_frame_channels == 0 || self.current_frame_sample_rate == 0 {
+            0.0
+        } else {
+            self.samples_counted as f64
+                / self.current_frame_sample_rate as f64
+                / self.current_frame_channels as f64
+        };
+        Duration::from_secs_f64(self.offset_duration + seconds)
+    }
+
+    #[inline]
+    fn set_current_frame(&mut self) {
+        self.current_frame_len = self.input.current_frame_len();
+        self.current_frame_sample_rate = self.input.sample_rate();
+        self.current_frame_channels = self.input.channels();
+    }
+}
+
+impl<I> Iterator for TrackPosition<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        // Only happens on the very first call.
+        if self.current_frame_channels == 0 {
+            self.set_current_frame();
+        }
+
+        let item = self.input.next()?;
+        self.samples_counted += 1;
+
+        // At the end of a frame, fold its duration into `offset_duration` because the next
+        // frame may have a different sample rate or channel count.
+        if Some(self.samples_counted) == self.current_frame_len {
+            self.offset_duration += self.samples_counted as f64
+                / self.current_frame_sample_rate as f64
+                / self.current_frame_channels as f64;
+            self.samples_counted = 0;
+            self.set_current_frame();
+        }
+
+        Some(item)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for TrackPosition<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.offset_duration = pos.as_secs_f64();
+        self.samples_counted = 0;
+        self.set_current_frame();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::buffer::SamplesBuffer;
+    use crate::Source;
+
+    #[test]
+    fn test_position() {
+        let mut source =
+            SamplesBuffer::new(1, 1, vec![10i16, -10, 10, -10, 20, -20]).track_position();
+
+        assert_eq!(source.get_pos(), Duration::from_secs(0));
+        source.next();
+        assert_eq!(source.get_pos(), Duration::from_secs(1));
+        source.next();
+        source.next();
+        assert_eq!(source.get_pos(), Duration::from_secs(3));
+
+        source.try_seek(Duration::from_secs(1)).unwrap();
+        assert_eq!(source.get_pos(), Duration::from_secs(1));
+        assert_eq!(source.next(), Some(-10));
+        assert_eq!(source.get_pos(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_position_stereo() {
+        let mut source = SamplesBuffer::new(2, 2, vec![1i16, 1, 2, 2, 3, 3]).track_position();
+
+        source.next();
+        source.next();
+        assert_eq!(source.get_pos(), Duration::from_millis(500));
+    }
+}
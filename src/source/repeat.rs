@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use crate::source::buffered::Buffered;
-
+use crate::source::ChannelLayout;
 use crate::{Sample, Source};
 
 /// Internal function that builds a `Repeat` object.
@@ -72,6 +72,14 @@ where
         }
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        match self.inner.current_frame_len() {
+            Some(0) => self.next.channel_layout(),
+            _ => self.inner.channel_layout(),
+        }
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         match self.inner.current_frame_len() {
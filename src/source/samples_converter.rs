@@ -1,6 +1,7 @@
 use std::marker::PhantomData;
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 use cpal::Sample as CpalSample;
 
@@ -86,6 +87,11 @@ where
         self.inner.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.inner.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.inner.sample_rate()
@@ -95,4 +101,9 @@ where
     fn total_duration(&self) -> Option<Duration> {
         self.inner.total_duration()
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
 }
@@ -1,6 +1,7 @@
 use std::f32::consts::PI;
 use std::time::Duration;
 
+use crate::source::SeekError;
 use crate::Source;
 
 /// An infinite source that produces a sine.
@@ -55,4 +56,10 @@ impl Source for SineWave {
     fn total_duration(&self) -> Option<Duration> {
         None
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.num_sample = (pos.as_secs_f64() * self.sample_rate() as f64) as usize;
+        Ok(())
+    }
 }
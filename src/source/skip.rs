@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 const NS_PER_SECOND: u128 = 1_000_000_000;
@@ -145,6 +146,11 @@ where
         self.input.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.input.sample_rate()
@@ -157,6 +163,11 @@ where
                 .unwrap_or_else(|| Duration::from_secs(0))
         })
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos + self.skipped_duration)
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use crate::source::time_stretch::scale_duration;
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 /// Internal function that builds a `Speed` object.
@@ -84,6 +86,11 @@ where
         self.input.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         (self.input.sample_rate() as f32 * self.factor) as u32
@@ -103,4 +110,13 @@ where
             None
         }
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // `pos` is expressed in played time, which runs `factor` times faster than the input.
+        let pos = scale_duration(pos, self.factor).ok_or(SeekError::NotSupported {
+            underlying_source: std::any::type_name::<Self>(),
+        })?;
+        self.input.try_seek(pos)
+    }
 }
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 /// Internal function that builds a `TakeDuration` object.
@@ -159,6 +160,11 @@ where
         self.input.channels()
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.input.sample_rate()
@@ -176,4 +182,16 @@ where
             None
         }
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.remaining_duration = self
+            .requested_duration
+            .checked_sub(pos)
+            .unwrap_or_else(|| Duration::from_secs(0));
+        self.current_frame_len = self.input.current_frame_len();
+        self.duration_per_sample = Self::get_duration_per_sample(&self.input);
+        Ok(())
+    }
 }
@@ -0,0 +1,506 @@
+use std::time::Duration;
+
+use crate::source::{ChannelLayout, SeekError};
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Length of the segments of the input that are joined together, in milliseconds.
+const SEGMENT_MS: u32 = 40;
+
+/// Length of the cross-fade between two segments, in milliseconds.
+const OVERLAP_MS: u32 = 10;
+
+/// Range in which the start of a segment is searched, in milliseconds.
+const SEEK_WINDOW_MS: u32 = 15;
+
+/// Internal function that builds a `TimeStretch` object.
+pub fn time_stretch<I>(input: I, rate: f32) -> TimeStretch<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let channels = input.channels().max(1);
+    let sample_rate = input.sample_rate();
+    let mut stretch = TimeStretch {
+        input,
+        rate: valid_rate(rate),
+        stretching: false,
+        channels,
+        sample_rate,
+        input_buffer: Vec::new(),
+        pending_skip: 0,
+        skip_remainder: 0.0,
+        overlap: Vec::new(),
+        continuation: 0,
+        output: Vec::new(),
+        output_pos: 0,
+        channel: 0,
+    };
+    // Allocated now rather than by the thread playing the sound.
+    stretch.reserve_buffers();
+    stretch
+}
+
+/// Filter that changes the tempo of the sound without changing its pitch.
+///
+/// This uses WSOLA (waveform similarity overlap-add): segments of the input are cross-faded
+/// together, each one starting where it best matches the end of the previous one, and are
+/// taken further apart or closer together than they are played to change the tempo. At a rate
+/// of 1.0 the samples are passed through unchanged.
+#[derive(Clone, Debug)]
+pub struct TimeStretch<I> {
+    input: I,
+    rate: f32,
+    // Whether the samples are stretched or passed through.
+    stretching: bool,
+    // Format of the stretched samples.
+    channels: u16,
+    sample_rate: u32,
+    // Frames of the input that haven't been used yet, interleaved.
+    input_buffer: Vec<f32>,
+    // Number of frames of the input to drop before filling `input_buffer`.
+    pending_skip: usize,
+    skip_remainder: f64,
+    // End of the last segment, cross-faded with the start of the next one.
+    overlap: Vec<f32>,
+    // Frame of `input_buffer` that follows `overlap` in the input. Negative if it was dropped.
+    continuation: isize,
+    // Stretched samples that haven't been returned yet.
+    output: Vec<f32>,
+    output_pos: usize,
+    // Channel of the next sample, when the samples are passed through.
+    channel: usize,
+}
+
+impl<I> TimeStretch<I> {
+    /// Modifies the rate. `2.0` plays the sound twice as fast, with the same pitch.
+    ///
+    /// The rate must be positive and finite; other values are treated as `1.0`.
+    #[inline]
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = valid_rate(rate);
+    }
+
+    /// Returns the rate.
+    #[inline]
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> TimeStretch<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    /// Returns the lengths of a segment, of the overlap and of the seek window, in frames.
+    #[inline]
+    fn lengths(&self) -> (usize, usize, usize) {
+        let frames = |ms: u32| (self.sample_rate as u64 * ms as u64 / 1000) as usize;
+        // Very low sample rates still need room for two overlaps in a segment.
+        let segment = frames(SEGMENT_MS).max(3);
+        let overlap = frames(OVERLAP_MS).min(segment / 3);
+        (segment, overlap, frames(SEEK_WINDOW_MS).max(1))
+    }
+
+    /// Makes room in the buffers for the current format, so that stretching doesn't allocate.
+    fn reserve_buffers(&mut self) {
+        let channels = self.channels as usize;
+        let (segment, overlap, seek_window) = self.lengths();
+        let reserve = |buffer: &mut Vec<f32>, frames: usize| {
+            buffer.reserve((frames * channels).saturating_sub(buffer.len()));
+        };
+        reserve(&mut self.input_buffer, seek_window + segment);
+        reserve(&mut self.overlap, overlap);
+        reserve(&mut self.output, overlap + seek_window + segment);
+    }
+
+    /// Starts stretching the samples of the input, which must be at a frame boundary.
+    fn start(&mut self) {
+        self.stretching = true;
+        self.channels = self.input.channels().max(1);
+        self.sample_rate = self.input.sample_rate();
+        self.reserve_buffers();
+        self.input_buffer.clear();
+        self.pending_skip = 0;
+        self.skip_remainder = 0.0;
+        self.overlap.clear();
+        self.continuation = 0;
+    }
+
+    /// Reads whole frames of the input until `input_buffer` contains `frames` frames. Stops
+    /// early if the input ends or changes its format.
+    fn fill(&mut self, frames: usize) {
+        let channels = self.channels as usize;
+        while self.input_buffer.len() < frames * channels {
+            if self.input.channels().max(1) != self.channels
+                || self.input.sample_rate() != self.sample_rate
+            {
+                return;
+            }
+            for _ in 0..channels {
+                match self.input.next() {
+                    Some(sample) if self.pending_skip == 0 => {
+                        self.input_buffer.push(sample.to_f32())
+                    }
+                    Some(_) => (),
+                    None => {
+                        // Drop the incomplete frame.
+                        let len = self.input_buffer.len() / channels * channels;
+                        self.input_buffer.truncate(len);
+                        return;
+                    }
+                }
+            }
+            self.pending_skip = self.pending_skip.saturating_sub(1);
+        }
+    }
+
+    /// Returns the frame of `input_buffer`, up to `seek_window`, where the next segment starts.
+    fn best_offset(&self, seek_window: usize) -> usize {
+        let mut best_offset = 0;
+        let mut best_score = f32::MIN;
+        for offset in 0..seek_window {
+            let start = offset * self.channels as usize;
+            let candidate = &self.input_buffer[start..start + self.overlap.len()];
+            let (correlation, energy) = self
+                .overlap
+                .iter()
+                .zip(candidate)
+                .fold((0.0, 0.0), |(c, e), (&a, &b)| (c + a * b, e + b * b));
+            let score = correlation / (energy.sqrt() + 1e-6);
+            if score > best_score {
+                best_score = score;
+                best_offset = offset;
+            }
+        }
+        best_offset
+    }
+
+    /// Fills `output` with the next stretched samples. Stops stretching if the rate went back to
+    /// 1.0, or if the input ended or changed its format, in which case the samples that were
+    /// read ahead are returned as they are.
+    fn process(&mut self) {
+        self.output.clear();
+        self.output_pos = 0;
+
+        let channels = self.channels as usize;
+        let (segment, overlap, seek_window) = self.lengths();
+        if self.rate != 1.0 {
+            self.fill(seek_window + segment);
+        }
+        let available = self.input_buffer.len() / channels;
+
+        if self.rate == 1.0 || available < segment {
+            self.output.append(&mut self.overlap);
+            if self.continuation >= 0 && self.pending_skip == 0 {
+                let start = (self.continuation as usize).min(available) * channels;
+                self.output.extend_from_slice(&self.input_buffer[start..]);
+            }
+            self.input_buffer.clear();
+            self.stretching = false;
+            return;
+        }
+
+        let offset = if self.overlap.is_empty() {
+            0
+        } else {
+            // Near the end of the input, the window is reduced to the frames that are left.
+            self.best_offset(seek_window.min(available - segment + 1))
+        };
+        let start = offset * channels;
+        let end = (offset + segment) * channels;
+        let overlap_end = (offset + segment - overlap) * channels;
+
+        if self.overlap.is_empty() {
+            self.output
+                .extend_from_slice(&self.input_buffer[start..overlap_end]);
+        } else {
+            for (i, &previous) in self.overlap.iter().enumerate() {
+                let fade = (i / channels) as f32 / overlap as f32;
+                let next = self.input_buffer[start + i];
+                self.output.push(previous * (1.0 - fade) + next * fade);
+            }
+            self.output
+                .extend_from_slice(&self.input_buffer[start + overlap * channels..overlap_end]);
+        }
+        self.overlap.clear();
+        self.overlap
+            .extend_from_slice(&self.input_buffer[overlap_end..end]);
+        self.continuation = (offset + segment) as isize;
+
+        // Move forward in the input by the length of the output, multiplied by the rate.
+        let skip = self.rate as f64 * (segment - overlap) as f64 + self.skip_remainder;
+        self.skip_remainder = skip.fract();
+        let skip = skip as usize;
+        let dropped = skip.min(available);
+        self.input_buffer.drain(..dropped * channels);
+        self.pending_skip = skip - dropped;
+        self.continuation -= skip as isize;
+    }
+
+    /// Returns the next sample of the input, unchanged.
+    #[inline]
+    fn next_passthrough(&mut self) -> Option<I::Item> {
+        let channels = self.input.channels() as usize;
+        let last_in_frame = self.input.current_frame_len() == Some(1);
+
+        let sample = self.input.next()?;
+
+        self.channel += 1;
+        if last_in_frame || self.channel >= channels {
+            self.channel = 0;
+        }
+        Some(sample)
+    }
+
+    #[inline]
+    fn has_output(&self) -> bool {
+        self.output_pos < self.output.len()
+    }
+}
+
+impl<I> Iterator for TimeStretch<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        if !self.has_output() {
+            // Samples can only start being stretched at a frame boundary.
+            if !self.stretching && (self.rate == 1.0 || self.channel != 0) {
+                return self.next_passthrough();
+            }
+            if !self.stretching {
+                self.start();
+            }
+            self.process();
+            if !self.has_output() {
+                return self.next_passthrough();
+            }
+        }
+
+        let sample = self.output[self.output_pos];
+        self.output_pos += 1;
+        // Stretch the next samples now, so that their format can be reported.
+        if !self.has_output() && self.stretching {
+            self.process();
+        }
+        Some(CpalSample::from(&sample))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.has_output() || self.stretching {
+            (self.output.len() - self.output_pos, None)
+        } else {
+            self.input.size_hint()
+        }
+    }
+}
+
+impl<I> Source for TimeStretch<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.has_output() {
+            Some(self.output.len() - self.output_pos)
+        } else {
+            self.input.current_frame_len()
+        }
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        if self.has_output() {
+            self.channels
+        } else {
+            self.input.channels()
+        }
+    }
+
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.input.channel_layout()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        if self.has_output() {
+            self.sample_rate
+        } else {
+            self.input.sample_rate()
+        }
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input
+            .total_duration()
+            .and_then(|duration| scale_duration(duration, 1.0 / self.rate))
+    }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // `pos` is expressed in played time, which runs `rate` times faster than the input.
+        let pos = scale_duration(pos, self.rate).ok_or(SeekError::NotSupported {
+            underlying_source: std::any::type_name::<Self>(),
+        })?;
+        self.input.try_seek(pos)?;
+        self.stretching = false;
+        self.output.clear();
+        self.output_pos = 0;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// Replaces the rates that would never or instantly end the sound with `1.0`.
+#[inline]
+fn valid_rate(rate: f32) -> f32 {
+    if rate > 0.0 && rate.is_finite() {
+        rate
+    } else {
+        1.0
+    }
+}
+
+/// Multiplies `duration` by `factor`. Returns `None` if the result is negative or can't be
+/// represented, for example if `factor` is infinite or NaN.
+#[inline]
+pub(crate) fn scale_duration(duration: Duration, factor: f32) -> Option<Duration> {
+    let secs = duration.as_secs_f64() * factor as f64;
+    if secs >= 0.0 && secs < u64::MAX as f64 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::buffer::SamplesBuffer;
+    use crate::source::{SineWave, Source};
+    use cpal::Sample as CpalSample;
+
+    /// Returns the number of times the samples change sign, per second.
+    fn crossings_per_second(samples: &[f32], sample_rate: u32) -> f32 {
+        let crossings = samples
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+        crossings as f32 * sample_rate as f32 / samples.len() as f32
+    }
+
+    fn stretch(rate: f32) -> Vec<f32> {
+        SineWave::new(440.0)
+            .take_duration(Duration::from_secs(1))
+            .time_stretch(rate)
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_at_normal_rate() {
+        let samples: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        let source = SamplesBuffer::new(2, 44100, samples.clone()).time_stretch(1.0);
+        assert_eq!(source.collect::<Vec<_>>(), samples);
+    }
+
+    #[test]
+    fn faster_with_same_pitch() {
+        let samples = stretch(2.0);
+        assert!(
+            (samples.len() as i32 - 24000).abs() < 2400,
+            "{}",
+            samples.len()
+        );
+        let crossings = crossings_per_second(&samples, 48000);
+        assert!((crossings - 880.0).abs() < 20.0, "{}", crossings);
+    }
+
+    #[test]
+    fn slower_with_same_pitch() {
+        let samples = stretch(0.5);
+        assert!(
+            (samples.len() as i32 - 96000).abs() < 2400,
+            "{}",
+            samples.len()
+        );
+        let crossings = crossings_per_second(&samples, 48000);
+        assert!((crossings - 880.0).abs() < 20.0, "{}", crossings);
+    }
+
+    #[test]
+    fn channels_are_kept_apart() {
+        let left = SineWave::new(440.0).take_duration(Duration::from_millis(500));
+        let samples: Vec<i16> = left.flat_map(|s| vec![s.to_i16(), 0]).collect();
+        let output: Vec<i16> = SamplesBuffer::new(2, 48000, samples)
+            .time_stretch(1.5)
+            .collect();
+        assert_eq!(output.len() % 2, 0);
+        assert!(output.iter().step_by(2).any(|&s| s != 0));
+        assert!(output.iter().skip(1).step_by(2).all(|&s| s == 0));
+    }
+
+    #[test]
+    fn rate_change_while_playing() {
+        let mut source = SineWave::new(440.0)
+            .take_duration(Duration::from_secs(1))
+            .time_stretch(2.0);
+        let first: Vec<f32> = source.by_ref().take(12000).collect();
+        source.set_rate(1.0);
+        let rest: Vec<f32> = source.collect();
+        // Half a second of the input was played in a quarter of a second, the rest is played
+        // at normal speed.
+        let len = first.len() + rest.len();
+        assert!((len as i32 - 36000).abs() < 2400, "{}", len);
+    }
+
+    #[test]
+    fn invalid_rates() {
+        let mut source = SamplesBuffer::new(1, 48000, vec![0.5f32; 48000]).time_stretch(2.0);
+        assert_eq!(source.total_duration(), Some(Duration::from_millis(500)));
+        for &rate in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            source.set_rate(rate);
+            assert_eq!(source.rate(), 1.0, "{}", rate);
+            assert_eq!(
+                source.total_duration(),
+                Some(Duration::from_secs(1)),
+                "{}",
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn zero_rate_ends() {
+        let source = SamplesBuffer::new(1, 48000, vec![0.5f32; 48000]).time_stretch(0.0);
+        assert_eq!(source.count(), 48000);
+    }
+}
@@ -1,7 +1,10 @@
 use std::cmp;
 use std::time::Duration;
 
-use crate::conversions::{ChannelCountConverter, DataConverter, SampleRateConverter};
+use crate::conversions::{
+    ChannelCountConverter, ChannelMixing, DataConverter, ResampleQuality, SampleRateConverter,
+};
+use crate::source::{ChannelLayout, SeekError};
 use crate::{Sample, Source};
 
 /// An iterator that reads from a `Source` and converts the samples to a specific rate and
@@ -19,6 +22,8 @@ where
     inner: Option<DataConverter<ChannelCountConverter<SampleRateConverter<Take<I>>>, D>>,
     target_channels: u16,
     target_sample_rate: u32,
+    quality: ResampleQuality,
+    mixing: ChannelMixing,
     total_duration: Option<Duration>,
 }
 
@@ -33,14 +38,57 @@ where
         input: I,
         target_channels: u16,
         target_sample_rate: u32,
+    ) -> UniformSourceIterator<I, D> {
+        UniformSourceIterator::with_quality(
+            input,
+            target_channels,
+            target_sample_rate,
+            ResampleQuality::default(),
+        )
+    }
+
+    /// Builds a new `UniformSourceIterator` that resamples the input with the given quality.
+    #[inline]
+    pub fn with_quality(
+        input: I,
+        target_channels: u16,
+        target_sample_rate: u32,
+        quality: ResampleQuality,
+    ) -> UniformSourceIterator<I, D> {
+        UniformSourceIterator::with_options(
+            input,
+            target_channels,
+            target_sample_rate,
+            quality,
+            ChannelMixing::default(),
+        )
+    }
+
+    /// Builds a new `UniformSourceIterator` that resamples the input with the given quality, and
+    /// converts its channels with the given mixing.
+    #[inline]
+    pub fn with_options(
+        input: I,
+        target_channels: u16,
+        target_sample_rate: u32,
+        quality: ResampleQuality,
+        mixing: ChannelMixing,
     ) -> UniformSourceIterator<I, D> {
         let total_duration = input.total_duration();
-        let input = UniformSourceIterator::bootstrap(input, target_channels, target_sample_rate);
+        let input = UniformSourceIterator::bootstrap(
+            input,
+            target_channels,
+            target_sample_rate,
+            quality,
+            &mixing,
+        );
 
         UniformSourceIterator {
             inner: Some(input),
             target_channels,
             target_sample_rate,
+            quality,
+            mixing,
             total_duration,
         }
     }
@@ -50,27 +98,74 @@ where
         input: I,
         target_channels: u16,
         target_sample_rate: u32,
+        quality: ResampleQuality,
+        mixing: &ChannelMixing,
     ) -> DataConverter<ChannelCountConverter<SampleRateConverter<Take<I>>>, D> {
-        // Limit the frame length to something reasonable
-        let frame_len = input.current_frame_len().map(|x| x.min(32768));
-
+        let frame_len = frame_len(&input);
         let from_channels = input.channels();
+        let from_layout = channel_layout(&input);
         let from_sample_rate = input.sample_rate();
 
         let input = Take {
             iter: input,
             n: frame_len,
+            channels: from_channels,
+            layout: from_layout,
+            sample_rate: from_sample_rate,
         };
-        let input = SampleRateConverter::new(
+        let input = SampleRateConverter::with_quality(
             input,
             cpal::SampleRate(from_sample_rate),
             cpal::SampleRate(target_sample_rate),
             from_channels,
+            quality,
         );
-        let input = ChannelCountConverter::new(input, from_channels, target_channels);
+        let input = match (from_layout, ChannelLayout::default_for(target_channels)) {
+            (Some(from), Some(to)) => ChannelCountConverter::with_layouts(input, from, to, mixing),
+            _ => ChannelCountConverter::with_mixing(input, from_channels, target_channels, mixing),
+        };
 
         DataConverter::new(input)
     }
+
+    /// Adapts the conversion chain to the format of the next frame of the input, and starts
+    /// converting again from its first sample.
+    ///
+    /// The chain is updated in place rather than rebuilt, so that the thread playing the sound
+    /// doesn't allocate, unless the new format needs another mixing matrix or other sinc
+    /// filters. `Sink` avoids this by converting each sound when it is appended.
+    fn restart(&mut self) {
+        let channel_converter = self.inner.as_mut().unwrap().inner_mut();
+        let rate_converter = channel_converter.inner_mut();
+        let take = rate_converter.inner_mut();
+
+        let previous = (take.channels, take.layout);
+        take.n = frame_len(&take.iter);
+        take.channels = take.iter.channels();
+        take.layout = channel_layout(&take.iter);
+        take.sample_rate = take.iter.sample_rate();
+        let (from_channels, from_layout) = (take.channels, take.layout);
+        let from_sample_rate = take.sample_rate;
+
+        rate_converter.restart(
+            cpal::SampleRate(from_sample_rate),
+            cpal::SampleRate(self.target_sample_rate),
+            from_channels,
+            self.quality,
+        );
+        if (from_channels, from_layout) != previous {
+            let matrix = match (
+                from_layout,
+                ChannelLayout::default_for(self.target_channels),
+            ) {
+                (Some(from), Some(to)) => self.mixing.layout_matrix(from, to),
+                _ => self.mixing.matrix(from_channels, self.target_channels),
+            };
+            channel_converter.set_input_channels(from_channels, matrix);
+        } else {
+            channel_converter.reset();
+        }
+    }
 }
 
 impl<I, D> Iterator for UniformSourceIterator<I, D>
@@ -87,21 +182,8 @@ where
             return Some(value);
         }
 
-        let input = self
-            .inner
-            .take()
-            .unwrap()
-            .into_inner()
-            .into_inner()
-            .into_inner()
-            .iter;
-
-        let mut input =
-            UniformSourceIterator::bootstrap(input, self.target_channels, self.target_sample_rate);
-
-        let value = input.next();
-        self.inner = Some(input);
-        value
+        self.restart();
+        self.inner.as_mut().unwrap().next()
     }
 
     #[inline]
@@ -126,6 +208,11 @@ where
         self.target_channels
     }
 
+    #[inline]
+    fn channel_layout(&self) -> Option<ChannelLayout> {
+        ChannelLayout::default_for(self.target_channels)
+    }
+
     #[inline]
     fn sample_rate(&self) -> u32 {
         self.target_sample_rate
@@ -135,51 +222,101 @@ where
     fn total_duration(&self) -> Option<Duration> {
         self.total_duration
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let input = &mut self
+            .inner
+            .as_mut()
+            .unwrap()
+            .inner_mut()
+            .inner_mut()
+            .inner_mut()
+            .iter;
+
+        // Restart the conversion chain so that no sample from before the seek is kept around.
+        let result = input.try_seek(pos);
+        self.restart();
+        result
+    }
+}
+
+#[inline]
+fn frame_len<I>(input: &I) -> Option<usize>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    // Limit the frame length to something reasonable
+    input.current_frame_len().map(|x| x.min(32768))
 }
 
+/// Returns the layout of the input, or the usual one for its channels if it is unknown or
+/// doesn't match them.
+///
+/// Both are mixed the same way, so a sound without a layout followed by one with the usual
+/// layout doesn't need a new matrix.
+#[inline]
+fn channel_layout<I>(input: &I) -> Option<ChannelLayout>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let channels = input.channels();
+    input
+        .channel_layout()
+        .filter(|layout| layout.channels() == channels)
+        .or_else(|| ChannelLayout::default_for(channels))
+}
+
+/// Reads the frames of the input as long as their format is the one the conversion chain was
+/// built for.
 #[derive(Clone, Debug)]
 struct Take<I> {
     iter: I,
     n: Option<usize>,
+    channels: u16,
+    layout: Option<ChannelLayout>,
+    sample_rate: u32,
 }
 
 impl<I> Iterator for Take<I>
 where
-    I: Iterator,
+    I: Source,
+    I::Item: Sample,
 {
     type Item = <I as Iterator>::Item;
 
     #[inline]
     fn next(&mut self) -> Option<<I as Iterator>::Item> {
-        if let Some(n) = &mut self.n {
-            if *n != 0 {
-                *n -= 1;
-                self.iter.next()
-            } else {
-                None
+        if self.n == Some(0) {
+            // The frame has ended. If the next one has the same format, keep reading instead of
+            // rebuilding the conversion chain, which would reset the sample rate converter.
+            if self.iter.channels() != self.channels
+                || channel_layout(&self.iter) != self.layout
+                || self.iter.sample_rate() != self.sample_rate
+            {
+                return None;
+            }
+            self.n = frame_len(&self.iter);
+            if self.n == Some(0) {
+                return None;
             }
-        } else {
-            self.iter.next()
         }
+
+        if let Some(n) = &mut self.n {
+            *n -= 1;
+        }
+        self.iter.next()
     }
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        if let Some(n) = self.n {
-            let (lower, upper) = self.iter.size_hint();
-
-            let lower = cmp::min(lower, n);
-
-            let upper = match upper {
-                Some(x) if x < n => Some(x),
-                _ => Some(n),
-            };
-
-            (lower, upper)
-        } else {
-            self.iter.size_hint()
+        // The following frames may be read as well, so only the lower bound is limited.
+        let (lower, upper) = self.iter.size_hint();
+        match self.n {
+            Some(n) => (cmp::min(lower, n), upper),
+            None => (lower, upper),
         }
     }
 }
-
-impl<I> ExactSizeIterator for Take<I> where I: ExactSizeIterator {}
@@ -2,7 +2,7 @@ use std::f32;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::source::Spatial;
+use crate::source::{SeekError, Spatial};
 use crate::stream::{OutputStreamHandle, PlayError};
 use crate::{Sample, Sink, Source};
 
@@ -66,7 +66,11 @@ impl SpatialSink {
             pos_lock.right_ear,
         )
         .periodic_access(Duration::from_millis(10), move |i| {
-            let pos = positions.lock().unwrap();
+            // The positions are updated at the next access if the lock is held.
+            let pos = match positions.try_lock() {
+                Ok(pos) => pos,
+                Err(_) => return,
+            };
             i.set_positions(pos.emitter_position, pos.left_ear, pos.right_ear);
         });
         self.sink.append(source);
@@ -108,6 +112,39 @@ impl SpatialSink {
         self.sink.set_speed(value)
     }
 
+    /// Gets the playback rate of the sound.
+    ///
+    /// The value `1.0` is the "normal" rate. Unlike the speed, the playback rate doesn't change
+    /// the pitch of the sound.
+    #[inline]
+    pub fn playback_rate(&self) -> f32 {
+        self.sink.playback_rate()
+    }
+
+    /// Changes the playback rate of the sound, without changing its pitch.
+    ///
+    /// The value `1.0` is the "normal" rate, `2.0` plays the sound twice as fast.
+    #[inline]
+    pub fn set_playback_rate(&self, value: f32) {
+        self.sink.set_playback_rate(value)
+    }
+
+    /// Attempts to seek to a given position in the current sound.
+    ///
+    /// See `Sink::try_seek` for details.
+    #[inline]
+    pub fn try_seek(&self, pos: Duration) -> Result<(), SeekError> {
+        self.sink.try_seek(pos)
+    }
+
+    /// Returns the position of the sound that is currently playing.
+    ///
+    /// See `Sink::get_pos` for details.
+    #[inline]
+    pub fn get_pos(&self) -> Duration {
+        self.sink.get_pos()
+    }
+
     /// Resumes playback of a paused sound.
     ///
     /// No effect if not paused.
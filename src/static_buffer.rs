@@ -13,6 +13,7 @@
 use std::slice::Iter as SliceIter;
 use std::time::Duration;
 
+use crate::source::SeekError;
 use crate::{Sample, Source};
 
 /// A buffer of samples treated as a source.
@@ -22,6 +23,7 @@ where
     S: 'static,
 {
     data: SliceIter<'static, S>,
+    full_data: &'static [S],
     channels: u16,
     sample_rate: u32,
     duration: Duration,
@@ -54,6 +56,7 @@ where
 
         StaticSamplesBuffer {
             data: data.iter(),
+            full_data: data,
             channels,
             sample_rate,
             duration,
@@ -84,6 +87,16 @@ where
     fn total_duration(&self) -> Option<Duration> {
         Some(self.duration)
     }
+
+    #[inline]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let frame = (pos.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize;
+        let offset = frame
+            .saturating_mul(self.channels as usize)
+            .min(self.full_data.len());
+        self.data = self.full_data[offset..].iter();
+        Ok(())
+    }
 }
 
 impl<S> Iterator for StaticSamplesBuffer<S>
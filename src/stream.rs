@@ -1,12 +1,22 @@
+#[cfg(feature = "wav")]
+use std::io;
 use std::io::{Read, Seek};
 use std::marker::Sync;
-use std::sync::{Arc, Weak};
+#[cfg(feature = "wav")]
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
 use std::{error, fmt};
 
+#[cfg(feature = "wav")]
+use crate::buffer::SamplesBuffer;
 use crate::decoder;
-use crate::dynamic_mixer::{self, DynamicMixerController};
+use crate::dynamic_mixer::{self, DynamicMixer, DynamicMixerController, SoundHandle};
 use crate::sink::Sink;
 use crate::source::Source;
+use crate::{ChannelMixing, ResampleQuality};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Sample;
 
@@ -14,10 +24,41 @@ use cpal::Sample;
 ///
 /// If this is dropped playback will end & attached `OutputStreamHandle`s will no longer work.
 pub struct OutputStream {
-    mixer: Arc<DynamicMixerController<f32>>,
+    shared: Arc<StreamShared>,
     _stream: cpal::Stream,
 }
 
+/// State shared by an `OutputStream` and the callbacks of its cpal streams.
+pub(crate) struct StreamShared {
+    controller: Arc<DynamicMixerController<f32>>,
+    // Locked by the data callback, so that the mixer can be moved to a new stream by the device
+    // recovery.
+    mixer: Mutex<DynamicMixer<f32>>,
+    config: cpal::StreamConfig,
+    // Changed by the device recovery.
+    device: Mutex<ActiveDevice>,
+    error_callback: Mutex<Option<ErrorCallback>>,
+    recovery: Mutex<Option<Sender<RecoveryEvent>>>,
+}
+
+/// Device that an `OutputStream` plays on.
+struct ActiveDevice {
+    name: String,
+    sample_format: cpal::SampleFormat,
+}
+
+/// Function called when an error occurs on an `OutputStream`.
+pub(crate) type ErrorCallback = Box<dyn FnMut(cpal::StreamError) + Send>;
+
+/// Message sent to the device recovery thread.
+enum RecoveryEvent {
+    DeviceLost,
+    Stop,
+}
+
+/// Interval between two attempts to open the new default device after the device was lost.
+const RECOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 /// More flexible handle to a `OutputStream` that provides playback.
 #[derive(Clone)]
 pub struct OutputStreamHandle {
@@ -29,18 +70,58 @@ impl OutputStream {
     pub fn try_from_device(
         device: &cpal::Device,
     ) -> Result<(Self, OutputStreamHandle), StreamError> {
-        let (mixer, _stream) = device.try_new_output_stream()?;
+        let (shared, stream) = device.try_new_output_stream()?;
+        Self::start(shared, stream)
+    }
+
+    /// Returns a new stream & handle using the given output device and stream config.
+    ///
+    /// The samples are sent to the device in the sample format of its default config. Use
+    /// `OutputStreamBuilder` to choose it as well. Fails if the device doesn't support the config.
+    pub fn try_from_device_config(
+        device: &cpal::Device,
+        config: cpal::StreamConfig,
+    ) -> Result<(Self, OutputStreamHandle), StreamError> {
+        let sample_format = device.default_output_config()?.sample_format();
+        Self::try_from_device_config_and_format(device, &config, sample_format)
+    }
+
+    fn try_from_device_config_and_format(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+    ) -> Result<(Self, OutputStreamHandle), StreamError> {
+        let (shared, stream) = device.new_output_stream_with_config(config, sample_format)?;
+        Self::start(shared, stream)
+    }
+
+    fn start(
+        shared: Arc<StreamShared>,
+        _stream: cpal::Stream,
+    ) -> Result<(Self, OutputStreamHandle), StreamError> {
         _stream.play()?;
-        let out = Self { mixer, _stream };
         let handle = OutputStreamHandle {
-            mixer: Arc::downgrade(&out.mixer),
+            mixer: Arc::downgrade(&shared.controller),
         };
+        let out = Self { shared, _stream };
         Ok((out, handle))
     }
 
+    /// Returns a new stream & handle using the output device with the given name.
+    ///
+    /// The names are the ones returned by `OutputDeviceInfo::name`.
+    pub fn try_from_device_name(name: &str) -> Result<(Self, OutputStreamHandle), StreamError> {
+        let device = cpal::default_host()
+            .output_devices()?
+            .find(|d| d.name().ok().as_deref() == Some(name))
+            .ok_or(StreamError::NoDevice)?;
+        Self::try_from_device(&device)
+    }
+
     /// Return a new stream & handle using the default output device.
     ///
-    /// On failure will fallback to trying any non-default output devices.
+    /// On failure will fallback to trying any non-default output devices. Use `device_name` and
+    /// `config` to know which device and config were chosen.
     pub fn try_default() -> Result<(Self, OutputStreamHandle), StreamError> {
         let default_device = cpal::default_host()
             .default_output_device()
@@ -62,15 +143,431 @@ impl OutputStream {
     }
 }
 
+impl OutputStream {
+    /// Returns the name of the device the stream plays on.
+    ///
+    /// This changes if the device recovery moves the stream to another device.
+    pub fn device_name(&self) -> String {
+        self.shared.device.lock().unwrap().name.clone()
+    }
+
+    /// Returns the config of the stream: its channels, sample rate and buffer size.
+    #[inline]
+    pub fn config(&self) -> &cpal::StreamConfig {
+        &self.shared.config
+    }
+
+    /// Returns the format of the samples sent to the device.
+    pub fn sample_format(&self) -> cpal::SampleFormat {
+        self.shared.device.lock().unwrap().sample_format
+    }
+
+    /// Sets the function called when an error occurs on the stream, for example when the device
+    /// is unplugged. By default the errors are printed to the standard error.
+    ///
+    /// The callback is called by the audio thread, and shouldn't block.
+    pub fn set_error_callback<F>(&self, callback: F)
+    where
+        F: FnMut(cpal::StreamError) + Send + 'static,
+    {
+        *self.shared.error_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Enables or disables the limiter of the mixer, which is disabled by default.
+    ///
+    /// When enabled, the volume of the mix is reduced when it gets close to the maximum, so that
+    /// many loud sounds playing at the same time don't clip.
+    #[inline]
+    pub fn set_limiter(&self, enabled: bool) {
+        self.shared.controller.set_limiter(enabled);
+    }
+
+    /// Sets the algorithm used to convert the sources to the sample rate of the device. Only
+    /// affects the sources played from now on. `ResampleQuality::Linear` by default.
+    #[inline]
+    pub fn set_resample_quality(&self, quality: ResampleQuality) {
+        self.shared.controller.set_resample_quality(quality);
+    }
+
+    /// Sets how the channels of the sources are converted to the channels of the device. Only
+    /// affects the sources played from now on. `ChannelMixing::Simple` by default.
+    #[inline]
+    pub fn set_channel_mixing(&self, mixing: ChannelMixing) {
+        self.shared.controller.set_channel_mixing(mixing);
+    }
+
+    /// Enables or disables the device recovery, which is disabled by default.
+    ///
+    /// When enabled, if the device becomes unavailable the sounds that are playing move to the
+    /// default output device once one can be opened, with the same channels and sample rate.
+    /// Sinks and `OutputStreamHandle`s keep working. The error is still reported to the error
+    /// callback.
+    pub fn set_device_recovery(&self, enabled: bool) {
+        let mut recovery = self.shared.recovery.lock().unwrap();
+        match (enabled, recovery.take()) {
+            (true, None) => {
+                let (tx, rx) = mpsc::channel();
+                let shared = self.shared.clone();
+                thread::spawn(move || run_device_recovery(shared, rx));
+                *recovery = Some(tx);
+            }
+            (true, Some(tx)) => *recovery = Some(tx),
+            (false, Some(tx)) => {
+                let _ = tx.send(RecoveryEvent::Stop);
+            }
+            (false, None) => (),
+        }
+    }
+}
+
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        self.set_device_recovery(false);
+    }
+}
+
+impl StreamShared {
+    fn new(
+        config: cpal::StreamConfig,
+        device_name: String,
+        sample_format: cpal::SampleFormat,
+    ) -> Arc<StreamShared> {
+        let (controller, mixer) =
+            dynamic_mixer::mixer::<f32>(config.channels, config.sample_rate.0);
+        Arc::new(StreamShared {
+            controller,
+            mixer: Mutex::new(mixer),
+            config,
+            device: Mutex::new(ActiveDevice {
+                name: device_name,
+                sample_format,
+            }),
+            error_callback: Mutex::new(None),
+            recovery: Mutex::new(None),
+        })
+    }
+
+    /// Fills the buffer of the device with the output of the mixer.
+    fn fill<T>(&self, data: &mut [T])
+    where
+        T: Sample,
+    {
+        // Never block the audio thread. The mixer is only locked by another stream for a short
+        // time, when the device recovery switches to a new device.
+        match self.mixer.try_lock() {
+            Ok(mut mixer) => data
+                .iter_mut()
+                .for_each(|d| *d = T::from(&mixer.next().unwrap_or(0f32))),
+            Err(_) => data.iter_mut().for_each(|d| *d = T::from(&0f32)),
+        }
+    }
+
+    fn on_error(&self, err: cpal::StreamError) {
+        if let cpal::StreamError::DeviceNotAvailable = err {
+            if let Some(recovery) = &*self.recovery.lock().unwrap() {
+                let _ = recovery.send(RecoveryEvent::DeviceLost);
+            }
+        }
+
+        match &mut *self.error_callback.lock().unwrap() {
+            Some(callback) => callback(err),
+            None => eprintln!("an error occurred on output stream: {}", err),
+        }
+    }
+}
+
+/// Opens a stream on the default output device for the mixer of a lost device, until asked to
+/// stop.
+///
+/// The new stream is owned by this thread because cpal streams can't be sent to another thread
+/// on every platform.
+fn run_device_recovery(shared: Arc<StreamShared>, events: Receiver<RecoveryEvent>) {
+    let mut stream: Option<cpal::Stream> = None;
+    let mut lost = false;
+    loop {
+        let event = if lost {
+            events.recv_timeout(RECOVERY_RETRY_INTERVAL)
+        } else {
+            events.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        match event {
+            Ok(RecoveryEvent::DeviceLost) => {
+                drop(stream.take());
+                lost = true;
+            }
+            Ok(RecoveryEvent::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => (),
+        }
+
+        if lost {
+            stream = open_default_device(&shared);
+            lost = stream.is_none();
+        }
+    }
+}
+
+fn open_default_device(shared: &Arc<StreamShared>) -> Option<cpal::Stream> {
+    let device = cpal::default_host().default_output_device()?;
+    let sample_format = device.default_output_config().ok()?.sample_format();
+    let stream = device
+        .build_mixer_stream(&shared.config, sample_format, shared.clone())
+        .ok()?;
+    stream.play().ok()?;
+    *shared.device.lock().unwrap() = ActiveDevice {
+        name: device.name().unwrap_or_default(),
+        sample_format,
+    };
+    Some(stream)
+}
+
+/// Opens an `OutputStream` with the given characteristics.
+///
+/// The characteristics that aren't set are taken from the default config of the device. If the
+/// sample format isn't set, the preferred one among the configs of the device that support the
+/// requested channels and sample rate is used.
+///
+/// ```no_run
+/// use rodio::{BufferSize, OutputStreamBuilder};
+///
+/// let (_stream, handle) = OutputStreamBuilder::new()
+///     .with_sample_rate(96_000)
+///     .with_buffer_size(BufferSize::Fixed(256))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct OutputStreamBuilder {
+    device: Option<cpal::Device>,
+    channels: Option<u16>,
+    sample_rate: Option<u32>,
+    sample_format: Option<cpal::SampleFormat>,
+    buffer_size: Option<cpal::BufferSize>,
+    limiter: bool,
+    resample_quality: ResampleQuality,
+    channel_mixing: ChannelMixing,
+}
+
+impl OutputStreamBuilder {
+    /// Builds a new `OutputStreamBuilder` for the default output device.
+    #[inline]
+    pub fn new() -> OutputStreamBuilder {
+        OutputStreamBuilder::default()
+    }
+
+    /// Sets the device to open instead of the default output device.
+    #[inline]
+    pub fn with_device(mut self, device: cpal::Device) -> OutputStreamBuilder {
+        self.device = Some(device);
+        self
+    }
+
+    /// Sets the number of channels of the stream.
+    #[inline]
+    pub fn with_channels(mut self, channels: u16) -> OutputStreamBuilder {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Sets the sample rate of the stream.
+    #[inline]
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> OutputStreamBuilder {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Sets the format of the samples sent to the device.
+    #[inline]
+    pub fn with_sample_format(mut self, sample_format: cpal::SampleFormat) -> OutputStreamBuilder {
+        self.sample_format = Some(sample_format);
+        self
+    }
+
+    /// Sets the size of the buffer of the device, which determines the latency.
+    #[inline]
+    pub fn with_buffer_size(mut self, buffer_size: cpal::BufferSize) -> OutputStreamBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Enables the limiter of the mixer. See `OutputStream::set_limiter`.
+    #[inline]
+    pub fn with_limiter(mut self, enabled: bool) -> OutputStreamBuilder {
+        self.limiter = enabled;
+        self
+    }
+
+    /// Sets the resampling quality of the mixer. See `OutputStream::set_resample_quality`.
+    #[inline]
+    pub fn with_resample_quality(mut self, quality: ResampleQuality) -> OutputStreamBuilder {
+        self.resample_quality = quality;
+        self
+    }
+
+    /// Sets the channel mixing of the mixer. See `OutputStream::set_channel_mixing`.
+    #[inline]
+    pub fn with_channel_mixing(mut self, mixing: ChannelMixing) -> OutputStreamBuilder {
+        self.channel_mixing = mixing;
+        self
+    }
+
+    /// Opens the stream. Fails if the device doesn't support the requested characteristics.
+    pub fn build(mut self) -> Result<(OutputStream, OutputStreamHandle), StreamError> {
+        let device = match self.device.take() {
+            Some(device) => device,
+            None => cpal::default_host()
+                .default_output_device()
+                .ok_or(StreamError::NoDevice)?,
+        };
+        let (config, sample_format) = self.resolve_config(&device)?;
+        let (stream, handle) =
+            OutputStream::try_from_device_config_and_format(&device, &config, sample_format)?;
+        stream.set_limiter(self.limiter);
+        stream.set_resample_quality(self.resample_quality);
+        stream.set_channel_mixing(self.channel_mixing);
+        Ok((stream, handle))
+    }
+
+    /// Completes the requested characteristics with the configs of the device.
+    fn resolve_config(
+        &self,
+        device: &cpal::Device,
+    ) -> Result<(cpal::StreamConfig, cpal::SampleFormat), StreamError> {
+        let default = device.default_output_config()?;
+        let channels = self.channels.unwrap_or_else(|| default.channels());
+        let sample_rate = cpal::SampleRate(self.sample_rate.unwrap_or(default.sample_rate().0));
+
+        let sample_format = match self.sample_format {
+            Some(sample_format) => sample_format,
+            None if channels == default.channels() && sample_rate == default.sample_rate() => {
+                default.sample_format()
+            }
+            None => {
+                let mut supported: Vec<_> = device
+                    .supported_output_configs()?
+                    .filter(|c| {
+                        c.channels() == channels
+                            && c.min_sample_rate() <= sample_rate
+                            && sample_rate <= c.max_sample_rate()
+                    })
+                    .collect();
+                supported.sort_by(|a, b| b.cmp_default_heuristics(a));
+                supported
+                    .first()
+                    .map_or(default.sample_format(), |c| c.sample_format())
+            }
+        };
+
+        let config = cpal::StreamConfig {
+            channels,
+            sample_rate,
+            buffer_size: self
+                .buffer_size
+                .clone()
+                .unwrap_or(cpal::BufferSize::Default),
+        };
+        Ok((config, sample_format))
+    }
+}
+
+/// Output stream that isn't connected to a device. Also see `OutputStreamHandle`.
+///
+/// Nothing is played until the samples are requested with `render`, from the caller's thread.
+/// This makes it possible to use a `Sink` or a `SpatialSink` in tests, or to render a mix to a
+/// file, faster than real time and on machines without audio hardware. Keep in mind that
+/// `Sink::sleep_until_end` blocks forever if nothing is rendered meanwhile.
+///
+/// If this is dropped the attached `OutputStreamHandle`s will no longer work.
+pub struct OfflineOutputStream {
+    controller: Arc<DynamicMixerController<f32>>,
+    mixer: DynamicMixer<f32>,
+}
+
+impl OfflineOutputStream {
+    /// Returns a new offline stream & handle producing samples with the given characteristics.
+    pub fn new(channels: u16, sample_rate: u32) -> (Self, OutputStreamHandle) {
+        let (controller, mixer) = dynamic_mixer::mixer::<f32>(channels, sample_rate);
+        let handle = OutputStreamHandle {
+            mixer: Arc::downgrade(&controller),
+        };
+        (Self { controller, mixer }, handle)
+    }
+
+    /// Enables or disables the limiter of the mixer. See `OutputStream::set_limiter`.
+    #[inline]
+    pub fn set_limiter(&self, enabled: bool) {
+        self.controller.set_limiter(enabled);
+    }
+
+    /// Sets the resampling quality of the mixer. See `OutputStream::set_resample_quality`.
+    #[inline]
+    pub fn set_resample_quality(&self, quality: ResampleQuality) {
+        self.controller.set_resample_quality(quality);
+    }
+
+    /// Sets the channel mixing of the mixer. See `OutputStream::set_channel_mixing`.
+    #[inline]
+    pub fn set_channel_mixing(&self, mixing: ChannelMixing) {
+        self.controller.set_channel_mixing(mixing);
+    }
+
+    /// Returns the number of channels of the samples produced by `render`.
+    #[inline]
+    pub fn channels(&self) -> u16 {
+        self.mixer.channels()
+    }
+
+    /// Returns the sample rate of the samples produced by `render`.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.mixer.sample_rate()
+    }
+
+    /// Fills `buffer` with the next interleaved samples of the mix, exactly as an output device
+    /// would. The buffer should contain a whole number of frames.
+    pub fn render(&mut self, buffer: &mut [f32]) {
+        buffer
+            .iter_mut()
+            .for_each(|d| *d = self.mixer.next().unwrap_or(0f32))
+    }
+
+    /// Renders the next `duration` of the mix, rounded down to a whole number of frames.
+    pub fn render_duration(&mut self, duration: Duration) -> Vec<f32> {
+        let frames = duration.as_secs_f64() * self.sample_rate() as f64;
+        let mut buffer = vec![0.0; frames as usize * self.channels() as usize];
+        self.render(&mut buffer);
+        buffer
+    }
+
+    /// Renders the next `duration` of the mix to a 32 bit float WAV file at `path`.
+    #[cfg(feature = "wav")]
+    pub fn render_to_wav<P>(&mut self, duration: Duration, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let samples = self.render_duration(duration);
+        let source = SamplesBuffer::new(self.channels(), self.sample_rate(), samples);
+        crate::output_to_wav_with_format(source, path, crate::WavSampleFormat::F32)
+    }
+}
+
 impl OutputStreamHandle {
-    /// Plays a source with a device until it ends.
-    pub fn play_raw<S>(&self, source: S) -> Result<(), PlayError>
+    /// Plays a source with a device until it ends. Returns a handle to stop, pause or change the
+    /// volume of the sound.
+    pub fn play_raw<S>(&self, source: S) -> Result<SoundHandle, PlayError>
     where
         S: Source<Item = f32> + Send + 'static,
     {
-        let mixer = self.mixer.upgrade().ok_or(PlayError::NoDevice)?;
-        mixer.add(source);
-        Ok(())
+        let mixer = self.mixer().ok_or(PlayError::NoDevice)?;
+        Ok(mixer.add(source))
+    }
+
+    /// Returns the mixer of the stream, or `None` if the stream has been dropped. Drops the
+    /// sounds that ended meanwhile.
+    #[inline]
+    pub(crate) fn mixer(&self) -> Option<Arc<DynamicMixerController<f32>>> {
+        let mixer = self.mixer.upgrade()?;
+        mixer.collect_finished();
+        Some(mixer)
     }
 
     /// Plays a sound once. Returns a `Sink` that can be used to control the sound.
@@ -124,6 +621,8 @@ pub enum StreamError {
     DefaultStreamConfigError(cpal::DefaultStreamConfigError),
     BuildStreamError(cpal::BuildStreamError),
     SupportedStreamConfigsError(cpal::SupportedStreamConfigsError),
+    /// The devices of the host couldn't be listed.
+    DevicesError(cpal::DevicesError),
     NoDevice,
 }
 
@@ -145,6 +644,12 @@ impl From<cpal::BuildStreamError> for StreamError {
     }
 }
 
+impl From<cpal::DevicesError> for StreamError {
+    fn from(err: cpal::DevicesError) -> Self {
+        Self::DevicesError(err)
+    }
+}
+
 impl From<cpal::PlayStreamError> for StreamError {
     fn from(err: cpal::PlayStreamError) -> Self {
         Self::PlayStreamError(err)
@@ -158,6 +663,7 @@ impl fmt::Display for StreamError {
             Self::BuildStreamError(e) => e.fmt(f),
             Self::DefaultStreamConfigError(e) => e.fmt(f),
             Self::SupportedStreamConfigsError(e) => e.fmt(f),
+            Self::DevicesError(e) => e.fmt(f),
             Self::NoDevice => write!(f, "NoDevice"),
         }
     }
@@ -170,6 +676,7 @@ impl error::Error for StreamError {
             Self::BuildStreamError(e) => Some(e),
             Self::DefaultStreamConfigError(e) => Some(e),
             Self::SupportedStreamConfigsError(e) => Some(e),
+            Self::DevicesError(e) => Some(e),
             Self::NoDevice => None,
         }
     }
@@ -180,66 +687,79 @@ pub(crate) trait CpalDeviceExt {
     fn new_output_stream_with_format(
         &self,
         format: cpal::SupportedStreamConfig,
-    ) -> Result<(Arc<DynamicMixerController<f32>>, cpal::Stream), cpal::BuildStreamError>;
+    ) -> Result<(Arc<StreamShared>, cpal::Stream), cpal::BuildStreamError>;
 
-    fn try_new_output_stream(
+    fn new_output_stream_with_config(
         &self,
-    ) -> Result<(Arc<DynamicMixerController<f32>>, cpal::Stream), StreamError>;
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+    ) -> Result<(Arc<StreamShared>, cpal::Stream), cpal::BuildStreamError>;
+
+    fn build_mixer_stream(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+        shared: Arc<StreamShared>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>;
+
+    fn try_new_output_stream(&self) -> Result<(Arc<StreamShared>, cpal::Stream), StreamError>;
 }
 
 impl CpalDeviceExt for cpal::Device {
     fn new_output_stream_with_format(
         &self,
         format: cpal::SupportedStreamConfig,
-    ) -> Result<(Arc<DynamicMixerController<f32>>, cpal::Stream), cpal::BuildStreamError> {
-        let (mixer_tx, mut mixer_rx) =
-            dynamic_mixer::mixer::<f32>(format.channels(), format.sample_rate().0);
+    ) -> Result<(Arc<StreamShared>, cpal::Stream), cpal::BuildStreamError> {
+        self.new_output_stream_with_config(&format.config(), format.sample_format())
+    }
+
+    fn new_output_stream_with_config(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+    ) -> Result<(Arc<StreamShared>, cpal::Stream), cpal::BuildStreamError> {
+        let name = self.name().unwrap_or_default();
+        let shared = StreamShared::new(config.clone(), name, sample_format);
+        self.build_mixer_stream(config, sample_format, shared.clone())
+            .map(|stream| (shared, stream))
+    }
 
-        let error_callback = |err| eprintln!("an error occurred on output stream: {}", err);
+    fn build_mixer_stream(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+        shared: Arc<StreamShared>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError> {
+        let error_shared = shared.clone();
+        let error_callback = move |err| error_shared.on_error(err);
 
-        match format.sample_format() {
+        match sample_format {
             cpal::SampleFormat::F32 => self.build_output_stream::<f32, _, _>(
-                &format.config(),
-                move |data, _| {
-                    data.iter_mut()
-                        .for_each(|d| *d = mixer_rx.next().unwrap_or(0f32))
-                },
+                config,
+                move |data, _| shared.fill(data),
                 error_callback,
             ),
             cpal::SampleFormat::I16 => self.build_output_stream::<i16, _, _>(
-                &format.config(),
-                move |data, _| {
-                    data.iter_mut()
-                        .for_each(|d| *d = mixer_rx.next().map(|s| s.to_i16()).unwrap_or(0i16))
-                },
+                config,
+                move |data, _| shared.fill(data),
                 error_callback,
             ),
             cpal::SampleFormat::U16 => self.build_output_stream::<u16, _, _>(
-                &format.config(),
-                move |data, _| {
-                    data.iter_mut().for_each(|d| {
-                        *d = mixer_rx
-                            .next()
-                            .map(|s| s.to_u16())
-                            .unwrap_or(u16::max_value() / 2)
-                    })
-                },
+                config,
+                move |data, _| shared.fill(data),
                 error_callback,
             ),
         }
-        .map(|stream| (mixer_tx, stream))
     }
 
-    fn try_new_output_stream(
-        &self,
-    ) -> Result<(Arc<DynamicMixerController<f32>>, cpal::Stream), StreamError> {
+    fn try_new_output_stream(&self) -> Result<(Arc<StreamShared>, cpal::Stream), StreamError> {
         // Determine the format to use for the new stream.
         let default_format = self.default_output_config()?;
 
         self.new_output_stream_with_format(default_format)
             .or_else(|err| {
                 // look through all supported formats to see if another works
-                supported_output_formats(self)?
+                supported_formats(self.supported_output_configs()?.collect())
                 .filter_map(|format| self.new_output_stream_with_format(format).ok())
                 .next()
                 // return original error if nothing works
@@ -248,16 +768,15 @@ impl CpalDeviceExt for cpal::Device {
     }
 }
 
-/// All the supported output formats with sample rates
-fn supported_output_formats(
-    device: &cpal::Device,
-) -> Result<impl Iterator<Item = cpal::SupportedStreamConfig>, StreamError> {
+/// All the formats with sample rates of the given supported configs, best first.
+pub(crate) fn supported_formats(
+    mut supported: Vec<cpal::SupportedStreamConfigRange>,
+) -> impl Iterator<Item = cpal::SupportedStreamConfig> {
     const HZ_44100: cpal::SampleRate = cpal::SampleRate(44_100);
 
-    let mut supported: Vec<_> = device.supported_output_configs()?.collect();
     supported.sort_by(|a, b| b.cmp_default_heuristics(a));
 
-    Ok(supported.into_iter().flat_map(|sf| {
+    supported.into_iter().flat_map(|sf| {
         let max_rate = sf.max_sample_rate();
         let min_rate = sf.min_sample_rate();
         let mut formats = vec![sf.clone().with_max_sample_rate()];
@@ -266,5 +785,63 @@ fn supported_output_formats(
         }
         formats.push(sf.with_sample_rate(min_rate));
         formats
-    }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+
+    use super::{RecoveryEvent, StreamShared};
+    use crate::source::{SineWave, Source};
+
+    fn shared() -> Arc<StreamShared> {
+        let config = cpal::StreamConfig {
+            channels: 2,
+            sample_rate: cpal::SampleRate(44100),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        StreamShared::new(config, "test".to_owned(), cpal::SampleFormat::F32)
+    }
+
+    #[test]
+    fn fill_converts_mixer_output() {
+        let shared = shared();
+        shared.controller.add(SineWave::new(440.0).amplify(0.0));
+        let mut data = [1234i16; 8];
+        shared.fill(&mut data);
+        assert_eq!(data, [0; 8]);
+
+        // The mixer is in use by another stream.
+        let _lock = shared.mixer.lock().unwrap();
+        let mut data = [1.0f32; 8];
+        shared.fill(&mut data);
+        assert_eq!(data, [0.0; 8]);
+    }
+
+    #[test]
+    fn errors_reach_callback_and_recovery() {
+        let shared = shared();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let callback_errors = errors.clone();
+        *shared.error_callback.lock().unwrap() = Some(Box::new(move |err| {
+            callback_errors.lock().unwrap().push(format!("{}", err))
+        }));
+        let (tx, rx) = mpsc::channel();
+        *shared.recovery.lock().unwrap() = Some(tx);
+
+        shared.on_error(cpal::StreamError::DeviceNotAvailable);
+        assert_eq!(errors.lock().unwrap().len(), 1);
+        assert!(matches!(rx.try_recv(), Ok(RecoveryEvent::DeviceLost)));
+    }
+
+    #[test]
+    fn unknown_device_name() {
+        let name = "rodio test device that doesn't exist";
+        assert!(crate::output_devices()
+            .map(|devices| devices.iter().all(|d| d.name() != name))
+            .unwrap_or(true));
+        assert!(super::OutputStream::try_from_device_name(name).is_err());
+    }
 }
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::Path;
+
+use crate::source::UniformSourceIterator;
+use crate::{Sample, Source};
+use cpal::Sample as CpalSample;
+
+/// Format of the samples of a WAV file written by `output_to_wav_with_format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavSampleFormat {
+    /// 16 bit integers.
+    I16,
+    /// 24 bit integers.
+    I24,
+    /// 32 bit floats.
+    F32,
+}
+
+impl Default for WavSampleFormat {
+    #[inline]
+    fn default() -> WavSampleFormat {
+        WavSampleFormat::I16
+    }
+}
+
+/// Writes all the samples of `source` to a 16 bit WAV file at `path`.
+///
+/// The source must be finite, otherwise this function never returns. Use `take_duration` to
+/// render a part of an infinite source.
+///
+/// The channels and sample rate of the file are the ones of `source` when this function is
+/// called. If they change later, the rest of the source is converted to them.
+#[inline]
+pub fn output_to_wav<S, P>(source: S, path: P) -> io::Result<()>
+where
+    S: Source,
+    S::Item: Sample,
+    P: AsRef<Path>,
+{
+    output_to_wav_with_format(source, path, WavSampleFormat::default())
+}
+
+/// Writes all the samples of `source` to a WAV file at `path`, in the given sample format.
+///
+/// See `output_to_wav`.
+pub fn output_to_wav_with_format<S, P>(
+    source: S,
+    path: P,
+    format: WavSampleFormat,
+) -> io::Result<()>
+where
+    S: Source,
+    S::Item: Sample,
+    P: AsRef<Path>,
+{
+    let writer = BufWriter::new(File::create(path)?);
+    output_to_wav_writer(source, writer, format)
+}
+
+/// Writes all the samples of `source` as a WAV file to `writer`, in the given sample format.
+///
+/// The writer must be seekable, as the size of the data is written in the header once all the
+/// samples have been written. To produce a WAV file in memory, use a `std::io::Cursor`.
+///
+/// See `output_to_wav`.
+pub fn output_to_wav_writer<S, W>(source: S, writer: W, format: WavSampleFormat) -> io::Result<()>
+where
+    S: Source,
+    S::Item: Sample,
+    W: Write + Seek,
+{
+    let channels = source.channels();
+    let sample_rate = source.sample_rate();
+    let (bits_per_sample, sample_format) = match format {
+        WavSampleFormat::I16 => (16, hound::SampleFormat::Int),
+        WavSampleFormat::I24 => (24, hound::SampleFormat::Int),
+        WavSampleFormat::F32 => (32, hound::SampleFormat::Float),
+    };
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    };
+
+    let mut wav = hound::WavWriter::new(writer, spec).map_err(into_io_error)?;
+    let samples: UniformSourceIterator<S, f32> =
+        UniformSourceIterator::new(source, channels, sample_rate);
+    for sample in samples {
+        match format {
+            WavSampleFormat::I16 => wav.write_sample(sample.to_i16()),
+            WavSampleFormat::I24 => wav.write_sample(to_i24(sample)),
+            WavSampleFormat::F32 => wav.write_sample(sample),
+        }
+        .map_err(into_io_error)?;
+    }
+    wav.finalize().map_err(into_io_error)
+}
+
+/// Converts a sample to a 24 bit integer, stored in an `i32`.
+#[inline]
+fn to_i24(sample: f32) -> i32 {
+    const MAX: f32 = 8_388_607.0;
+    (sample.clamp(-1.0, 1.0) * MAX).round() as i32
+}
+
+/// Hound only fails with something else than an I/O error if the spec is invalid.
+fn into_io_error(err: hound::Error) -> io::Error {
+    match err {
+        hound::Error::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidInput, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use super::{output_to_wav_writer, WavSampleFormat};
+    use crate::buffer::SamplesBuffer;
+    use crate::source::{SineWave, Source};
+
+    fn render<S>(source: S, format: WavSampleFormat) -> hound::WavReader<Cursor<Vec<u8>>>
+    where
+        S: Source,
+        S::Item: crate::Sample,
+    {
+        let mut data = Cursor::new(Vec::new());
+        output_to_wav_writer(source, &mut data, format).unwrap();
+        data.set_position(0);
+        hound::WavReader::new(data).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        let source = || SamplesBuffer::new(2, 44100, vec![0.0f32, 0.5, -1.0, 1.0]);
+
+        let mut reader = render(source(), WavSampleFormat::I16);
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 44100);
+        assert_eq!(reader.spec().bits_per_sample, 16);
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, vec![0, 16383, -32768, 32767]);
+
+        let mut reader = render(source(), WavSampleFormat::I24);
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        let samples: Vec<i32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, vec![0, 4_194_304, -8_388_607, 8_388_607]);
+
+        let mut reader = render(source(), WavSampleFormat::F32);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Float);
+        let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, vec![0.0, 0.5, -1.0, 1.0]);
+    }
+
+    #[test]
+    fn format_change() {
+        // One second at 48kHz, then one second in stereo at 96kHz.
+        let first = SineWave::new(440.0).take_duration(Duration::from_secs(1));
+        let second = SamplesBuffer::new(2, 96000, vec![0.0f32; 192000]);
+        let (tx, rx) = crate::queue::queue(false);
+        tx.append(first);
+        tx.append(second);
+
+        let reader = render(rx, WavSampleFormat::I16);
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.spec().sample_rate, 48000);
+        assert_eq!(reader.duration(), 96000);
+    }
+}
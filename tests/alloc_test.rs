@@ -0,0 +1,179 @@
+//! Checks that the audio thread never allocates nor frees memory, which could block it.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::Duration;
+
+use rodio::buffer::SamplesBuffer;
+use rodio::source::{SineWave, Source};
+use rodio::{ChannelMixing, OfflineOutputStream, ResampleQuality, Sink, SpatialSink};
+
+/// Counts the allocations and deallocations of the threads that are rendering.
+struct CountingAllocator;
+
+thread_local! {
+    static RENDERING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    // Accessing the thread locals doesn't allocate, because they are const and have no destructor.
+    if RENDERING.with(Cell::get) {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count();
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Renders `duration` of the stream, and returns the number of allocations and deallocations.
+fn render(stream: &mut OfflineOutputStream, buffer: &mut [f32], duration: Duration) -> usize {
+    let frames = duration.as_secs_f64() * stream.sample_rate() as f64;
+    let buffers = (frames as usize * stream.channels() as usize).max(1) / buffer.len();
+
+    ALLOCATIONS.with(|a| a.set(0));
+    RENDERING.with(|r| r.set(true));
+    for _ in 0..buffers {
+        stream.render(buffer);
+    }
+    RENDERING.with(|r| r.set(false));
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn sink() {
+    let (mut stream, handle) = OfflineOutputStream::new(2, 44100);
+    let mut buffer = vec![0.0; 2 * 512];
+    let sink = Sink::try_new(&handle).unwrap();
+    sink.append(SineWave::new(440.0).take_duration(Duration::from_millis(200)));
+    sink.append(SamplesBuffer::new(1, 48000, vec![0.5f32; 4800]));
+    sink.append(SamplesBuffer::new(2, 22050, vec![0.5f32; 2 * 2205]));
+    let ten_ms = Duration::from_millis(10);
+
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "start");
+    sink.set_volume(0.5);
+    sink.pause();
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "pause");
+    sink.play();
+    sink.set_speed(1.5);
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "speed");
+    sink.set_playback_rate(0.8);
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "rate");
+    // The next sounds start, with other channels and sample rates.
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(400)),
+        0,
+        "next"
+    );
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(200)),
+        0,
+        "end"
+    );
+    assert!(sink.empty());
+    sink.sleep_until_end();
+
+    sink.append(SineWave::new(440.0));
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "append");
+    sink.stop();
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "stop");
+
+    // The filters and matrices of the sounds are computed when they are appended.
+    let (mut stream, handle) = OfflineOutputStream::new(2, 44100);
+    stream.set_resample_quality(ResampleQuality::Sinc);
+    stream.set_channel_mixing(ChannelMixing::Standard);
+    let sink = Sink::try_new(&handle).unwrap();
+    sink.append(SamplesBuffer::new(1, 48000, vec![0.5f32; 4800]));
+    sink.append(SamplesBuffer::new(6, 22050, vec![0.5f32; 6 * 2205]));
+    sink.append(SamplesBuffer::new(2, 32000, vec![0.5f32; 2 * 3200]));
+
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "sinc start");
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(400)),
+        0,
+        "sinc next"
+    );
+    assert!(sink.empty());
+}
+
+#[test]
+fn spatial_sink() {
+    let (mut stream, handle) = OfflineOutputStream::new(2, 48000);
+    let mut buffer = vec![0.0; 2 * 256];
+    let sink =
+        SpatialSink::try_new(&handle, [-1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]).unwrap();
+    sink.append(SineWave::new(440.0).take_duration(Duration::from_millis(100)));
+
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(50)),
+        0
+    );
+    sink.set_emitter_position([1.0, 0.0, 0.0]);
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(100)),
+        0
+    );
+}
+
+#[test]
+fn play_raw() {
+    let (mut stream, handle) = OfflineOutputStream::new(2, 48000);
+    let mut buffer = vec![0.0; 2 * 256];
+    let first = handle.play_raw(SineWave::new(440.0)).unwrap();
+    let second = handle
+        .play_raw(SamplesBuffer::new(2, 48000, vec![0.25f32; 2 * 2400]))
+        .unwrap();
+    let ten_ms = Duration::from_millis(10);
+
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "start");
+    first.set_volume(0.5);
+    first.pause();
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "pause");
+    first.stop();
+    assert_eq!(render(&mut stream, &mut buffer, ten_ms), 0, "stop");
+    assert!(first.is_finished());
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(100)),
+        0,
+        "end"
+    );
+    assert!(second.is_finished());
+}
+
+#[test]
+fn sinc_resampling() {
+    let (mut stream, handle) = OfflineOutputStream::new(2, 48000);
+    stream.set_resample_quality(ResampleQuality::Sinc);
+    let mut buffer = vec![0.0; 2 * 480];
+    let samples: Vec<f32> = (0..44100).map(|i| (i as f32 * 0.05).sin()).collect();
+    let sound = handle
+        .play_raw(SamplesBuffer::new(1, 44100, samples))
+        .unwrap();
+
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(900)),
+        0
+    );
+    assert_eq!(
+        render(&mut stream, &mut buffer, Duration::from_millis(200)),
+        0
+    );
+    assert!(sound.is_finished());
+}
@@ -1,7 +1,5 @@
-#[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
 use rodio::Source;
 use std::io::BufReader;
-#[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
 use std::time::Duration;
 
 #[test]
@@ -11,8 +9,6 @@ fn test_flac_encodings() {
     let mut decoder = rodio::Decoder::new(BufReader::new(file)).unwrap();
     // File is not just silence
     assert!(decoder.any(|x| x != 0));
-    // Symphonia does not expose functionality to get the duration so this check must be disabled
-    #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
     assert_eq!(decoder.total_duration(), Some(Duration::from_secs(3))); // duration is calculated correctly
 
     // 24 bit FLAC file exported from Audacity (2 channels, various compression levels)
@@ -21,7 +17,34 @@ fn test_flac_encodings() {
             std::fs::File::open(format!("assets/audacity24bit_level{}.flac", level)).unwrap();
         let mut decoder = rodio::Decoder::new(BufReader::new(file)).unwrap();
         assert!(decoder.any(|x| x != 0));
-        #[cfg(all(feature = "flac", not(feature = "symphonia-flac")))]
         assert_eq!(decoder.total_duration(), Some(Duration::from_secs(3)));
     }
 }
+
+#[test]
+fn test_flac_f32() {
+    // 24 bit FLAC keeps more precision than i16 when decoded to f32.
+    let file = std::fs::File::open("assets/audacity24bit_level8.flac").unwrap();
+    let decoder = rodio::Decoder::new(BufReader::new(file)).unwrap();
+    let file = std::fs::File::open("assets/audacity24bit_level8.flac").unwrap();
+    let decoder_f32 = rodio::Decoder::new_f32(BufReader::new(file)).unwrap();
+
+    let mut finer_than_i16 = false;
+    for (a, b) in decoder.zip(decoder_f32) {
+        assert!((-1.0..=1.0).contains(&b));
+        assert!((a as f32 / 32768.0 - b).abs() < 2.0 / 32768.0);
+        finer_than_i16 |= (b * 32768.0).fract() != 0.0;
+    }
+    assert!(finer_than_i16);
+}
+
+#[test]
+fn test_flac_channel_layout() {
+    let file = std::fs::File::open("assets/audacity16bit_level5.flac").unwrap();
+    let decoder = rodio::Decoder::new(BufReader::new(file)).unwrap();
+    assert_eq!(decoder.channels(), 1);
+    assert_eq!(
+        decoder.channel_layout(),
+        Some(rodio::source::ChannelLayout::MONO)
+    );
+}
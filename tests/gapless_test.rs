@@ -0,0 +1,44 @@
+use std::io::BufReader;
+
+use rodio::decoder::GaplessInfo;
+use rodio::{queue, Decoder};
+
+fn open(name: &str) -> Decoder<BufReader<std::fs::File>> {
+    let file = std::fs::File::open(name).unwrap();
+    Decoder::new(BufReader::new(file)).unwrap()
+}
+
+// The LAME tag of music.mp3 stores an encoder delay of 576 and a padding of 984, to which the
+// delay of the decoder is added.
+const MP3_GAPLESS: GaplessInfo = GaplessInfo {
+    delay: 576 + 529,
+    padding: 984 - 529,
+};
+// 390 frames of 1152 samples, without the delay and padding.
+const MP3_FRAMES: usize = 390 * 1152 - 576 - 984;
+
+#[test]
+fn gapless_mp3() {
+    let decoder = open("assets/music.mp3");
+    assert_eq!(decoder.gapless_info(), Some(MP3_GAPLESS));
+    assert_eq!(decoder.count(), MP3_FRAMES * 2);
+}
+
+#[test]
+fn gapless_none() {
+    assert_eq!(open("assets/music.wav").gapless_info(), None);
+    assert_eq!(open("assets/music.flac").gapless_info(), None);
+}
+
+#[test]
+fn gapless_queue() {
+    let (tx, rx) = queue::queue(false);
+    tx.append(open("assets/music.mp3"));
+    tx.append(open("assets/music.mp3"));
+
+    // The second track starts right after the last sample of the first one.
+    let track: Vec<i16> = open("assets/music.mp3").collect();
+    let samples: Vec<i16> = rx.collect();
+    assert_eq!(samples.len(), track.len() * 2);
+    assert_eq!(&samples[track.len()..], &track[..]);
+}
@@ -0,0 +1,37 @@
+use std::io::BufReader;
+
+use rodio::Decoder;
+
+fn open(name: &str) -> Decoder<BufReader<std::fs::File>> {
+    let file = std::fs::File::open(name).unwrap();
+    Decoder::new(BufReader::new(file)).unwrap()
+}
+
+#[test]
+fn metadata_tags() {
+    for name in &["assets/music.mp3", "assets/music.ogg", "assets/music.flac"] {
+        let decoder = open(name);
+        let metadata = decoder.metadata();
+        assert_eq!(
+            metadata.title(),
+            Some("Corelli Trio Sonata 11, m1"),
+            "{}",
+            name
+        );
+        assert_eq!(metadata.artist(), Some("RP and E Goldstein"), "{}", name);
+        assert!(metadata.pictures().is_empty());
+    }
+}
+
+#[test]
+fn metadata_encoder() {
+    let decoder = open("assets/music.mp3");
+    assert_eq!(decoder.metadata().get("encoder"), Some("Lavf58.12.100"));
+}
+
+#[test]
+fn metadata_empty() {
+    let decoder = open("assets/beep3.ogg");
+    assert!(decoder.metadata().tags().is_empty());
+    assert_eq!(decoder.metadata().title(), None);
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use rodio::source::{SineWave, Source};
+use rodio::{OfflineOutputStream, Sink, SpatialSink};
+
+#[test]
+fn sink_renders_offline() {
+    let (mut stream, handle) = OfflineOutputStream::new(2, 44100);
+    assert_eq!(stream.channels(), 2);
+    assert_eq!(stream.sample_rate(), 44100);
+
+    let sink = Sink::try_new(&handle).unwrap();
+    sink.append(SineWave::new(440.0).take_duration(Duration::from_millis(100)));
+    sink.set_volume(0.5);
+
+    let rendered = stream.render_duration(Duration::from_millis(200));
+    assert_eq!(rendered.len(), 2 * 8820);
+    let (sound, silence) = rendered.split_at(2 * 4410);
+    let peak = sound.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!(peak > 0.45 && peak <= 0.5, "peak: {}", peak);
+    // The resampler may output a couple of frames more than the sine wave.
+    assert!(silence[2 * 10..].iter().all(|&s| s == 0.0));
+    assert!(sink.empty());
+}
+
+#[test]
+fn spatial_sink_renders_offline() {
+    let (mut stream, handle) = OfflineOutputStream::new(2, 48000);
+    let sink =
+        SpatialSink::try_new(&handle, [-1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]).unwrap();
+    sink.append(SineWave::new(440.0).take_duration(Duration::from_millis(50)));
+
+    let mut buffer = [0.0; 2 * 2400];
+    stream.render(&mut buffer);
+    let left: f32 = buffer.iter().step_by(2).map(|s| s.abs()).sum();
+    let right: f32 = buffer.iter().skip(1).step_by(2).map(|s| s.abs()).sum();
+    assert!(left > right);
+}
+
+#[test]
+fn handle_fails_once_dropped() {
+    let (stream, handle) = OfflineOutputStream::new(1, 48000);
+    drop(stream);
+    assert!(Sink::try_new(&handle).is_err());
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn render_to_wav() {
+    let (mut stream, handle) = OfflineOutputStream::new(1, 48000);
+    handle.play_raw(SineWave::new(440.0)).unwrap();
+
+    let path = std::env::temp_dir().join("rodio_offline_test.wav");
+    stream
+        .render_to_wav(Duration::from_millis(500), &path)
+        .unwrap();
+    let reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.duration(), 24000);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn limiter_prevents_clipping() {
+    let (mut stream, handle) = OfflineOutputStream::new(1, 48000);
+    stream.set_limiter(true);
+
+    let sinks: Vec<Sink> = (0..3)
+        .map(|_| {
+            let sink = Sink::try_new(&handle).unwrap();
+            sink.append(SineWave::new(440.0).take_duration(Duration::from_millis(100)));
+            sink
+        })
+        .collect();
+
+    let rendered = stream.render_duration(Duration::from_millis(100));
+    let peak = rendered.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!(peak > 0.8 && peak < 1.0, "peak: {}", peak);
+    drop(sinks);
+}
+
+#[test]
+fn playback_rate_keeps_pitch() {
+    let (mut stream, handle) = OfflineOutputStream::new(1, 48000);
+    let sink = Sink::try_new(&handle).unwrap();
+    sink.set_playback_rate(2.0);
+    sink.append(SineWave::new(440.0).take_duration(Duration::from_secs(1)));
+
+    let rendered = stream.render_duration(Duration::from_millis(400));
+    let crossings = rendered
+        .windows(2)
+        .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+        .count();
+    // Still a 440Hz tone, with two zero crossings per period.
+    assert!(
+        (crossings as i32 - 352).abs() < 10,
+        "crossings: {}",
+        crossings
+    );
+
+    // One second of the sound plays in half a second.
+    stream.render_duration(Duration::from_millis(150));
+    assert!(sink.empty());
+}
+
+#[test]
+fn play_raw_handle() {
+    let (mut stream, handle) = OfflineOutputStream::new(1, 48000);
+    let sound = handle
+        .play_raw(SineWave::new(440.0).take_duration(Duration::from_secs(1)))
+        .unwrap();
+
+    sound.set_volume(0.25);
+    let rendered = stream.render_duration(Duration::from_millis(100));
+    let peak = rendered.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!((peak - 0.25).abs() < 0.01, "peak: {}", peak);
+
+    sound.stop();
+    let rendered = stream.render_duration(Duration::from_millis(10));
+    assert!(rendered.iter().all(|&s| s == 0.0));
+    assert!(sound.is_finished());
+}
@@ -0,0 +1,101 @@
+use rodio::source::SeekError;
+use rodio::{Decoder, Source};
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Duration;
+
+fn open(name: &str) -> Decoder<BufReader<File>> {
+    let file = File::open(format!("assets/{}", name)).unwrap();
+    Decoder::new(BufReader::new(file)).unwrap()
+}
+
+/// Returns the samples played after seeking to `pos`, compared with the samples found at the
+/// same position when decoding the whole file.
+fn seek_and_compare(name: &str, pos: Duration) {
+    let reference = open(name);
+    let offset = (pos.as_secs_f64() * reference.sample_rate() as f64) as usize
+        * reference.channels() as usize;
+    let expected: Vec<i16> = reference.skip(offset).take(1000).collect();
+
+    let mut decoder = open(name);
+    decoder.try_seek(pos).unwrap();
+    let actual: Vec<i16> = decoder.take(1000).collect();
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn seek_wav() {
+    seek_and_compare("music.wav", Duration::from_secs(3));
+}
+
+#[test]
+fn seek_flac() {
+    seek_and_compare("music.flac", Duration::from_millis(3_500));
+}
+
+// The symphonia mp3 decoder needs a few frames to settle after a seek, so
+// the samples only match approximately.
+#[cfg(all(feature = "mp3", not(feature = "symphonia-mp3")))]
+#[test]
+fn seek_mp3() {
+    seek_and_compare("music.mp3", Duration::from_millis(4_200));
+}
+
+#[test]
+fn seek_backwards() {
+    for name in &["music.wav", "music.flac", "music.mp3", "music.ogg"] {
+        let mut decoder = open(name);
+        let start: Vec<i16> = decoder.by_ref().take(5000).collect();
+
+        decoder.try_seek(Duration::from_secs(5)).unwrap();
+        assert!(decoder.next().is_some());
+
+        decoder.try_seek(Duration::from_secs(0)).unwrap();
+        let again: Vec<i16> = decoder.take(5000).collect();
+        assert_eq!(again, start, "{}", name);
+    }
+}
+
+#[test]
+#[cfg(not(feature = "symphonia"))]
+fn seek_past_end() {
+    for name in &["music.wav", "music.flac", "music.mp3"] {
+        let mut decoder = open(name);
+        decoder.try_seek(Duration::from_secs(60)).unwrap();
+        assert_eq!(decoder.next(), None, "{}", name);
+    }
+}
+
+#[test]
+fn seek_through_filters() {
+    let mut source = open("beep.wav")
+        .amplify(0.5)
+        .pausable(false)
+        .take_duration(Duration::from_secs(5));
+    source.try_seek(Duration::from_secs(4)).unwrap();
+
+    // About one second of mono 44.1 kHz audio remains.
+    let remaining = source.count();
+    assert!((44_090..44_110).contains(&remaining), "{}", remaining);
+}
+
+#[test]
+fn seek_not_supported() {
+    let mut source = open("beep.wav").buffered();
+    match source.try_seek(Duration::from_secs(1)) {
+        Err(SeekError::NotSupported { .. }) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn seek_with_invalid_speed() {
+    for &factor in &[-1.0, f32::NAN] {
+        let mut source = open("beep.wav").speed(factor);
+        match source.try_seek(Duration::from_secs(1)) {
+            Err(SeekError::NotSupported { .. }) => (),
+            other => panic!("unexpected result for {}: {:?}", factor, other),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::BufReader;
+use std::time::Duration;
+
+use rodio::{Decoder, Source};
+
+fn open(name: &str) -> Decoder<BufReader<std::fs::File>> {
+    let file = std::fs::File::open(name).unwrap();
+    Decoder::new(BufReader::new(file)).unwrap()
+}
+
+/// Checks that `total_duration` matches the duration of the samples that are decoded.
+fn assert_duration_matches(name: &str) {
+    let decoder = open(name);
+    let total_duration = decoder.total_duration().unwrap();
+    let channels = decoder.channels() as f64;
+    let sample_rate = decoder.sample_rate() as f64;
+    let decoded = Duration::from_secs_f64(decoder.count() as f64 / channels / sample_rate);
+
+    assert!(
+        total_duration.abs_diff(decoded) < Duration::from_millis(1),
+        "{}: {:?} != {:?}",
+        name,
+        total_duration,
+        decoded
+    );
+}
+
+#[test]
+fn total_duration_wav() {
+    assert_duration_matches("assets/music.wav");
+}
+
+#[test]
+fn total_duration_flac() {
+    assert_duration_matches("assets/music.flac");
+}
+
+#[test]
+fn total_duration_mp3() {
+    assert_duration_matches("assets/music.mp3");
+}
+
+// The symphonia ogg reader doesn't trim the last packet to the granule position of the last page,
+// so it decodes a few more samples than the container reports.
+#[cfg(all(feature = "vorbis", not(feature = "symphonia-vorbis")))]
+#[test]
+fn total_duration_ogg() {
+    assert_duration_matches("assets/music.ogg");
+    assert_duration_matches("assets/beep3.ogg");
+}
+
+#[cfg(feature = "symphonia-vorbis")]
+#[test]
+fn total_duration_ogg() {
+    let duration = open("assets/music.ogg").total_duration().unwrap();
+    assert_eq!(duration.as_secs(), 69);
+}
+
+// The AAC decoder doesn't remove the encoder delay, so only check the reported duration.
+#[cfg(feature = "symphonia-isomp4")]
+#[test]
+fn total_duration_m4a() {
+    let duration = open("assets/music.m4a").total_duration().unwrap();
+    assert_eq!(duration.as_millis(), 10188);
+}
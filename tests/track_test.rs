@@ -0,0 +1,46 @@
+use std::io::BufReader;
+
+use rodio::Decoder;
+
+fn open(name: &str) -> BufReader<std::fs::File> {
+    BufReader::new(std::fs::File::open(name).unwrap())
+}
+
+#[cfg(all(feature = "wav", not(feature = "symphonia-wav")))]
+#[test]
+fn tracks_single_stream() {
+    let decoder = Decoder::new(open("assets/beep.wav")).unwrap();
+    let tracks = decoder.tracks();
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(tracks[0].id(), 0);
+    assert_eq!(tracks[0].codec(), "pcm");
+    assert_eq!(tracks[0].channels(), Some(1));
+    assert_eq!(tracks[0].sample_rate(), Some(44100));
+    assert_eq!(tracks[0].language(), None);
+}
+
+#[cfg(all(feature = "symphonia-aac", feature = "symphonia-isomp4"))]
+#[test]
+fn tracks_mp4() {
+    let decoder = Decoder::new(open("assets/music.m4a")).unwrap();
+    let tracks = decoder.tracks();
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(tracks[0].codec(), "aac");
+    assert_eq!(tracks[0].channels(), Some(2));
+    assert_eq!(tracks[0].sample_rate(), Some(44100));
+
+    let mut decoder = Decoder::new_with_track(open("assets/music.m4a"), tracks[0].id()).unwrap();
+    assert_eq!(rodio::Source::channels(&decoder), 2);
+    assert!(decoder.next().is_some());
+}
+
+#[cfg(all(feature = "symphonia-aac", feature = "symphonia-isomp4"))]
+#[test]
+fn tracks_unknown_id() {
+    let id = Decoder::new(open("assets/music.m4a")).unwrap().tracks()[0].id();
+    match Decoder::new_with_track(open("assets/music.m4a"), id + 1) {
+        Err(rodio::decoder::DecoderError::TrackNotFound) => (),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a track that doesn't exist was decoded"),
+    }
+}
@@ -32,3 +32,22 @@ fn test_wav_encodings() {
     let mut decoder = rodio::Decoder::new(BufReader::new(file)).unwrap();
     assert!(decoder.any(|x| x != 0));
 }
+
+#[test]
+fn test_wav_f32() {
+    // 24 bit and 32 bit float wav files keep more precision than i16 when decoded to f32.
+    for name in &["assets/lmms24bit.wav", "assets/lmms32bit.wav"] {
+        let file = std::fs::File::open(name).unwrap();
+        let decoder = rodio::Decoder::new(BufReader::new(file)).unwrap();
+        let file = std::fs::File::open(name).unwrap();
+        let decoder_f32 = rodio::Decoder::new_f32(BufReader::new(file)).unwrap();
+
+        let mut finer_than_i16 = false;
+        for (a, b) in decoder.zip(decoder_f32) {
+            assert!((-1.0..=1.0).contains(&b));
+            assert!((a as f32 / 32768.0 - b).abs() < 2.0 / 32768.0);
+            finer_than_i16 |= (b * 32768.0).fract() != 0.0;
+        }
+        assert!(finer_than_i16);
+    }
+}